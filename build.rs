@@ -0,0 +1,27 @@
+fn main() {
+    #[cfg(feature = "generate-header")]
+    generate_header();
+}
+
+// Regenerates the C header of the API in `src/ffi.rs`. The header is committed, so it is only
+// written when `CHIPO_GENERATE_HEADER` is set, and builds never modify the source directory
+// otherwise.
+#[cfg(feature = "generate-header")]
+fn generate_header() {
+    println!("cargo:rerun-if-env-changed=CHIPO_GENERATE_HEADER");
+    if std::env::var_os("CHIPO_GENERATE_HEADER").is_none() {
+        return;
+    }
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+        .expect("Expected cbindgen.toml to be valid.");
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("Unable to generate C header.")
+        .write_to_file(format!("{}/include/chipo.h", crate_dir));
+}
@@ -0,0 +1,220 @@
+/* Generated by cbindgen from src/ffi.rs. Do not edit. */
+
+#ifndef CHIPO_H
+#define CHIPO_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The result of a function of the C API.
+typedef enum ChipoResult {
+  // The function succeeded.
+  CHIPO_RESULT_OK = 0,
+  // A pointer argument was null.
+  CHIPO_RESULT_NULL_POINTER = 1,
+  // The rom does not fit in memory.
+  CHIPO_RESULT_ROM_TOO_LARGE = 2,
+  // The emulator encountered an opcode that is not a valid instruction.
+  CHIPO_RESULT_UNKNOWN_OPCODE = 3,
+  // A subroutine was called when the stack was full.
+  CHIPO_RESULT_STACK_OVERFLOW = 4,
+  // A subroutine returned when the stack was empty.
+  CHIPO_RESULT_STACK_UNDERFLOW = 5,
+  // An instruction accessed memory past the end of memory.
+  CHIPO_RESULT_MEMORY_OUT_OF_BOUNDS = 6,
+  // The save state is malformed.
+  CHIPO_RESULT_INVALID_STATE = 7,
+  // The output buffer is too small.
+  CHIPO_RESULT_BUFFER_TOO_SMALL = 8,
+  // The key is not a hex key.
+  CHIPO_RESULT_INVALID_KEY = 9,
+} ChipoResult;
+
+// A chip-8 emulator.
+typedef struct Chipo Chipo;
+
+typedef struct Option_AudioSampleBatchFn Option_AudioSampleBatchFn;
+
+typedef struct Option_AudioSampleFn Option_AudioSampleFn;
+
+typedef struct Option_EnvironmentFn Option_EnvironmentFn;
+
+typedef struct Option_InputPollFn Option_InputPollFn;
+
+typedef struct Option_InputStateFn Option_InputStateFn;
+
+typedef struct Option_VideoRefreshFn Option_VideoRefreshFn;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns a static, null-terminated description of the `ChipoResult` value `result`, or of an
+// unknown result if `result` is not one. `result` is an `int` since C allows any value to be
+// stored in an enum.
+const char *chipo_result_message(int result);
+
+// Constructs a new emulator. The emulator must be freed with `chipo_free`.
+struct Chipo *chipo_new(void);
+
+// Frees an emulator constructed by `chipo_new`. Does nothing if `chip_8` is null.
+//
+// # Safety
+//
+// `chip_8` must be null or a pointer returned by `chipo_new` that has not been freed.
+void chipo_free(struct Chipo *chip_8);
+
+// Loads the rom of `length` bytes at `rom`. If `should_wrap` is true, then sprites wrap around
+// the edges of the screen, else they are clipped.
+//
+// # Safety
+//
+// `chip_8` must be null or a valid emulator, and `rom` must be null or point to `length` bytes.
+enum ChipoResult chipo_load_rom(struct Chipo *chip_8,
+                                const uint8_t *rom,
+                                size_t length,
+                                bool should_wrap);
+
+// Runs `cycles` cycles and then decrements the timers once. Stops at the first invalid
+// instruction.
+//
+// # Safety
+//
+// `chip_8` must be null or a valid emulator.
+enum ChipoResult chipo_run_frame(struct Chipo *chip_8, size_t cycles);
+
+// Returns the number of bytes written by `chipo_screen`.
+size_t chipo_screen_size(void);
+
+// Returns the width in pixels of the screen of the current mode, which is either 64 or 128.
+//
+// # Safety
+//
+// `chip_8` must be null or a valid emulator.
+size_t chipo_screen_width(const struct Chipo *chip_8);
+
+// Returns the height in pixels of the screen of the current mode, which is either 32 or 64.
+//
+// # Safety
+//
+// `chip_8` must be null or a valid emulator.
+size_t chipo_screen_height(const struct Chipo *chip_8);
+
+// Copies the screen into `buffer`, which must hold at least `chipo_screen_size()` bytes. The
+// layout is the same as `Chip8::screen_buffer`: 128x64 pixels in row-major order, 8 pixels per
+// byte with the leftmost pixel in the least significant bit. In standard mode, each pixel is a
+// 2x2 block.
+//
+// # Safety
+//
+// `chip_8` must be null or a valid emulator, and `buffer` must be null or point to `length`
+// writable bytes.
+enum ChipoResult chipo_screen(const struct Chipo *chip_8, uint8_t *buffer, size_t length);
+
+// Returns whether the last call to `chipo_run_frame` updated the screen.
+//
+// # Safety
+//
+// `chip_8` must be null or a valid emulator.
+bool chipo_should_draw(const struct Chipo *chip_8);
+
+// Returns whether the sound timer reached zero during the last call to `chipo_run_frame`.
+//
+// # Safety
+//
+// `chip_8` must be null or a valid emulator.
+bool chipo_should_beep(const struct Chipo *chip_8);
+
+// Presses the hex key `key`.
+//
+// # Safety
+//
+// `chip_8` must be null or a valid emulator.
+enum ChipoResult chipo_press_key(struct Chipo *chip_8, uint8_t key);
+
+// Releases the hex key `key`.
+//
+// # Safety
+//
+// `chip_8` must be null or a valid emulator.
+enum ChipoResult chipo_release_key(struct Chipo *chip_8, uint8_t key);
+
+// Returns the number of bytes written by `chipo_save_state`.
+size_t chipo_save_state_size(void);
+
+// Writes a save state into `buffer`, which must hold at least `chipo_save_state_size()` bytes.
+//
+// # Safety
+//
+// `chip_8` must be null or a valid emulator, and `buffer` must be null or point to `length`
+// writable bytes.
+enum ChipoResult chipo_save_state(const struct Chipo *chip_8, uint8_t *buffer, size_t length);
+
+// Restores the save state of `length` bytes at `state`. The emulator is not modified if the save
+// state is malformed.
+//
+// # Safety
+//
+// `chip_8` must be null or a valid emulator, and `state` must be null or point to `length` bytes.
+enum ChipoResult chipo_load_state(struct Chipo *chip_8, const uint8_t *state, size_t length);
+
+unsigned int retro_api_version(void);
+
+void retro_set_environment(struct Option_EnvironmentFn callback);
+
+void retro_set_video_refresh(struct Option_VideoRefreshFn callback);
+
+void retro_set_audio_sample(struct Option_AudioSampleFn _callback);
+
+void retro_set_audio_sample_batch(struct Option_AudioSampleBatchFn callback);
+
+void retro_set_input_poll(struct Option_InputPollFn callback);
+
+void retro_set_input_state(struct Option_InputStateFn callback);
+
+void retro_init(void);
+
+void retro_deinit(void);
+
+void retro_get_system_info(struct SystemInfo *info);
+
+void retro_get_system_av_info(struct SystemAvInfo *info);
+
+void retro_set_controller_port_device(unsigned int port, unsigned int device);
+
+void retro_reset(void);
+
+void retro_run(void);
+
+size_t retro_serialize_size(void);
+
+bool retro_serialize(void *data, size_t size);
+
+bool retro_unserialize(const void *data, size_t size);
+
+void retro_cheat_reset(void);
+
+void retro_cheat_set(unsigned int _index, bool _enabled, const char *_code);
+
+bool retro_load_game(const struct GameInfo *game);
+
+bool retro_load_game_special(unsigned int _game_type,
+                             const struct GameInfo *_info,
+                             size_t _num_info);
+
+void retro_unload_game(void);
+
+unsigned int retro_get_region(void);
+
+void *retro_get_memory_data(unsigned int _id);
+
+size_t retro_get_memory_size(unsigned int _id);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* CHIPO_H */
@@ -0,0 +1,154 @@
+// Extracts the source and options of an Octo cartridge.
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use chipo::{DrawMode, OctoOptions, Octocart};
+
+const USAGE: &str = "\
+Usage: chipo-cart-extract [OPTIONS] CART
+
+Options:
+    --source PATH       Write the Octo source to PATH, or to the standard output instead of the
+                        report if PATH is -
+    -h, --help          Print this message
+
+Reports the tickrate, quirks, colors, and screen rotation of an Octo cartridge, the quirks that
+chipo cannot emulate, and the chipo options that run the assembled program with the same settings.
+chipo cannot assemble or run the source, so it has to be assembled by Octo.";
+
+struct Options {
+    cart_path: String,
+    source_path: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut cart_path = None;
+    let mut source_path = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}.", name))
+        };
+        match arg.as_str() {
+            "--source" => source_path = Some(value("--source")?),
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}.", arg)),
+            _ if cart_path.is_none() => cart_path = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}.", arg)),
+        }
+    }
+
+    Ok(Options {
+        cart_path: cart_path.ok_or_else(|| String::from("Missing cartridge."))?,
+        source_path,
+    })
+}
+
+fn color(color: Option<u32>) -> String {
+    match color {
+        Some(color) => format!("#{:06X}", color),
+        None => String::from("default"),
+    }
+}
+
+fn write_report(out: &mut impl Write, options: &OctoOptions) -> io::Result<()> {
+    match options.tickrate {
+        Some(tickrate) => writeln!(out, "Tickrate: {}", tickrate)?,
+        None => writeln!(out, "Tickrate: default")?,
+    }
+    let quirks: Vec<&str> = [
+        (options.shift_quirks, "shift"),
+        (options.load_store_quirks, "load-store"),
+        (options.vf_order_quirks, "vf-order"),
+        (options.clip_quirks, "clip"),
+        (options.jump_quirks, "jump"),
+        (options.vblank_quirks, "vblank"),
+        (options.logic_quirks, "logic"),
+    ]
+    .iter()
+    .filter(|(is_enabled, _)| *is_enabled)
+    .map(|(_, name)| *name)
+    .collect();
+    if quirks.is_empty() {
+        writeln!(out, "Quirks: none")?;
+    } else {
+        writeln!(out, "Quirks: {}", quirks.join(", "))?;
+    }
+    writeln!(
+        out,
+        "Colors: fill {}, fill 2 {}, blend {}, background {}, buzz {}, quiet {}",
+        color(options.fill_color),
+        color(options.fill_color_2),
+        color(options.blend_color),
+        color(options.background_color),
+        color(options.buzz_color),
+        color(options.quiet_color),
+    )?;
+    writeln!(out, "Screen rotation: {}", options.screen_rotation)?;
+    let unsupported: Vec<String> = options
+        .unsupported_quirks()
+        .iter()
+        .map(|(name, value)| format!("{} {}", name, value))
+        .collect();
+    if !unsupported.is_empty() {
+        writeln!(out, "Unsupported quirks: {}", unsupported.join(", "))?;
+    }
+
+    let mut chipo_options = String::from("--quirks schip-modern");
+    if options.quirks().draw_mode == DrawMode::Clip {
+        chipo_options.push_str(" --clip");
+    } else {
+        chipo_options.push_str(" --wrap");
+    }
+    if let Some(tickrate) = options.tickrate {
+        chipo_options.push_str(&format!(" --speed {}", tickrate));
+    }
+    writeln!(out, "chipo options: {}", chipo_options)
+}
+
+fn run(options: &Options, gif: &[u8]) -> Result<(), Box<dyn Error>> {
+    let cart = Octocart::parse(gif).map_err(|error| format!("{}: {}", options.cart_path, error))?;
+    let mut out = BufWriter::new(io::stdout());
+    match options.source_path.as_deref() {
+        Some("-") => out.write_all(cart.source.as_bytes())?,
+        Some(path) => {
+            fs::write(path, &cart.source)?;
+            write_report(&mut out, &cart.options)?;
+        }
+        None => write_report(&mut out, &cart.options)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if message.is_empty() {
+                println!("{}", USAGE);
+                return;
+            }
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let gif = match fs::read(&options.cart_path) {
+        Ok(gif) => gif,
+        Err(error) => {
+            eprintln!("Unable to read {}: {}", options.cart_path, error);
+            process::exit(1);
+        }
+    };
+
+    if let Err(error) = run(&options, &gif) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,153 @@
+// Analyzes the control flow of a rom without running it.
+
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use chipo::{ControlFlowGraph, SymbolMap};
+
+const USAGE: &str = "\
+Usage: chipo-cfg [OPTIONS] ROM
+
+Options:
+    --dot PATH          Write the control flow graph to PATH in the Graphviz DOT format, or to
+                        the standard output instead of the report if PATH is -
+    --symbols PATH      Symbol map used to label the blocks of the graph
+    -h, --help          Print this message
+
+Reports the subroutines, halts, BNNN jumps, invalid instructions, unreachable ranges, and stack
+issues found by following every instruction that can be reached from 0x200.";
+
+struct Options {
+    rom_path: String,
+    dot_path: Option<String>,
+    symbols_path: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut rom_path = None;
+    let mut dot_path = None;
+    let mut symbols_path = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}.", name))
+        };
+        match arg.as_str() {
+            "--dot" => dot_path = Some(value("--dot")?),
+            "--symbols" => symbols_path = Some(value("--symbols")?),
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}.", arg)),
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}.", arg)),
+        }
+    }
+
+    Ok(Options {
+        rom_path: rom_path.ok_or_else(|| String::from("Missing rom."))?,
+        dot_path,
+        symbols_path,
+    })
+}
+
+fn addresses(addresses: impl Iterator<Item = u16>) -> String {
+    let addresses: Vec<String> = addresses
+        .map(|address| format!("0x{:03X}", address))
+        .collect();
+    if addresses.is_empty() {
+        String::from("none")
+    } else {
+        addresses.join(", ")
+    }
+}
+
+fn write_report(out: &mut impl Write, graph: &ControlFlowGraph) -> io::Result<()> {
+    writeln!(out, "Blocks: {}", graph.blocks().count())?;
+    writeln!(out, "Subroutines: {}", addresses(graph.subroutines()))?;
+    writeln!(out, "Halts: {}", addresses(graph.halts().iter().copied()))?;
+    writeln!(
+        out,
+        "BNNN jumps: {}",
+        addresses(graph.unresolved_jumps().iter().copied())
+    )?;
+    writeln!(
+        out,
+        "Invalid instructions: {}",
+        addresses(graph.invalid_instructions().iter().copied())
+    )?;
+    let unreachable: Vec<String> = graph
+        .unreachable()
+        .iter()
+        .map(|range| format!("0x{:03X}-0x{:03X}", range.start, range.end - 1))
+        .collect();
+    if unreachable.is_empty() {
+        writeln!(out, "Unreachable: none")?;
+    } else {
+        writeln!(out, "Unreachable: {}", unreachable.join(", "))?;
+    }
+    if graph.stack_issues().is_empty() {
+        writeln!(out, "Stack issues: none")
+    } else {
+        writeln!(out, "Stack issues:")?;
+        for issue in graph.stack_issues() {
+            writeln!(out, "  {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+fn run(options: &Options, rom: &[u8]) -> Result<(), Box<dyn Error>> {
+    let symbols = match &options.symbols_path {
+        Some(path) => {
+            let text = fs::read_to_string(path)
+                .map_err(|error| format!("Unable to read {}: {}", path, error))?;
+            Some(SymbolMap::parse(&text).map_err(|error| format!("{}: {}", path, error))?)
+        }
+        None => None,
+    };
+
+    let graph = ControlFlowGraph::analyze(rom);
+    let mut out = BufWriter::new(io::stdout());
+    match options.dot_path.as_deref() {
+        Some("-") => graph.write_dot(&mut out, symbols.as_ref())?,
+        Some(path) => {
+            let mut file = BufWriter::new(File::create(path)?);
+            graph.write_dot(&mut file, symbols.as_ref())?;
+            file.flush()?;
+            write_report(&mut out, &graph)?;
+        }
+        None => write_report(&mut out, &graph)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if message.is_empty() {
+                println!("{}", USAGE);
+                return;
+            }
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let rom = match fs::read(&options.rom_path) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("Unable to read {}: {}", options.rom_path, error);
+            process::exit(1);
+        }
+    };
+
+    if let Err(error) = run(&options, &rom) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
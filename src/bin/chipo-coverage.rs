@@ -0,0 +1,176 @@
+// Reports which bytes of a rom were executed, read, or written while it ran.
+
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use chipo::{Chip8, Coverage, DrawMode, Quirks, SymbolMap};
+
+const PROGRAM_START: usize = 0x200;
+
+const USAGE: &str = "\
+Usage: chipo-coverage [OPTIONS] ROM
+
+Options:
+    --quirks PRESET     Quirks preset: chip-8, schip-legacy, or schip-modern [default: chip-8]
+    --speed N           Instructions per frame [default: 10]
+    --frames N          Number of frames to run [default: 600]
+    --symbols PATH      Symbol map that maps addresses to source lines
+    --lcov PATH         Write the coverage of the source lines to PATH in the lcov format,
+                        which requires --symbols
+    -h, --help          Print this message
+
+Prints a summary and the disassembly of the rom, with each line prefixed by X if it was executed,
+R if it was read by DXYN or FX65, and W if it was written by FX33 or FX55, after the writes to
+executed code and the executions of written code. No keys are pressed while the rom runs.";
+
+struct Options {
+    rom_path: String,
+    quirks: Quirks,
+    speed: usize,
+    frames: usize,
+    symbols_path: Option<String>,
+    lcov_path: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut rom_path = None;
+    let mut quirks = Quirks::new();
+    let mut speed = 10;
+    let mut frames = 600;
+    let mut symbols_path = None;
+    let mut lcov_path = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}.", name))
+        };
+        match arg.as_str() {
+            "--quirks" => {
+                let name = value("--quirks")?;
+                quirks = Quirks::from_preset(&name)
+                    .ok_or_else(|| format!("Unknown quirks preset: {}.", name))?;
+            }
+            "--speed" => {
+                let speed_value = value("--speed")?;
+                speed = speed_value
+                    .parse()
+                    .map_err(|_| format!("Invalid speed: {}.", speed_value))?;
+            }
+            "--frames" => {
+                let frames_value = value("--frames")?;
+                frames = frames_value
+                    .parse()
+                    .map_err(|_| format!("Invalid number of frames: {}.", frames_value))?;
+            }
+            "--symbols" => symbols_path = Some(value("--symbols")?),
+            "--lcov" => lcov_path = Some(value("--lcov")?),
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}.", arg)),
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}.", arg)),
+        }
+    }
+    if lcov_path.is_some() && symbols_path.is_none() {
+        return Err(String::from("--lcov requires --symbols."));
+    }
+
+    Ok(Options {
+        rom_path: rom_path.ok_or_else(|| String::from("Missing rom."))?,
+        quirks,
+        speed,
+        frames,
+        symbols_path,
+        lcov_path,
+    })
+}
+
+fn run(options: &Options, rom: &[u8]) -> Result<(), Box<dyn Error>> {
+    let symbols = match &options.symbols_path {
+        Some(path) => {
+            let text = fs::read_to_string(path)
+                .map_err(|error| format!("Unable to read {}: {}", path, error))?;
+            Some(SymbolMap::parse(&text).map_err(|error| format!("{}: {}", path, error))?)
+        }
+        None => None,
+    };
+
+    let mut chip_8 = Chip8::new();
+    chip_8.set_quirks(options.quirks);
+    chip_8.try_load_rom(rom, options.quirks.draw_mode == DrawMode::Wrap)?;
+
+    let mut coverage = Coverage::new();
+    let mut result = Ok(());
+    for _ in 0..options.frames {
+        result = chip_8.run_frame_observed(options.speed, &mut coverage);
+        if result.is_err() {
+            break;
+        }
+    }
+
+    let range = PROGRAM_START..PROGRAM_START + rom.len();
+    let count = |is_covered: &dyn Fn(u16) -> bool| {
+        range
+            .clone()
+            .filter(|address| is_covered(*address as u16))
+            .count()
+    };
+    let covered = coverage.covered_bytes(range.clone());
+    let mut out = BufWriter::new(io::stdout());
+    writeln!(
+        out,
+        "Covered {} of {} bytes ({:.1}%): {} executed, {} read, {} written",
+        covered,
+        rom.len(),
+        covered as f64 * 100.0 / rom.len().max(1) as f64,
+        count(&|address| coverage.is_executed(address)),
+        count(&|address| coverage.is_read(address)),
+        count(&|address| coverage.is_written(address)),
+    )?;
+    if !coverage.self_modifications().is_empty() {
+        writeln!(out, "\nSelf-modifying code:")?;
+        for modification in coverage.self_modifications() {
+            writeln!(out, "  {}", modification)?;
+        }
+    }
+    writeln!(out)?;
+    coverage.write_annotated(&mut out, chip_8.memory(), range)?;
+    out.flush()?;
+
+    if let (Some(path), Some(symbols)) = (&options.lcov_path, &symbols) {
+        let mut file = BufWriter::new(File::create(path)?);
+        coverage.write_lcov(&mut file, symbols)?;
+        file.flush()?;
+    }
+    Ok(result?)
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if message.is_empty() {
+                println!("{}", USAGE);
+                return;
+            }
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let rom = match fs::read(&options.rom_path) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("Unable to read {}: {}", options.rom_path, error);
+            process::exit(1);
+        }
+    };
+
+    if let Err(error) = run(&options, &rom) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
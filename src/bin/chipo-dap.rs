@@ -0,0 +1,25 @@
+// Runs a Debug Adapter Protocol server on the standard input and output.
+
+use std::env;
+use std::io;
+use std::process;
+
+use chipo::DapServer;
+
+const USAGE: &str = "\
+Usage: chipo-dap
+
+Serves the Debug Adapter Protocol on the standard input and output. Configure it as the debug
+adapter executable of an editor, and launch roms with a `program` path and optionally `symbols`,
+`quirks`, `speed`, and `stopOnEntry`.";
+
+fn main() {
+    if env::args().len() > 1 {
+        println!("{}", USAGE);
+        return;
+    }
+    if let Err(error) = DapServer::new().serve(io::stdin(), io::stdout()) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,535 @@
+// Debugs a rom in a terminal.
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+use std::time::{Duration, Instant};
+
+use chipo::terminal::{queue_screen, HostKeys, Terminal};
+use chipo::{disassemble, Chip8, Debugger, DrawMode, Palette, Quirks, ScreenMode, TextStyle};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{Clear, ClearType};
+use crossterm::{cursor, execute, queue};
+
+const USAGE: &str = "\
+Usage: chipo-debug [OPTIONS] ROM
+
+Options:
+    --quirks PRESET     Quirks preset: chip-8, schip-legacy, or schip-modern [default: chip-8]
+    --speed N           Instructions per frame when running [default: 10]
+    --break ADDRESS     Add a breakpoint at the hex address, can be repeated
+    -h, --help          Print this message
+
+Commands:
+    F5                  Continue or pause
+    F10                 Step one instruction
+    F9                  Toggle a breakpoint
+    F2                  Edit memory
+    F3                  Go to a memory address
+    PageUp, PageDown    Scroll memory
+    Ctrl+Q              Quit
+
+Other keys are forwarded to the hex keypad like in `chipo`.";
+
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+const SCREEN_POSITION: (u16, u16) = (0, 1);
+const REGISTERS_COL: u16 = 66;
+const PANES_ROW: u16 = 18;
+const PANE_HEIGHT: u16 = 16;
+const DISASSEMBLY_COL: u16 = 0;
+const STACK_COL: u16 = 34;
+const MEMORY_COL: u16 = 48;
+const MEMORY_BYTES_PER_ROW: usize = 8;
+const HELP_ROW: u16 = PANES_ROW + PANE_HEIGHT + 2;
+
+const HELP: &str =
+    "F5 continue/pause  F10 step  F9 breakpoint  F2 edit memory  F3 go to memory  Ctrl+Q quit";
+
+struct Options {
+    rom_path: String,
+    quirks: Quirks,
+    speed: usize,
+    breakpoints: Vec<u16>,
+}
+
+fn parse_address(value: &str) -> Option<u16> {
+    let value = value.trim();
+    let value = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    u16::from_str_radix(value, 16).ok()
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut rom_path = None;
+    let mut quirks = Quirks::new();
+    let mut speed = 10;
+    let mut breakpoints = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}.", name))
+        };
+        match arg.as_str() {
+            "--quirks" => {
+                let name = value("--quirks")?;
+                quirks = Quirks::from_preset(&name)
+                    .ok_or_else(|| format!("Unknown quirks preset: {}.", name))?;
+            }
+            "--speed" => {
+                let speed_value = value("--speed")?;
+                speed = speed_value
+                    .parse()
+                    .map_err(|_| format!("Invalid speed: {}.", speed_value))?;
+            }
+            "--break" => {
+                let address = value("--break")?;
+                breakpoints.push(
+                    parse_address(&address)
+                        .ok_or_else(|| format!("Invalid address: {}.", address))?,
+                );
+            }
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}.", arg)),
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}.", arg)),
+        }
+    }
+
+    Ok(Options {
+        rom_path: rom_path.ok_or_else(|| String::from("Missing rom."))?,
+        quirks,
+        speed,
+        breakpoints,
+    })
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum PromptKind {
+    Breakpoint,
+    EditMemory,
+    GoToMemory,
+}
+
+impl PromptKind {
+    fn label(self) -> &'static str {
+        match self {
+            PromptKind::Breakpoint => "Toggle breakpoint at (empty for pc): ",
+            PromptKind::EditMemory => "Write bytes (ADDRESS BYTE...): ",
+            PromptKind::GoToMemory => "Go to memory address: ",
+        }
+    }
+}
+
+struct App {
+    chip_8: Chip8,
+    debugger: Debugger,
+    rom_path: String,
+    speed: usize,
+    is_running: bool,
+    memory_address: usize,
+    prompt: Option<(PromptKind, String)>,
+    message: String,
+}
+
+impl App {
+    fn continue_or_pause(&mut self) {
+        self.is_running = !self.is_running;
+        self.message = String::new();
+    }
+
+    fn step(&mut self) {
+        self.is_running = false;
+        self.message = match self.debugger.step(&mut self.chip_8) {
+            Ok(()) => String::new(),
+            Err(error) => error.to_string(),
+        };
+    }
+
+    fn run_frame(&mut self) {
+        if !self.is_running {
+            return;
+        }
+        match self.debugger.run(&mut self.chip_8, self.speed) {
+            Ok(None) => self.chip_8.decrement_timers(),
+            Ok(Some(address)) => {
+                self.is_running = false;
+                self.message = format!("Breakpoint at {:03X}", address);
+            }
+            Err(error) => {
+                self.is_running = false;
+                self.message = error.to_string();
+            }
+        }
+    }
+
+    fn submit_prompt(&mut self, kind: PromptKind, input: &str) {
+        let mut tokens = input.split_whitespace();
+        let address = match tokens.next() {
+            Some(token) => parse_address(token),
+            None if kind == PromptKind::Breakpoint => Some(self.chip_8.program_counter()),
+            None => None,
+        };
+        let address = match address {
+            Some(address) => address,
+            None => {
+                self.message = format!("Invalid address: {}", input.trim());
+                return;
+            }
+        };
+
+        self.message = match kind {
+            PromptKind::Breakpoint => {
+                if self.debugger.toggle_breakpoint(address) {
+                    format!("Added breakpoint at {:03X}", address)
+                } else {
+                    format!("Removed breakpoint at {:03X}", address)
+                }
+            }
+            PromptKind::EditMemory => {
+                let bytes: Option<Vec<u8>> = tokens
+                    .map(|token| u8::from_str_radix(token, 16).ok())
+                    .collect();
+                match bytes {
+                    Some(ref bytes) if !bytes.is_empty() => {
+                        match self.chip_8.write_memory(address as usize, bytes) {
+                            Ok(()) => {
+                                self.memory_address = address as usize;
+                                format!("Wrote {} bytes at {:03X}", bytes.len(), address)
+                            }
+                            Err(error) => error.to_string(),
+                        }
+                    }
+                    _ => String::from("Expected hex bytes after the address"),
+                }
+            }
+            PromptKind::GoToMemory => {
+                self.memory_address = address as usize;
+                String::new()
+            }
+        };
+    }
+
+    fn scroll_memory(&mut self, rows: isize) {
+        let offset = rows * MEMORY_BYTES_PER_ROW as isize;
+        let max_address = self.chip_8.memory().len() - MEMORY_BYTES_PER_ROW;
+        self.memory_address = (self.memory_address as isize + offset)
+            .max(0)
+            .min(max_address as isize) as usize;
+    }
+
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        let chip_8 = &self.chip_8;
+        queue!(out, Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+        let state = if self.is_running { "RUNNING" } else { "PAUSED" };
+        queue!(
+            out,
+            SetAttribute(Attribute::Bold),
+            Print(format!("chipo-debug {} [{}]", self.rom_path, state)),
+            SetAttribute(Attribute::Reset),
+            Print(format!("  {}", self.message)),
+        )?;
+
+        // Both modes fit in 64x16 characters.
+        let style = match chip_8.screen_mode() {
+            ScreenMode::Standard => TextStyle::HalfBlock,
+            ScreenMode::Super => TextStyle::Braille,
+        };
+        let palette = Palette::from_name("white-on-black").unwrap();
+        queue_screen(out, chip_8, style, palette, SCREEN_POSITION)?;
+
+        let registers = chip_8.data_registers();
+        for row in 0..4 {
+            let line: Vec<String> = (row * 4..row * 4 + 4)
+                .map(|i| format!("V{:X} {:02X}", i, registers[i]))
+                .collect();
+            queue!(
+                out,
+                cursor::MoveTo(REGISTERS_COL, 1 + row as u16),
+                Print(line.join("  "))
+            )?;
+        }
+        let lines = [
+            format!(
+                "PC {:03X}   I {:03X}",
+                chip_8.program_counter(),
+                chip_8.index()
+            ),
+            format!(
+                "DT {:02X}    ST {:02X}",
+                chip_8.delay_timer(),
+                chip_8.sound_timer()
+            ),
+            format!("Mode {:?}", chip_8.screen_mode()),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            queue!(
+                out,
+                cursor::MoveTo(REGISTERS_COL, 6 + i as u16),
+                Print(line)
+            )?;
+        }
+
+        self.draw_disassembly(out)?;
+        self.draw_stack(out)?;
+        self.draw_memory(out)?;
+
+        queue!(out, cursor::MoveTo(0, HELP_ROW), Print(HELP))?;
+        if let Some((kind, input)) = &self.prompt {
+            queue!(
+                out,
+                cursor::MoveTo(0, HELP_ROW + 1),
+                Print(kind.label()),
+                Print(input),
+                cursor::Show
+            )?;
+        } else {
+            queue!(out, cursor::Hide)?;
+        }
+        out.flush()
+    }
+
+    fn draw_disassembly(&self, out: &mut impl Write) -> io::Result<()> {
+        let memory = self.chip_8.memory();
+        let pc = self.chip_8.program_counter() as usize;
+        let start = pc.saturating_sub(2 * (PANE_HEIGHT as usize / 2 - 2)) & !1 | pc & 1;
+        queue!(
+            out,
+            cursor::MoveTo(DISASSEMBLY_COL, PANES_ROW),
+            SetAttribute(Attribute::Bold),
+            Print("Disassembly"),
+            SetAttribute(Attribute::Reset)
+        )?;
+        for (i, address) in (start..memory.len())
+            .step_by(2)
+            .take(PANE_HEIGHT as usize)
+            .enumerate()
+        {
+            let marker = if address == pc { '>' } else { ' ' };
+            let breakpoint = if self.debugger.has_breakpoint(address as u16) {
+                '*'
+            } else {
+                ' '
+            };
+            let bytes: String = memory[address..(address + 2).min(memory.len())]
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect();
+            queue!(
+                out,
+                cursor::MoveTo(DISASSEMBLY_COL, PANES_ROW + 1 + i as u16),
+                Print(format!(
+                    "{}{} {:03X} {:4} {}",
+                    marker,
+                    breakpoint,
+                    address,
+                    bytes,
+                    disassemble(memory, address)
+                ))
+            )?;
+        }
+        Ok(())
+    }
+
+    fn draw_stack(&self, out: &mut impl Write) -> io::Result<()> {
+        queue!(
+            out,
+            cursor::MoveTo(STACK_COL, PANES_ROW),
+            SetAttribute(Attribute::Bold),
+            Print("Stack"),
+            SetAttribute(Attribute::Reset)
+        )?;
+        let stack = self.chip_8.stack();
+        let stack_pointer = self.chip_8.stack_pointer() as usize;
+        for (i, address) in stack[..stack_pointer].iter().rev().enumerate() {
+            queue!(
+                out,
+                cursor::MoveTo(STACK_COL, PANES_ROW + 1 + i as u16),
+                Print(format!("{:2} {:03X}", stack_pointer - 1 - i, address))
+            )?;
+        }
+        Ok(())
+    }
+
+    fn draw_memory(&self, out: &mut impl Write) -> io::Result<()> {
+        let memory = self.chip_8.memory();
+        let index = self.chip_8.index() as usize;
+        queue!(
+            out,
+            cursor::MoveTo(MEMORY_COL, PANES_ROW),
+            SetAttribute(Attribute::Bold),
+            Print("Memory"),
+            SetAttribute(Attribute::Reset)
+        )?;
+        for row in 0..PANE_HEIGHT as usize {
+            let address = self.memory_address + row * MEMORY_BYTES_PER_ROW;
+            if address >= memory.len() {
+                break;
+            }
+            queue!(
+                out,
+                cursor::MoveTo(MEMORY_COL, PANES_ROW + 1 + row as u16),
+                Print(format!("{:03X}:", address))
+            )?;
+            let end = (address + MEMORY_BYTES_PER_ROW).min(memory.len());
+            for (offset, byte) in memory[address..end].iter().enumerate() {
+                // The byte at `I` is highlighted.
+                let attribute = if address + offset == index {
+                    Attribute::Reverse
+                } else {
+                    Attribute::Reset
+                };
+                queue!(
+                    out,
+                    Print(' '),
+                    SetAttribute(attribute),
+                    Print(format!("{:02X}", byte)),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn run(options: Options, rom: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut chip_8 = Chip8::new();
+    chip_8.set_quirks(options.quirks);
+    chip_8.try_load_rom(rom, options.quirks.draw_mode == DrawMode::Wrap)?;
+    let mut debugger = Debugger::new();
+    for address in options.breakpoints {
+        debugger.add_breakpoint(address);
+    }
+    let mut app = App {
+        chip_8,
+        debugger,
+        rom_path: options.rom_path,
+        speed: options.speed,
+        is_running: false,
+        memory_address: 0x200,
+        prompt: None,
+        message: String::new(),
+    };
+
+    let terminal = Terminal::new()?;
+    let mut host_keys = HostKeys::new(&terminal);
+    let mut stdout = io::stdout();
+    let mut next_frame = Instant::now();
+    app.draw(&mut stdout)?;
+
+    loop {
+        let now = Instant::now();
+        if now < next_frame {
+            if !event::poll(next_frame - now)? {
+                continue;
+            }
+            let (code, modifiers, kind) = match event::read()? {
+                Event::Key(KeyEvent {
+                    code,
+                    modifiers,
+                    kind,
+                    ..
+                }) => (code, modifiers, kind),
+                Event::Resize(_, _) => {
+                    app.draw(&mut stdout)?;
+                    continue;
+                }
+                _ => continue,
+            };
+
+            if let Some((prompt_kind, mut input)) = app.prompt.take() {
+                if kind != KeyEventKind::Release {
+                    match code {
+                        KeyCode::Enter => app.submit_prompt(prompt_kind, &input),
+                        KeyCode::Esc => {}
+                        KeyCode::Backspace => {
+                            input.pop();
+                            app.prompt = Some((prompt_kind, input));
+                        }
+                        KeyCode::Char(c) => {
+                            input.push(c);
+                            app.prompt = Some((prompt_kind, input));
+                        }
+                        _ => app.prompt = Some((prompt_kind, input)),
+                    }
+                } else {
+                    app.prompt = Some((prompt_kind, input));
+                }
+                app.draw(&mut stdout)?;
+                continue;
+            }
+
+            let is_release = kind == KeyEventKind::Release;
+            match code {
+                KeyCode::Char('q') | KeyCode::Char('c')
+                    if modifiers.contains(KeyModifiers::CONTROL) =>
+                {
+                    return Ok(());
+                }
+                KeyCode::F(5) if !is_release => app.continue_or_pause(),
+                KeyCode::F(10) if !is_release => app.step(),
+                KeyCode::F(9) if !is_release => {
+                    app.prompt = Some((PromptKind::Breakpoint, String::new()))
+                }
+                KeyCode::F(2) if !is_release => {
+                    app.prompt = Some((PromptKind::EditMemory, String::new()))
+                }
+                KeyCode::F(3) if !is_release => {
+                    app.prompt = Some((PromptKind::GoToMemory, String::new()))
+                }
+                KeyCode::PageUp if !is_release => app.scroll_memory(-(PANE_HEIGHT as isize)),
+                KeyCode::PageDown if !is_release => app.scroll_memory(PANE_HEIGHT as isize),
+                _ => {
+                    host_keys.handle_key(&mut app.chip_8, code, kind, now);
+                }
+            }
+            app.draw(&mut stdout)?;
+            continue;
+        }
+
+        next_frame += FRAME_DURATION;
+        if next_frame < now {
+            next_frame = now + FRAME_DURATION;
+        }
+
+        host_keys.release_expired(&mut app.chip_8, now);
+        if app.is_running {
+            app.run_frame();
+            app.draw(&mut stdout)?;
+        }
+    }
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if message.is_empty() {
+                println!("{}", USAGE);
+                return;
+            }
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let rom = match fs::read(&options.rom_path) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("Unable to read {}: {}", options.rom_path, error);
+            process::exit(1);
+        }
+    };
+
+    if let Err(error) = run(options, &rom) {
+        let _ = execute!(io::stdout(), cursor::Show);
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,114 @@
+// Runs roms under each quirks preset and recommends the preset that each rom most likely expects.
+
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use chipo::QuirkDetection;
+
+const USAGE: &str = "\
+Usage: chipo-detect [OPTIONS] ROM...
+
+Options:
+    --speed N           Instructions per frame [default: 10]
+    --frames N          Number of frames to run under each preset [default: 600]
+    -q, --quiet         Only print the recommended quirks preset of each rom
+    -h, --help          Print this message
+
+Runs each rom under every quirks preset while pressing a pseudo-random sequence of keys, and
+prints the recommended preset followed by the score of each run. Runs score higher when they
+change the screen, and lower when they are stuck at one address or stop at an error.";
+
+struct Options {
+    rom_paths: Vec<String>,
+    speed: usize,
+    frames: usize,
+    is_quiet: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut rom_paths = Vec::new();
+    let mut speed = 10;
+    let mut frames = 600;
+    let mut is_quiet = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}.", name))
+        };
+        match arg.as_str() {
+            "--speed" => {
+                let speed_value = value("--speed")?;
+                speed = speed_value
+                    .parse()
+                    .map_err(|_| format!("Invalid speed: {}.", speed_value))?;
+            }
+            "--frames" => {
+                let frames_value = value("--frames")?;
+                frames = frames_value
+                    .parse()
+                    .map_err(|_| format!("Invalid number of frames: {}.", frames_value))?;
+            }
+            "-q" | "--quiet" => is_quiet = true,
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}.", arg)),
+            _ => rom_paths.push(arg),
+        }
+    }
+    if rom_paths.is_empty() {
+        return Err(String::from("Missing rom."));
+    }
+
+    Ok(Options {
+        rom_paths,
+        speed,
+        frames,
+        is_quiet,
+    })
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if message.is_empty() {
+                println!("{}", USAGE);
+                return;
+            }
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let mut out = BufWriter::new(io::stdout());
+    let mut has_failed = false;
+    for path in &options.rom_paths {
+        let detection = fs::read(path)
+            .map_err(|error| error.to_string())
+            .and_then(|rom| {
+                QuirkDetection::analyze(&rom, options.frames, options.speed)
+                    .map_err(|error| error.to_string())
+            });
+        let detection = match detection {
+            Ok(detection) => detection,
+            Err(error) => {
+                let _ = out.flush();
+                eprintln!("Unable to analyze {}: {}", path, error);
+                has_failed = true;
+                continue;
+            }
+        };
+        let _ = writeln!(out, "{}: {}", path, detection.recommended_preset());
+        if !options.is_quiet {
+            for run in detection.runs() {
+                let _ = writeln!(out, "  {}", run);
+            }
+        }
+    }
+    let _ = out.flush();
+    if has_failed {
+        process::exit(1);
+    }
+}
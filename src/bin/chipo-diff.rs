@@ -0,0 +1,145 @@
+// Replays a rom against a trace of another emulator and reports the first divergence.
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::process;
+
+use chipo::{Chip8, DrawMode, Quirks, ReferenceTrace};
+
+const USAGE: &str = "\
+Usage: chipo-diff [OPTIONS] ROM TRACE
+
+Options:
+    --quirks PRESET     Quirks preset: chip-8, schip-legacy, or schip-modern [default: chip-8]
+    --speed N           Instructions per frame, used to decrement the timers [default: 10]
+    --format FORMAT     Trace format: csv or json [default: json for .json and .jsonl files,
+                        csv otherwise]
+    -h, --help          Print this message
+
+Each step of the trace is the state before an instruction, with the columns or keys pc, opcode,
+v0 to vf, i, and sp, and optionally dt, st, and memory. Exits with 1 if the rom diverges.";
+
+#[derive(Clone, Copy)]
+enum Format {
+    Csv,
+    Json,
+}
+
+struct Options {
+    rom_path: String,
+    trace_path: String,
+    quirks: Quirks,
+    speed: usize,
+    format: Option<Format>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut paths = Vec::new();
+    let mut quirks = Quirks::new();
+    let mut speed = 10;
+    let mut format = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}.", name))
+        };
+        match arg.as_str() {
+            "--quirks" => {
+                let name = value("--quirks")?;
+                quirks = Quirks::from_preset(&name)
+                    .ok_or_else(|| format!("Unknown quirks preset: {}.", name))?;
+            }
+            "--speed" => {
+                let speed_value = value("--speed")?;
+                speed = speed_value
+                    .parse()
+                    .map_err(|_| format!("Invalid speed: {}.", speed_value))?;
+            }
+            "--format" => {
+                format = match value("--format")?.as_str() {
+                    "csv" => Some(Format::Csv),
+                    "json" => Some(Format::Json),
+                    name => return Err(format!("Unknown format: {}.", name)),
+                };
+            }
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}.", arg)),
+            _ if paths.len() < 2 => paths.push(arg),
+            _ => return Err(format!("Unexpected argument: {}.", arg)),
+        }
+    }
+
+    let mut paths = paths.into_iter();
+    Ok(Options {
+        rom_path: paths.next().ok_or_else(|| String::from("Missing rom."))?,
+        trace_path: paths.next().ok_or_else(|| String::from("Missing trace."))?,
+        quirks,
+        speed,
+        format,
+    })
+}
+
+fn run(options: &Options, rom: &[u8]) -> Result<bool, Box<dyn Error>> {
+    let text = fs::read_to_string(&options.trace_path)
+        .map_err(|error| format!("Unable to read {}: {}", options.trace_path, error))?;
+    let format = options.format.unwrap_or_else(|| {
+        if options.trace_path.ends_with(".json") || options.trace_path.ends_with(".jsonl") {
+            Format::Json
+        } else {
+            Format::Csv
+        }
+    });
+    let trace = match format {
+        Format::Csv => ReferenceTrace::parse_csv(&text),
+        Format::Json => ReferenceTrace::parse_json(&text),
+    }
+    .map_err(|error| format!("Invalid trace {}: {}", options.trace_path, error))?;
+
+    let mut chip_8 = Chip8::new();
+    chip_8.set_quirks(options.quirks);
+    chip_8.try_load_rom(rom, options.quirks.draw_mode == DrawMode::Wrap)?;
+
+    match trace.replay(&mut chip_8, options.speed) {
+        Ok(steps) => {
+            println!("All {} steps match.", steps);
+            Ok(true)
+        }
+        Err(divergence) => {
+            println!("Diverged at {}", divergence);
+            Ok(false)
+        }
+    }
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if message.is_empty() {
+                println!("{}", USAGE);
+                return;
+            }
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let rom = match fs::read(&options.rom_path) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("Unable to read {}: {}", options.rom_path, error);
+            process::exit(1);
+        }
+    };
+
+    match run(&options, &rom) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
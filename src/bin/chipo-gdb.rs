@@ -0,0 +1,133 @@
+// Serves a rom to GDB over TCP.
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::net::TcpListener;
+use std::process;
+
+use chipo::{Chip8, DrawMode, GdbServer, Quirks};
+
+const USAGE: &str = "\
+Usage: chipo-gdb [OPTIONS] ROM
+
+Options:
+    --port PORT         Port to listen on at 127.0.0.1 [default: 1234]
+    --quirks PRESET     Quirks preset: chip-8, schip-legacy, or schip-modern [default: chip-8]
+    --speed N           Instructions per frame [default: 10]
+    --break ADDRESS     Add a breakpoint at the hex address, can be repeated
+    -h, --help          Print this message
+
+Connect with `target remote 127.0.0.1:PORT` in GDB. The emulator keeps its state between
+connections.";
+
+struct Options {
+    rom_path: String,
+    port: u16,
+    quirks: Quirks,
+    speed: usize,
+    breakpoints: Vec<u16>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut rom_path = None;
+    let mut port = 1234;
+    let mut quirks = Quirks::new();
+    let mut speed = 10;
+    let mut breakpoints = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}.", name))
+        };
+        match arg.as_str() {
+            "--port" => {
+                let port_value = value("--port")?;
+                port = port_value
+                    .parse()
+                    .map_err(|_| format!("Invalid port: {}.", port_value))?;
+            }
+            "--quirks" => {
+                let name = value("--quirks")?;
+                quirks = Quirks::from_preset(&name)
+                    .ok_or_else(|| format!("Unknown quirks preset: {}.", name))?;
+            }
+            "--speed" => {
+                let speed_value = value("--speed")?;
+                speed = speed_value
+                    .parse()
+                    .map_err(|_| format!("Invalid speed: {}.", speed_value))?;
+            }
+            "--break" => {
+                let address = value("--break")?;
+                let hex = address.trim_start_matches("0x");
+                breakpoints.push(
+                    u16::from_str_radix(hex, 16)
+                        .map_err(|_| format!("Invalid address: {}.", address))?,
+                );
+            }
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}.", arg)),
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}.", arg)),
+        }
+    }
+
+    Ok(Options {
+        rom_path: rom_path.ok_or_else(|| String::from("Missing rom."))?,
+        port,
+        quirks,
+        speed,
+        breakpoints,
+    })
+}
+
+fn run(options: Options, rom: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut chip_8 = Chip8::new();
+    chip_8.set_quirks(options.quirks);
+    chip_8.try_load_rom(rom, options.quirks.draw_mode == DrawMode::Wrap)?;
+    let mut server = GdbServer::new(options.speed);
+    for address in options.breakpoints {
+        server.debugger_mut().add_breakpoint(address);
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", options.port))?;
+    println!("Listening on {}", listener.local_addr()?);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        println!("Connected to {}", stream.peer_addr()?);
+        if let Err(error) = server.serve(&mut chip_8, stream) {
+            eprintln!("{}", error);
+        }
+        println!("Disconnected");
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if message.is_empty() {
+                println!("{}", USAGE);
+                return;
+            }
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let rom = match fs::read(&options.rom_path) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("Unable to read {}: {}", options.rom_path, error);
+            process::exit(1);
+        }
+    };
+
+    if let Err(error) = run(options, &rom) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,87 @@
+// Reports the ambiguous behaviours that roms rely on and suggests a quirks preset for each.
+
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use chipo::RomLints;
+
+const USAGE: &str = "\
+Usage: chipo-lint [OPTIONS] ROM...
+
+Options:
+    -q, --quiet         Only print the suggested quirks preset of each rom
+    -h, --help          Print this message
+
+Prints the suggested quirks preset of each rom, followed by the reachable instructions that behave
+differently between interpreters: shifts with VX != VY, uses of I after FX55 and FX65, BNNN with a
+high nibble, DXYN past the edges of the screen, and SCHIP instructions. No preset is suggested for
+roms with the first three, since the presets do not differ in them.";
+
+struct Options {
+    rom_paths: Vec<String>,
+    is_quiet: bool,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut rom_paths = Vec::new();
+    let mut is_quiet = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "-q" | "--quiet" => is_quiet = true,
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}.", arg)),
+            _ => rom_paths.push(arg),
+        }
+    }
+    if rom_paths.is_empty() {
+        return Err(String::from("Missing rom."));
+    }
+
+    Ok(Options {
+        rom_paths,
+        is_quiet,
+    })
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if message.is_empty() {
+                println!("{}", USAGE);
+                return;
+            }
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let mut out = BufWriter::new(io::stdout());
+    let mut has_failed = false;
+    for path in &options.rom_paths {
+        let rom = match fs::read(path) {
+            Ok(rom) => rom,
+            Err(error) => {
+                let _ = out.flush();
+                eprintln!("Unable to read {}: {}", path, error);
+                has_failed = true;
+                continue;
+            }
+        };
+        let lints = RomLints::analyze(&rom);
+        let preset = lints.suggested_preset().unwrap_or("none");
+        let _ = writeln!(out, "{}: {}", path, preset);
+        if !options.is_quiet {
+            for lint in lints.lints() {
+                let _ = writeln!(out, "  {}", lint);
+            }
+        }
+    }
+    let _ = out.flush();
+    if has_failed {
+        process::exit(1);
+    }
+}
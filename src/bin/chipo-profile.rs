@@ -0,0 +1,143 @@
+// Profiles a rom and reports where it spends its instructions.
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use chipo::{Chip8, DrawMode, Profiler, Quirks, SymbolMap};
+
+const USAGE: &str = "\
+Usage: chipo-profile [OPTIONS] ROM
+
+Options:
+    --quirks PRESET     Quirks preset: chip-8, schip-legacy, or schip-modern [default: chip-8]
+    --speed N           Instructions per frame [default: 10]
+    --frames N          Number of frames to run [default: 600]
+    --symbols PATH      Symbol map used to name subroutines and loops
+    --top N             Number of addresses and loops to report [default: 10]
+    -h, --help          Print this message
+
+Reports the hottest addresses, the opcode classes, the instructions spent in each subroutine, and
+the hottest loops. No keys are pressed while the rom runs.";
+
+struct Options {
+    rom_path: String,
+    quirks: Quirks,
+    speed: usize,
+    frames: usize,
+    symbols_path: Option<String>,
+    top: usize,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut rom_path = None;
+    let mut quirks = Quirks::new();
+    let mut speed = 10;
+    let mut frames = 600;
+    let mut symbols_path = None;
+    let mut top = 10;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}.", name))
+        };
+        match arg.as_str() {
+            "--quirks" => {
+                let name = value("--quirks")?;
+                quirks = Quirks::from_preset(&name)
+                    .ok_or_else(|| format!("Unknown quirks preset: {}.", name))?;
+            }
+            "--speed" => {
+                let speed_value = value("--speed")?;
+                speed = speed_value
+                    .parse()
+                    .map_err(|_| format!("Invalid speed: {}.", speed_value))?;
+            }
+            "--frames" => {
+                let frames_value = value("--frames")?;
+                frames = frames_value
+                    .parse()
+                    .map_err(|_| format!("Invalid number of frames: {}.", frames_value))?;
+            }
+            "--symbols" => symbols_path = Some(value("--symbols")?),
+            "--top" => {
+                let top_value = value("--top")?;
+                top = top_value
+                    .parse()
+                    .map_err(|_| format!("Invalid number: {}.", top_value))?;
+            }
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}.", arg)),
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}.", arg)),
+        }
+    }
+
+    Ok(Options {
+        rom_path: rom_path.ok_or_else(|| String::from("Missing rom."))?,
+        quirks,
+        speed,
+        frames,
+        symbols_path,
+        top,
+    })
+}
+
+fn run(options: &Options, rom: &[u8]) -> Result<(), Box<dyn Error>> {
+    let symbols = match &options.symbols_path {
+        Some(path) => {
+            let text = fs::read_to_string(path)
+                .map_err(|error| format!("Unable to read {}: {}", path, error))?;
+            Some(SymbolMap::parse(&text).map_err(|error| format!("{}: {}", path, error))?)
+        }
+        None => None,
+    };
+
+    let mut chip_8 = Chip8::new();
+    chip_8.set_quirks(options.quirks);
+    chip_8.try_load_rom(rom, options.quirks.draw_mode == DrawMode::Wrap)?;
+
+    let mut profiler = Profiler::new();
+    let mut result = Ok(());
+    for _ in 0..options.frames {
+        result = chip_8.run_frame_observed(options.speed, &mut profiler);
+        if result.is_err() {
+            break;
+        }
+    }
+
+    let mut out = BufWriter::new(io::stdout());
+    profiler.write_report(&mut out, chip_8.memory(), symbols.as_ref(), options.top)?;
+    out.flush()?;
+    Ok(result?)
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if message.is_empty() {
+                println!("{}", USAGE);
+                return;
+            }
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let rom = match fs::read(&options.rom_path) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("Unable to read {}: {}", options.rom_path, error);
+            process::exit(1);
+        }
+    };
+
+    if let Err(error) = run(&options, &rom) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
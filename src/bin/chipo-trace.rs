@@ -0,0 +1,136 @@
+// Writes an execution trace of a rom.
+
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use chipo::{Chip8, DrawMode, Quirks, TraceFormat, Tracer};
+
+const USAGE: &str = "\
+Usage: chipo-trace [OPTIONS] ROM
+
+Options:
+    --quirks PRESET     Quirks preset: chip-8, schip-legacy, or schip-modern [default: chip-8]
+    --speed N           Instructions per frame [default: 10]
+    --frames N          Number of frames to run [default: 60]
+    --format FORMAT     Trace format: text or json [default: text]
+    --output PATH       Write the trace to a file instead of the standard output
+    -h, --help          Print this message
+
+Each line of the trace is an executed instruction and the registers that it changed. No keys are
+pressed while the rom runs.";
+
+struct Options {
+    rom_path: String,
+    quirks: Quirks,
+    speed: usize,
+    frames: usize,
+    format: TraceFormat,
+    output_path: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut rom_path = None;
+    let mut quirks = Quirks::new();
+    let mut speed = 10;
+    let mut frames = 60;
+    let mut format = TraceFormat::Text;
+    let mut output_path = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}.", name))
+        };
+        match arg.as_str() {
+            "--quirks" => {
+                let name = value("--quirks")?;
+                quirks = Quirks::from_preset(&name)
+                    .ok_or_else(|| format!("Unknown quirks preset: {}.", name))?;
+            }
+            "--speed" => {
+                let speed_value = value("--speed")?;
+                speed = speed_value
+                    .parse()
+                    .map_err(|_| format!("Invalid speed: {}.", speed_value))?;
+            }
+            "--frames" => {
+                let frames_value = value("--frames")?;
+                frames = frames_value
+                    .parse()
+                    .map_err(|_| format!("Invalid number of frames: {}.", frames_value))?;
+            }
+            "--format" => {
+                format = match value("--format")?.as_str() {
+                    "text" => TraceFormat::Text,
+                    "json" => TraceFormat::JsonLines,
+                    name => return Err(format!("Unknown format: {}.", name)),
+                };
+            }
+            "--output" => output_path = Some(value("--output")?),
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}.", arg)),
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}.", arg)),
+        }
+    }
+
+    Ok(Options {
+        rom_path: rom_path.ok_or_else(|| String::from("Missing rom."))?,
+        quirks,
+        speed,
+        frames,
+        format,
+        output_path,
+    })
+}
+
+fn run(options: &Options, rom: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut chip_8 = Chip8::new();
+    chip_8.set_quirks(options.quirks);
+    chip_8.try_load_rom(rom, options.quirks.draw_mode == DrawMode::Wrap)?;
+
+    let mut tracer = match &options.output_path {
+        Some(path) => Tracer::with_writer(0, BufWriter::new(File::create(path)?), options.format),
+        None => Tracer::with_writer(0, BufWriter::new(io::stdout()), options.format),
+    };
+    let mut result = Ok(());
+    for _ in 0..options.frames {
+        result = chip_8.run_frame_observed(options.speed, &mut tracer);
+        if result.is_err() {
+            break;
+        }
+    }
+    tracer.flush()?;
+    Ok(result?)
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if message.is_empty() {
+                println!("{}", USAGE);
+                return;
+            }
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let rom = match fs::read(&options.rom_path) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("Unable to read {}: {}", options.rom_path, error);
+            process::exit(1);
+        }
+    };
+
+    if let Err(error) = run(&options, &rom) {
+        let _ = io::stdout().flush();
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
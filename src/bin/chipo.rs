@@ -0,0 +1,276 @@
+// Runs a rom in a terminal.
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+use std::time::{Duration, Instant};
+
+use chipo::terminal::{queue_screen, HostKeys, Terminal};
+use chipo::{
+    Chip8, ControllerProfile, DrawMode, KeyMap, Palette, Quirks, RomDatabase, RomInfo, TextStyle,
+};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::{Print, ResetColor};
+use crossterm::{cursor, execute, queue, terminal};
+
+const USAGE: &str = "\
+Usage: chipo [OPTIONS] ROM
+
+Options:
+    --quirks PRESET     Quirks preset: chip-8, schip-legacy, or schip-modern [default: chip-8]
+    --speed N           Instructions per frame [default: 10]
+    --palette NAME      Palette: black-on-white, white-on-black, green, or amber
+                        [default: white-on-black]
+    --wrap              Wrap sprites around the edges of the screen
+    --clip              Clip sprites at the edges of the screen
+    --braille           Draw the screen with braille characters instead of half-blocks
+    --key-map NAME      Key map: qwerty, azerty, or dvorak [default: qwerty]
+    --database PATH     Rom database in the format of the chip-8 database's programs.json, which
+                        is added to the embedded database
+    -h, --help          Print this message
+
+If the rom is in the rom database, its quirks, speed, and palette are used unless they are set by
+the options above. The 4x4 block of keys from 1 to V on a QWERTY keyboard is the hex keypad, and
+the arrow keys, space, and enter are the d-pad, A, and start buttons of the controller profile of
+the rom. Press Esc to quit.";
+
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+struct Options {
+    rom_path: String,
+    quirks: Option<Quirks>,
+    draw_mode: Option<DrawMode>,
+    speed: Option<usize>,
+    palette: Option<Palette>,
+    text_style: TextStyle,
+    key_map: KeyMap,
+    database_path: Option<String>,
+}
+
+// The settings of a rom, from the options or else from the rom database.
+struct Settings {
+    quirks: Quirks,
+    speed: usize,
+    palette: Palette,
+    controller_profile: ControllerProfile,
+}
+
+impl Settings {
+    fn new(options: &Options, info: Option<&RomInfo>) -> Self {
+        let mut quirks = options
+            .quirks
+            .or_else(|| info.and_then(|info| info.quirks))
+            .unwrap_or_default();
+        if let Some(draw_mode) = options.draw_mode {
+            quirks.draw_mode = draw_mode;
+        }
+        Settings {
+            quirks,
+            speed: options
+                .speed
+                .or_else(|| info.and_then(|info| info.tickrate))
+                .unwrap_or(10),
+            palette: options
+                .palette
+                .or_else(|| info.and_then(|info| info.palette))
+                .unwrap_or_else(|| Palette::from_name("white-on-black").unwrap()),
+            controller_profile: info
+                .and_then(|info| info.controller_profile)
+                .unwrap_or_else(ControllerProfile::standard),
+        }
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut rom_path = None;
+    let mut quirks = None;
+    let mut draw_mode = None;
+    let mut speed = None;
+    let mut palette = None;
+    let mut text_style = TextStyle::HalfBlock;
+    let mut key_map = KeyMap::qwerty();
+    let mut database_path = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}.", name))
+        };
+        match arg.as_str() {
+            "--quirks" => {
+                let name = value("--quirks")?;
+                quirks = Some(
+                    Quirks::from_preset(&name)
+                        .ok_or_else(|| format!("Unknown quirks preset: {}.", name))?,
+                );
+            }
+            "--speed" => {
+                let speed_value = value("--speed")?;
+                speed = Some(
+                    speed_value
+                        .parse()
+                        .map_err(|_| format!("Invalid speed: {}.", speed_value))?,
+                );
+            }
+            "--palette" => {
+                let name = value("--palette")?;
+                palette = Some(
+                    Palette::from_name(&name)
+                        .ok_or_else(|| format!("Unknown palette: {}.", name))?,
+                );
+            }
+            "--wrap" => draw_mode = Some(DrawMode::Wrap),
+            "--clip" => draw_mode = Some(DrawMode::Clip),
+            "--braille" => text_style = TextStyle::Braille,
+            "--key-map" => {
+                key_map = match value("--key-map")?.as_str() {
+                    "qwerty" => KeyMap::qwerty(),
+                    "azerty" => KeyMap::azerty(),
+                    "dvorak" => KeyMap::dvorak(),
+                    name => return Err(format!("Unknown key map: {}.", name)),
+                };
+            }
+            "--database" => database_path = Some(value("--database")?),
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}.", arg)),
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}.", arg)),
+        }
+    }
+
+    Ok(Options {
+        rom_path: rom_path.ok_or_else(|| String::from("Missing rom."))?,
+        quirks,
+        draw_mode,
+        speed,
+        palette,
+        text_style,
+        key_map,
+        database_path,
+    })
+}
+
+fn draw(
+    chip_8: &Chip8,
+    options: &Options,
+    settings: &Settings,
+    stdout: &mut impl Write,
+) -> io::Result<()> {
+    queue_screen(stdout, chip_8, options.text_style, settings.palette, (0, 0))?;
+    let status_row = chip_8.screen_text(options.text_style).len() as u16;
+    queue!(stdout, cursor::MoveTo(0, status_row), Print("Esc to quit"))?;
+    stdout.flush()
+}
+
+fn run(options: &Options, settings: &Settings, rom: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut chip_8 = Chip8::new();
+    chip_8.set_quirks(settings.quirks);
+    chip_8.set_key_map(options.key_map.clone());
+    chip_8.set_controller_profile(settings.controller_profile);
+    chip_8.try_load_rom(rom, settings.quirks.draw_mode == DrawMode::Wrap)?;
+
+    let terminal = Terminal::new()?;
+    let mut host_keys = HostKeys::new(&terminal);
+    let mut stdout = io::stdout();
+    let mut screen_width = chip_8.screen_width();
+    let mut sound_timer = 0;
+    let mut next_frame = Instant::now();
+    execute!(stdout, terminal::Clear(terminal::ClearType::All))?;
+    draw(&chip_8, options, settings, &mut stdout)?;
+
+    loop {
+        let now = Instant::now();
+        if now < next_frame {
+            if !event::poll(next_frame - now)? {
+                continue;
+            }
+            let (code, modifiers, kind) = match event::read()? {
+                Event::Key(KeyEvent {
+                    code,
+                    modifiers,
+                    kind,
+                    ..
+                }) => (code, modifiers, kind),
+                _ => continue,
+            };
+            match code {
+                KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                _ => host_keys.handle_key(&mut chip_8, code, kind, now),
+            };
+            continue;
+        }
+
+        next_frame += FRAME_DURATION;
+        if next_frame < now {
+            next_frame = now + FRAME_DURATION;
+        }
+
+        host_keys.release_expired(&mut chip_8, now);
+
+        chip_8.run_frame(settings.speed)?;
+
+        if sound_timer == 0 && chip_8.sound_timer() > 0 {
+            queue!(stdout, Print('\u{7}'))?;
+        }
+        sound_timer = chip_8.sound_timer();
+
+        if chip_8.screen_width() != screen_width {
+            screen_width = chip_8.screen_width();
+            queue!(
+                stdout,
+                ResetColor,
+                terminal::Clear(terminal::ClearType::All)
+            )?;
+            draw(&chip_8, options, settings, &mut stdout)?;
+        } else if chip_8.should_draw() {
+            draw(&chip_8, options, settings, &mut stdout)?;
+        } else {
+            stdout.flush()?;
+        }
+    }
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if message.is_empty() {
+                println!("{}", USAGE);
+                return;
+            }
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let rom = match fs::read(&options.rom_path) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("Unable to read {}: {}", options.rom_path, error);
+            process::exit(1);
+        }
+    };
+
+    let mut database = RomDatabase::embedded();
+    if let Some(path) = &options.database_path {
+        let user_database = fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|json| RomDatabase::parse(&json).map_err(|error| error.to_string()));
+        match user_database {
+            Ok(user_database) => database.extend(user_database),
+            Err(error) => {
+                eprintln!("Unable to load {}: {}", path, error);
+                process::exit(1);
+            }
+        }
+    }
+    let settings = Settings::new(&options, database.lookup(&rom));
+
+    if let Err(error) = run(&options, &settings, &rom) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
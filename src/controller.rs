@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+const BUTTON_COUNT: usize = 10;
+
+/// A button of an abstract gamepad.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Button {
+    /// The up button of the d-pad.
+    Up,
+    /// The down button of the d-pad.
+    Down,
+    /// The left button of the d-pad.
+    Left,
+    /// The right button of the d-pad.
+    Right,
+    /// The bottom face button.
+    A,
+    /// The right face button.
+    B,
+    /// The left face button.
+    X,
+    /// The top face button.
+    Y,
+    /// The start button.
+    Start,
+    /// The select button.
+    Select,
+}
+
+impl Button {
+    const ALL: [Button; BUTTON_COUNT] = [
+        Button::Up,
+        Button::Down,
+        Button::Left,
+        Button::Right,
+        Button::A,
+        Button::B,
+        Button::X,
+        Button::Y,
+        Button::Start,
+        Button::Select,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Button::Up => "up",
+            Button::Down => "down",
+            Button::Left => "left",
+            Button::Right => "right",
+            Button::A => "a",
+            Button::B => "b",
+            Button::X => "x",
+            Button::Y => "y",
+            Button::Start => "start",
+            Button::Select => "select",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Button> {
+        Button::ALL
+            .iter()
+            .cloned()
+            .find(|button| button.name() == name)
+    }
+}
+
+/// A mapping from the buttons of an abstract gamepad to keys of the hex keypad.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ControllerProfile {
+    bindings: [Option<u8>; BUTTON_COUNT],
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl ControllerProfile {
+    /// Constructs a new, empty `ControllerProfile`.
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        ControllerProfile {
+            bindings: [None; BUTTON_COUNT],
+        }
+    }
+
+    /// Constructs a new `ControllerProfile` that uses the conventional movement keys of chip-8
+    /// games: `5`, `8`, `7`, and `9` for the d-pad, `6` for `A`, `4` for `B`, and `F` for start.
+    pub fn standard() -> Self {
+        let mut profile = ControllerProfile::new();
+        profile.bind(Button::Up, 0x5);
+        profile.bind(Button::Down, 0x8);
+        profile.bind(Button::Left, 0x7);
+        profile.bind(Button::Right, 0x9);
+        profile.bind(Button::A, 0x6);
+        profile.bind(Button::B, 0x4);
+        profile.bind(Button::Start, 0xF);
+        profile
+    }
+
+    /// Binds `button` to the hex key `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is not a hex key.
+    pub fn bind(&mut self, button: Button, key: u8) {
+        assert!(key < 16, "Invalid hex key: {}", key);
+        self.bindings[button as usize] = Some(key);
+    }
+
+    /// Removes the binding of `button`.
+    pub fn unbind(&mut self, button: Button) {
+        self.bindings[button as usize] = None;
+    }
+
+    /// Returns the hex key bound to `button`.
+    pub fn get(&self, button: Button) -> Option<u8> {
+        self.bindings[button as usize]
+    }
+}
+
+/// An error that occurred while parsing controller profiles.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProfileParseError {
+    /// The line of the config where the error occurred, starting from `1`.
+    pub line: usize,
+    /// A description of the error.
+    pub message: String,
+}
+
+impl fmt::Display for ProfileParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for ProfileParseError {}
+
+#[cfg(target_arch = "wasm32")]
+impl From<ProfileParseError> for JsValue {
+    fn from(error: ProfileParseError) -> Self {
+        js_sys::Error::new(&error.to_string()).into()
+    }
+}
+
+/// A collection of controller profiles keyed by the SHA-1 hash of roms.
+///
+/// Profiles are loaded from a config where each section is headed by the lowercase hex SHA-1 hash
+/// of a rom, or `default` for roms without a profile, followed by `button = key` lines. Buttons
+/// are `up`, `down`, `left`, `right`, `a`, `b`, `x`, `y`, `start`, and `select`, and keys are
+/// single hex digits. Lines starting with `#` are comments. The sections of roms start from the
+/// default profile, so they only need to bind the buttons that differ from it.
+///
+/// ```text
+/// [default]
+/// up = 5
+/// down = 8
+///
+/// # Tetris
+/// [0123456789abcdef0123456789abcdef01234567]
+/// left = 5
+/// right = 6
+/// a = 4
+/// ```
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ControllerProfiles {
+    default: ControllerProfile,
+    profiles: HashMap<String, ControllerProfile>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl ControllerProfiles {
+    /// Constructs a new `ControllerProfiles` with no rom specific profiles, and
+    /// `ControllerProfile::standard` as the default profile.
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        ControllerProfiles {
+            default: ControllerProfile::standard(),
+            profiles: HashMap::new(),
+        }
+    }
+
+    /// Parses controller profiles from a config. Sections that appear multiple times are merged.
+    pub fn parse(config: &str) -> Result<Self, ProfileParseError> {
+        let mut ret = ControllerProfiles::new();
+        // The bindings of each rom section, which are applied on top of the default profile once
+        // all of it has been read.
+        let mut overrides: HashMap<String, ControllerProfile> = HashMap::new();
+        let mut section: Option<String> = None;
+        let mut seen_default = false;
+
+        for (index, line) in config.lines().enumerate() {
+            let error = |message: String| ProfileParseError {
+                line: index + 1,
+                message,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                let name = line[1..line.len() - 1].trim().to_lowercase();
+                if name == "default" {
+                    // The first default section replaces the standard profile, and later ones are
+                    // merged into it.
+                    if !seen_default {
+                        ret.default = ControllerProfile::new();
+                        seen_default = true;
+                    }
+                } else if name.len() != 40 || !name.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(error(format!("Invalid rom hash: {}", name)));
+                }
+                section = Some(name);
+                continue;
+            }
+
+            let section = match &section {
+                Some(section) => section,
+                None => return Err(error(String::from("Binding outside of a section."))),
+            };
+            let (button, key) = match line.find('=') {
+                Some(position) => (line[..position].trim(), line[position + 1..].trim()),
+                None => return Err(error(format!("Expected `button = key`: {}", line))),
+            };
+            let button = Button::from_name(&button.to_lowercase())
+                .ok_or_else(|| error(format!("Unknown button: {}", button)))?;
+            let key = match u8::from_str_radix(key, 16) {
+                Ok(value) if key.len() == 1 => value,
+                _ => return Err(error(format!("Invalid hex key: {}", key))),
+            };
+
+            let profile = if section == "default" {
+                &mut ret.default
+            } else {
+                overrides.entry(section.clone()).or_default()
+            };
+            profile.bind(button, key);
+        }
+
+        for (rom_hash, bindings) in overrides {
+            let mut profile = ret.default;
+            for &button in Button::ALL.iter() {
+                if let Some(key) = bindings.get(button) {
+                    profile.bind(button, key);
+                }
+            }
+            ret.profiles.insert(rom_hash, profile);
+        }
+
+        Ok(ret)
+    }
+
+    /// Returns the default profile that is used for roms without a profile.
+    pub fn default_profile(&self) -> ControllerProfile {
+        self.default
+    }
+
+    /// Sets the profile of the rom with the SHA-1 hash `rom_hash`, which is a lowercase hex
+    /// string.
+    pub fn insert(&mut self, rom_hash: &str, profile: ControllerProfile) {
+        self.profiles.insert(rom_hash.to_lowercase(), profile);
+    }
+
+    /// Returns the profile of the rom with the SHA-1 hash `rom_hash`, or the default profile if
+    /// the rom does not have a profile.
+    pub fn get(&self, rom_hash: &str) -> ControllerProfile {
+        self.profiles
+            .get(&rom_hash.to_lowercase())
+            .cloned()
+            .unwrap_or(self.default)
+    }
+}
+
+impl Default for ControllerProfiles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    #[test]
+    fn test_parse() {
+        let config = format!(
+            "# Controls\n[default]\nup = 2\n\n[{}]\nLeft = 5\nright=6\nstart = f\n",
+            HASH.to_uppercase(),
+        );
+        let profiles = ControllerProfiles::parse(&config).unwrap();
+
+        let default = profiles.default_profile();
+        assert_eq!(default.get(Button::Up), Some(0x2));
+        assert_eq!(default.get(Button::Down), None);
+
+        let profile = profiles.get(HASH);
+        assert_eq!(profile.get(Button::Left), Some(0x5));
+        assert_eq!(profile.get(Button::Right), Some(0x6));
+        assert_eq!(profile.get(Button::Start), Some(0xF));
+        assert_eq!(profile.get(Button::Up), Some(0x2));
+        assert_eq!(profile.get(Button::Down), None);
+
+        assert_eq!(profiles.get(&"0".repeat(40)), default);
+    }
+
+    #[test]
+    fn test_parse_repeated_default() {
+        let config = format!(
+            "[default]
+up = 2
+[{}]
+a = 4
+[default]
+down = 8
+",
+            HASH
+        );
+        let profiles = ControllerProfiles::parse(&config).unwrap();
+
+        let default = profiles.default_profile();
+        assert_eq!(default.get(Button::Up), Some(0x2));
+        assert_eq!(default.get(Button::Down), Some(0x8));
+        assert_eq!(default.get(Button::Left), None);
+        let profile = profiles.get(HASH);
+        assert_eq!(profile.get(Button::A), Some(0x4));
+        assert_eq!(profile.get(Button::Down), Some(0x8));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for &(config, expected) in &[
+            ("up = 5", "line 1: Binding outside of a section."),
+            ("[default]\n\nup 5", "line 3: Expected `button = key`: up 5"),
+            ("[default]\njump = 5", "line 2: Unknown button: jump"),
+            ("[default]\nup = 10", "line 2: Invalid hex key: 10"),
+            ("[default]\nup = +5", "line 2: Invalid hex key: +5"),
+            ("[tetris]", "line 1: Invalid rom hash: tetris"),
+        ] {
+            let error = ControllerProfiles::parse(config).unwrap_err();
+            assert_eq!(error.to_string(), expected, "{:?}", config);
+        }
+    }
+}
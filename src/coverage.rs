@@ -0,0 +1,486 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::Range;
+
+use crate::{disassemble, Chip8, Instruction, LargeSprite, ScreenMode, StepObserver, SymbolMap};
+
+const EXECUTED: u8 = 1;
+const READ: u8 = 2;
+const WRITTEN: u8 = 4;
+
+/// The kinds of self-modifying code.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum SelfModificationKind {
+    /// A byte that was executed before was written.
+    CodeWritten,
+    /// An instruction was executed after one of its bytes was written.
+    WrittenExecuted,
+}
+
+/// A write to code that was executed, or the execution of code that was written.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SelfModification {
+    /// The kind of self-modification.
+    pub kind: SelfModificationKind,
+    /// The address of the byte that was written for `CodeWritten`, or of the instruction that was
+    /// executed for `WrittenExecuted`.
+    pub address: u16,
+    /// The address of the `FX33` or `FX55` instruction that wrote the byte.
+    pub writer: u16,
+    /// The opcode of the instruction that wrote the byte.
+    pub writer_opcode: u16,
+}
+
+impl fmt::Display for SelfModification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let writer = match Instruction::decode(self.writer_opcode) {
+            Some(instruction) => format!("0x{:03X} ({})", self.writer, instruction),
+            None => format!("0x{:03X}", self.writer),
+        };
+        match self.kind {
+            SelfModificationKind::CodeWritten => write!(
+                f,
+                "{} wrote 0x{:03X}, which was executed",
+                writer, self.address
+            ),
+            SelfModificationKind::WrittenExecuted => write!(
+                f,
+                "0x{:03X} was executed after {} wrote it",
+                self.address, writer
+            ),
+        }
+    }
+}
+
+/// Records which bytes of memory a `Chip8` executed as instructions, read as sprites or data with
+/// `DXYN` and `FX65`, and wrote with `FX33` and `FX55`.
+///
+/// Self-modifying code is detected along the way: writes to bytes that were executed, and the
+/// execution of bytes that were written, are recorded once per address and writing instruction.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Coverage {
+    flags: Vec<u8>,
+    // The number of times an instruction started at each address.
+    executions: Vec<u64>,
+    // The address and opcode of the last instruction that wrote each byte.
+    writers: Vec<Option<(u16, u16)>>,
+    self_modifications: Vec<SelfModification>,
+    reported: BTreeSet<(SelfModificationKind, u16, u16)>,
+    // The bytes that the instruction being executed reads or writes.
+    pending_access: Option<MemoryAccess>,
+}
+
+impl Coverage {
+    /// Constructs a new empty `Coverage`.
+    pub fn new() -> Self {
+        Coverage::default()
+    }
+
+    /// Removes all the recorded accesses.
+    pub fn clear(&mut self) {
+        *self = Coverage::new();
+    }
+
+    /// Returns `true` if the byte at `address` was executed as part of an instruction.
+    pub fn is_executed(&self, address: u16) -> bool {
+        self.has_flag(address, EXECUTED)
+    }
+
+    /// Returns `true` if the byte at `address` was read by `DXYN` or `FX65`.
+    pub fn is_read(&self, address: u16) -> bool {
+        self.has_flag(address, READ)
+    }
+
+    /// Returns `true` if the byte at `address` was written by `FX33` or `FX55`.
+    pub fn is_written(&self, address: u16) -> bool {
+        self.has_flag(address, WRITTEN)
+    }
+
+    /// Returns the address of the last instruction that wrote the byte at `address`.
+    pub fn writer(&self, address: u16) -> Option<u16> {
+        self.writers
+            .get(address as usize)
+            .copied()
+            .flatten()
+            .map(|(writer, _)| writer)
+    }
+
+    /// Returns the self-modifications in the order they happened.
+    pub fn self_modifications(&self) -> &[SelfModification] {
+        &self.self_modifications
+    }
+
+    /// Returns the number of times the instruction at `address` was executed.
+    pub fn execution_count(&self, address: u16) -> u64 {
+        self.executions.get(address as usize).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of bytes in `range` that were executed, read, or written.
+    pub fn covered_bytes(&self, range: Range<usize>) -> usize {
+        range
+            .filter(|address| self.flags.get(*address).map_or(false, |flags| *flags != 0))
+            .count()
+    }
+
+    /// Writes the disassembly of `range` in `memory` to `out`, with each line prefixed by `X` if
+    /// it was executed, `R` if it was read, and `W` if it was written, or `.` otherwise.
+    /// Executed addresses and addresses that were never accessed are disassembled as
+    /// instructions, and other addresses as data bytes.
+    ///
+    /// ```text
+    /// X.. 200 A20A LD I, 0x20A
+    /// X.. 202 D015 DRW V0, V1, 5
+    /// ... 204 1204 JP 0x204
+    /// .R. 20A F0   DB 0xF0
+    /// ```
+    pub fn write_annotated(
+        &self,
+        out: &mut impl Write,
+        memory: &[u8],
+        range: Range<usize>,
+    ) -> io::Result<()> {
+        let mut address = range.start;
+        let end = range.end.min(memory.len());
+        while address < end {
+            let flags = self.flags.get(address).copied().unwrap_or(0);
+            // Bytes followed by an instruction are data, unless they start an instruction.
+            let is_data =
+                flags & (READ | WRITTEN) != 0 || self.has_flag(address as u16 + 1, EXECUTED);
+            // Instructions that would end past the range or memory are shown as a single byte.
+            let length = if (flags & EXECUTED == 0 && is_data) || address + 1 >= end {
+                1
+            } else {
+                2
+            };
+            let (bytes, text) = if length == 1 {
+                (
+                    format!("{:02X}", memory[address]),
+                    format!("DB 0x{:02X}", memory[address]),
+                )
+            } else {
+                (
+                    format!("{:02X}{:02X}", memory[address], memory[address + 1]),
+                    disassemble(memory, address),
+                )
+            };
+
+            let mut all_flags = 0;
+            for offset in 0..length {
+                all_flags |= self.flags.get(address + offset).copied().unwrap_or(0);
+            }
+            writeln!(
+                out,
+                "{}{}{} {:03X} {:<4} {}",
+                if all_flags & EXECUTED != 0 { 'X' } else { '.' },
+                if all_flags & READ != 0 { 'R' } else { '.' },
+                if all_flags & WRITTEN != 0 { 'W' } else { '.' },
+                address,
+                bytes,
+                text
+            )?;
+            address += length;
+        }
+        Ok(())
+    }
+
+    /// Writes the coverage of the source lines and labels of `symbols` to `out` in the lcov
+    /// tracefile format. A line is hit as many times as its most executed address, or once if
+    /// its addresses were only read or written as data.
+    pub fn write_lcov(&self, out: &mut impl Write, symbols: &SymbolMap) -> io::Result<()> {
+        // The hit counts of the lines of each source file.
+        let mut files: BTreeMap<&str, BTreeMap<usize, u64>> = BTreeMap::new();
+        for (address, location) in symbols.locations() {
+            let is_covered = self.covered_bytes(address as usize..address as usize + 1) > 0;
+            let count = self.execution_count(address).max(is_covered as u64);
+            let hits = files
+                .entry(&location.path)
+                .or_default()
+                .entry(location.line)
+                .or_default();
+            *hits = (*hits).max(count);
+        }
+
+        for (path, lines) in &files {
+            writeln!(out, "TN:")?;
+            writeln!(out, "SF:{}", path)?;
+            let functions: Vec<(usize, &str, u64)> = symbols
+                .labels()
+                .filter_map(|(address, name)| match symbols.location(address) {
+                    Some(location) if location.path == *path => {
+                        Some((location.line, name, self.execution_count(address)))
+                    }
+                    _ => None,
+                })
+                .collect();
+            for (line, name, _) in &functions {
+                writeln!(out, "FN:{},{}", line, name)?;
+            }
+            for (_, name, count) in &functions {
+                writeln!(out, "FNDA:{},{}", count, name)?;
+            }
+            writeln!(out, "FNF:{}", functions.len())?;
+            writeln!(
+                out,
+                "FNH:{}",
+                functions.iter().filter(|(_, _, count)| *count > 0).count()
+            )?;
+            for (line, count) in lines {
+                writeln!(out, "DA:{},{}", line, count)?;
+            }
+            writeln!(out, "LF:{}", lines.len())?;
+            writeln!(
+                out,
+                "LH:{}",
+                lines.values().filter(|count| **count > 0).count()
+            )?;
+            writeln!(out, "end_of_record")?;
+        }
+        Ok(())
+    }
+
+    fn report(&mut self, modification: SelfModification) {
+        let key = (modification.kind, modification.address, modification.writer);
+        if self.reported.insert(key) {
+            self.self_modifications.push(modification);
+        }
+    }
+
+    fn has_flag(&self, address: u16, flag: u8) -> bool {
+        self.flags
+            .get(address as usize)
+            .map_or(false, |flags| flags & flag != 0)
+    }
+
+    fn mark(&mut self, range: Range<usize>, flag: u8) {
+        let end = range.end.min(self.flags.len());
+        for flags in &mut self.flags[range.start.min(end)..end] {
+            *flags |= flag;
+        }
+    }
+}
+
+impl StepObserver for Coverage {
+    fn before_step(&mut self, chip_8: &Chip8, _address: u16, opcode: u16) {
+        self.pending_access =
+            Instruction::decode(opcode).and_then(|instruction| memory_access(chip_8, instruction));
+    }
+
+    fn after_step(&mut self, chip_8: &Chip8, address: u16, opcode: u16) {
+        let address = address as usize;
+        let size = chip_8.memory().len();
+        if self.flags.len() < size {
+            self.flags.resize(size, 0);
+            self.executions.resize(size, 0);
+            self.writers.resize(size, None);
+        }
+        if let Some((writer, writer_opcode)) = self.writers[address..address + 2]
+            .iter()
+            .find_map(|writer| *writer)
+        {
+            self.report(SelfModification {
+                kind: SelfModificationKind::WrittenExecuted,
+                address: address as u16,
+                writer,
+                writer_opcode,
+            });
+        }
+        self.mark(address..address + 2, EXECUTED);
+        self.executions[address] += 1;
+
+        match self.pending_access.take() {
+            Some(MemoryAccess::Read(range)) => self.mark(range, READ),
+            Some(MemoryAccess::Write(range)) => {
+                let range = range.start.min(size)..range.end.min(size);
+                for written in range.clone() {
+                    if self.flags[written] & EXECUTED != 0 {
+                        self.report(SelfModification {
+                            kind: SelfModificationKind::CodeWritten,
+                            address: written as u16,
+                            writer: address as u16,
+                            writer_opcode: opcode,
+                        });
+                    }
+                    self.writers[written] = Some((address as u16, opcode));
+                }
+                self.mark(range, WRITTEN);
+            }
+            None => {}
+        }
+    }
+}
+
+// The bytes of memory that an instruction reads or writes, besides the instruction itself.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum MemoryAccess {
+    Read(Range<usize>),
+    Write(Range<usize>),
+}
+
+// Returns the bytes that `instruction` will read or write when `chip_8` executes it next.
+fn memory_access(chip_8: &Chip8, instruction: Instruction) -> Option<MemoryAccess> {
+    let index = chip_8.index as usize;
+    let access = match instruction {
+        Instruction::Draw(_, _, n) => {
+            let is_super_mode = chip_8.screen.get_mode() == ScreenMode::Super;
+            let length = match (n, is_super_mode, chip_8.quirks.large_sprite) {
+                (0, true, _) | (0, false, LargeSprite::Square) => 32,
+                (0, false, LargeSprite::Tall) => 16,
+                _ => n,
+            };
+            MemoryAccess::Read(index..index + length)
+        }
+        Instruction::LoadRegisters(x) => MemoryAccess::Read(index..index + x + 1),
+        Instruction::StoreBcd(_) => MemoryAccess::Write(index..index + 3),
+        Instruction::StoreRegisters(x) => MemoryAccess::Write(index..index + x + 1),
+        _ => return None,
+    };
+    Some(access)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 200: LD I, 0x20E; 202: DRW V0, V1, 2; 204: LD I, 0x210; 206: LD B, V0; 208: LD V1, [I];
+    // 20A: JP 0x20A; 20C: JP 0x200; 20E: sprite data
+    const ROM: [u8; 16] = [
+        0xA2, 0x0E, 0xD0, 0x12, 0xA2, 0x10, 0xF0, 0x33, 0xF1, 0x65, 0x12, 0x0A, 0x12, 0x00, 0xF0,
+        0x90,
+    ];
+
+    fn run(cycles: usize) -> (Coverage, Chip8) {
+        let mut coverage = Coverage::new();
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&ROM, false);
+        for _ in 0..cycles {
+            chip_8.step_observed(&mut coverage).unwrap();
+        }
+        (coverage, chip_8)
+    }
+
+    #[test]
+    fn test_step() {
+        let (coverage, _) = run(7);
+        assert!(coverage.is_executed(0x200) && coverage.is_executed(0x20B));
+        assert!(!coverage.is_executed(0x20C));
+        assert_eq!(coverage.execution_count(0x20A), 2);
+        assert!(coverage.is_read(0x20E) && coverage.is_read(0x20F));
+        assert!(!coverage.is_written(0x20E));
+        assert!(coverage.is_read(0x211) && coverage.is_written(0x211));
+        assert!(coverage.is_written(0x212) && !coverage.is_read(0x212));
+        assert_eq!(coverage.covered_bytes(0x200..0x214), 17);
+        assert_eq!(coverage.covered_bytes(0x214..0x1000), 0);
+    }
+
+    #[test]
+    fn test_self_modifications() {
+        // 200: LD I, 0x208; 202: LD V0, 0x12; 204: LD V1, 0x0A; 206: LD [I], V1; 208: CLS, which
+        // becomes JP 0x20A; 20A: LD [I], V1; 20C: JP 0x20C
+        let mut coverage = Coverage::new();
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(
+            &[
+                0xA2, 0x08, 0x60, 0x12, 0x61, 0x0A, 0xF1, 0x55, 0x00, 0xE0, 0xF1, 0x55, 0x12, 0x0C,
+            ],
+            false,
+        );
+        for _ in 0..10 {
+            chip_8.step_observed(&mut coverage).unwrap();
+        }
+        let modification = |kind, address, writer| SelfModification {
+            kind,
+            address,
+            writer,
+            writer_opcode: 0xF155,
+        };
+        assert_eq!(
+            coverage.self_modifications(),
+            &[
+                modification(SelfModificationKind::WrittenExecuted, 0x208, 0x206),
+                modification(SelfModificationKind::CodeWritten, 0x208, 0x20A),
+                modification(SelfModificationKind::CodeWritten, 0x209, 0x20A),
+            ]
+        );
+        assert_eq!(coverage.writer(0x208), Some(0x20A));
+        assert_eq!(coverage.writer(0x20A), None);
+        assert_eq!(
+            coverage.self_modifications()[0].to_string(),
+            "0x208 was executed after 0x206 (LD [I], V1) wrote it"
+        );
+        assert_eq!(
+            coverage.self_modifications()[1].to_string(),
+            "0x20A (LD [I], V1) wrote 0x208, which was executed"
+        );
+    }
+
+    #[test]
+    fn test_annotated() {
+        let (coverage, chip_8) = run(7);
+        let mut out = Vec::new();
+        coverage
+            .write_annotated(&mut out, chip_8.memory(), 0x200..0x214)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "X.. 200 A20E LD I, 0x20E\n\
+             X.. 202 D012 DRW V0, V1, 2\n\
+             X.. 204 A210 LD I, 0x210\n\
+             X.. 206 F033 LD B, V0\n\
+             X.. 208 F165 LD V1, [I]\n\
+             X.. 20A 120A JP 0x20A\n\
+             ... 20C 1200 JP 0x200\n\
+             .R. 20E F0   DB 0xF0\n\
+             .R. 20F 90   DB 0x90\n\
+             .RW 210 00   DB 0x00\n\
+             .RW 211 00   DB 0x00\n\
+             ..W 212 00   DB 0x00\n\
+             ... 213 00   DB 0x00\n"
+        );
+    }
+
+    #[test]
+    fn test_annotated_edges() {
+        // Instructions at 0xFFD and 0xFFE mark the last byte of memory as executed.
+        let (mut coverage, chip_8) = run(1);
+        for address in 0xFFD..0x1000 {
+            coverage.flags[address] |= EXECUTED;
+        }
+        let mut out = Vec::new();
+        coverage
+            .write_annotated(&mut out, chip_8.memory(), 0xFFE..0x1000)
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "X.. FFE 0000 DW 0x0000\n");
+
+        let mut out = Vec::new();
+        coverage
+            .write_annotated(&mut out, chip_8.memory(), 0xFFF..0x1000)
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "X.. FFF 00   DB 0x00\n");
+
+        let mut out = Vec::new();
+        coverage
+            .write_annotated(&mut out, chip_8.memory(), 0x200..0x201)
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "X.. 200 A2   DB 0xA2\n");
+    }
+
+    #[test]
+    fn test_lcov() {
+        let (coverage, _) = run(7);
+        let symbols = SymbolMap::parse(
+            "200 main\n200 test.8o:2\n202 test.8o:3\n204 test.8o:3\n20A test.8o:5\n\
+             20C unused\n20C test.8o:7\n20E sprite\n20E test.8o:9\n",
+        )
+        .unwrap();
+        let mut out = Vec::new();
+        coverage.write_lcov(&mut out, &symbols).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "TN:\nSF:test.8o\n\
+             FN:2,main\nFN:7,unused\nFN:9,sprite\n\
+             FNDA:1,main\nFNDA:0,unused\nFNDA:0,sprite\nFNF:3\nFNH:1\n\
+             DA:2,1\nDA:3,1\nDA:5,2\nDA:7,0\nDA:9,1\nLF:5\nLH:4\nend_of_record\n"
+        );
+    }
+}
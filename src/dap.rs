@@ -0,0 +1,1030 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::json::{json_object, Json};
+use crate::{disassemble, Chip8, Debugger, DrawMode, Instruction, Quirks, SymbolMap};
+
+const THREAD_ID: u64 = 1;
+// Running roms execute a frame every 60th of a second, like the timers.
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// The largest message body that is read, so that a bad `Content-Length` is not allocated.
+const MAX_MESSAGE_LENGTH: usize = 16 * 1024 * 1024;
+
+// The variables references of the scopes.
+const REGISTERS_REFERENCE: u64 = 1;
+const TIMERS_REFERENCE: u64 = 2;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Execution {
+    Stopped,
+    Running,
+    // Runs until the subroutine called by the instruction being stepped over returns to `address`.
+    StepOver { stack_pointer: u16, address: u16 },
+    // Runs until the current subroutine returns.
+    StepOut { stack_pointer: u16 },
+}
+
+/// A Debug Adapter Protocol server that launches a rom and debugs it from an editor.
+///
+/// The `launch` request takes the path of the rom as `program`, and optionally the path of a
+/// [`SymbolMap`] as `symbols`, a quirks preset name as `quirks`, the number of instructions per
+/// frame as `speed`, and `stopOnEntry`. Breakpoints can be set on source lines that appear in the
+/// symbol map, or on instruction addresses. The registers and timers are reported as variables
+/// and the return addresses on the stack as stack frames.
+pub struct DapServer {
+    chip_8: Chip8,
+    debugger: Debugger,
+    symbols: SymbolMap,
+    // The directory that relative paths in the symbol map are relative to.
+    symbols_directory: PathBuf,
+    source_breakpoints: BTreeMap<String, Vec<u16>>,
+    instruction_breakpoints: Vec<u16>,
+    cycles_per_frame: usize,
+    // The number of instructions executed since the timers were last decremented.
+    cycles: usize,
+    execution: Execution,
+    stop_on_entry: bool,
+    should_exit: bool,
+    seq: u64,
+}
+
+impl DapServer {
+    /// Constructs a new `DapServer`. A rom is loaded when the client sends a `launch` request.
+    pub fn new() -> Self {
+        DapServer {
+            chip_8: Chip8::new(),
+            debugger: Debugger::new(),
+            symbols: SymbolMap::new(),
+            symbols_directory: PathBuf::new(),
+            source_breakpoints: BTreeMap::new(),
+            instruction_breakpoints: Vec::new(),
+            cycles_per_frame: 10,
+            cycles: 0,
+            execution: Execution::Stopped,
+            stop_on_entry: false,
+            should_exit: false,
+            seq: 0,
+        }
+    }
+
+    /// Serves a client that sends requests to `input` and reads responses and events from
+    /// `output`, such as the standard input and output of a debug adapter process. Returns when
+    /// the client disconnects.
+    pub fn serve(
+        &mut self,
+        input: impl Read + Send + 'static,
+        mut output: impl Write,
+    ) -> io::Result<()> {
+        // Requests are read on another thread so that they can interrupt a running rom.
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(input);
+            loop {
+                let message = read_message(&mut reader);
+                let is_done = !matches!(message, Ok(Some(_)));
+                if sender.send(message).is_err() || is_done {
+                    break;
+                }
+            }
+        });
+
+        let mut next_frame = Instant::now();
+        while !self.should_exit {
+            let request = if self.execution == Execution::Stopped {
+                match receiver.recv() {
+                    Ok(message) => message?,
+                    Err(_) => None,
+                }
+            } else {
+                let now = Instant::now();
+                match receiver.recv_timeout(next_frame.saturating_duration_since(now)) {
+                    Ok(message) => message?,
+                    Err(RecvTimeoutError::Timeout) => {
+                        next_frame += FRAME_DURATION;
+                        if next_frame < now {
+                            next_frame = now + FRAME_DURATION;
+                        }
+                        for message in self.run_frame() {
+                            self.send(&mut output, message)?;
+                        }
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => None,
+                }
+            };
+            let request = match request {
+                Some(request) => request,
+                None => return Ok(()),
+            };
+            for message in self.handle_request(&request) {
+                self.send(&mut output, message)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Numbers a response or event in the order that it is sent.
+    fn send(&mut self, output: &mut impl Write, message: Json) -> io::Result<()> {
+        self.seq += 1;
+        let mut message = message;
+        if let Json::Object(members) = &mut message {
+            members.insert(0, ("seq".to_string(), Json::from(self.seq)));
+        }
+        write_message(output, &message)
+    }
+
+    fn stopped(&mut self, reason: &str, description: Option<String>) -> Json {
+        self.execution = Execution::Stopped;
+        let mut body = json_object! {
+            "reason" => reason,
+            "threadId" => THREAD_ID,
+            "allThreadsStopped" => true,
+        };
+        if let (Json::Object(members), Some(description)) = (&mut body, description) {
+            members.push(("text".to_string(), Json::from(description.clone())));
+            members.push(("description".to_string(), Json::from(description)));
+        }
+        event("stopped", body)
+    }
+
+    fn exited(&mut self) -> Vec<Json> {
+        self.execution = Execution::Stopped;
+        vec![
+            event("exited", json_object! { "exitCode" => 0 }),
+            event("terminated", json_object! {}),
+        ]
+    }
+
+    fn handle_request(&mut self, request: &Json) -> Vec<Json> {
+        let command = request
+            .get("command")
+            .and_then(Json::as_str)
+            .unwrap_or("")
+            .to_string();
+        let arguments = request.get("arguments").cloned().unwrap_or(Json::Null);
+        let mut events = Vec::new();
+
+        let result = match command.as_str() {
+            "initialize" => Ok(json_object! {
+                "supportsConfigurationDoneRequest" => true,
+                "supportsSetVariable" => true,
+                "supportsReadMemoryRequest" => true,
+                "supportsWriteMemoryRequest" => true,
+                "supportsDisassembleRequest" => true,
+                "supportsInstructionBreakpoints" => true,
+                "supportsSteppingGranularity" => false,
+                "supportsTerminateRequest" => true,
+            }),
+            "launch" => self.launch(&arguments).map(|()| {
+                events.push(event("initialized", json_object! {}));
+                Json::Null
+            }),
+            "setBreakpoints" => self.set_breakpoints(&arguments),
+            "setInstructionBreakpoints" => self.set_instruction_breakpoints(&arguments),
+            "configurationDone" => {
+                if self.stop_on_entry {
+                    events.push(self.stopped("entry", None));
+                } else {
+                    self.execution = Execution::Running;
+                }
+                Ok(Json::Null)
+            }
+            "threads" => Ok(json_object! {
+                "threads" => vec![json_object! { "id" => THREAD_ID, "name" => "chip-8" }],
+            }),
+            "stackTrace" => Ok(self.stack_trace(&arguments)),
+            "scopes" => Ok(json_object! {
+                "scopes" => vec![
+                    json_object! {
+                        "name" => "Registers",
+                        "presentationHint" => "registers",
+                        "variablesReference" => REGISTERS_REFERENCE,
+                        "expensive" => false,
+                    },
+                    json_object! {
+                        "name" => "Timers",
+                        "variablesReference" => TIMERS_REFERENCE,
+                        "expensive" => false,
+                    },
+                ],
+            }),
+            "variables" => self.variables(&arguments),
+            "setVariable" => self.set_variable(&arguments),
+            "continue" => {
+                self.execution = Execution::Running;
+                Ok(json_object! { "allThreadsContinued" => true })
+            }
+            "next" => {
+                let pc = self.chip_8.pc;
+                let opcode = u16::from_be_bytes([
+                    self.chip_8.memory[pc as usize % self.chip_8.memory.len()],
+                    self.chip_8.memory[(pc as usize + 1) % self.chip_8.memory.len()],
+                ]);
+                if let Some(Instruction::Call(_)) = Instruction::decode(opcode) {
+                    self.execution = Execution::StepOver {
+                        stack_pointer: self.chip_8.sp,
+                        address: pc + 2,
+                    };
+                } else {
+                    events.extend(self.step());
+                }
+                Ok(Json::Null)
+            }
+            "stepIn" => {
+                events.extend(self.step());
+                Ok(Json::Null)
+            }
+            "stepOut" => {
+                self.execution = Execution::StepOut {
+                    stack_pointer: self.chip_8.sp,
+                };
+                Ok(Json::Null)
+            }
+            "pause" => {
+                if self.execution != Execution::Stopped {
+                    events.push(self.stopped("pause", None));
+                }
+                Ok(Json::Null)
+            }
+            "readMemory" => self.read_memory(&arguments),
+            "writeMemory" => self.write_memory(&arguments),
+            "disassemble" => self.disassemble(&arguments),
+            "disconnect" => {
+                self.should_exit = true;
+                Ok(Json::Null)
+            }
+            "terminate" => {
+                self.should_exit = true;
+                events.push(event("terminated", json_object! {}));
+                Ok(Json::Null)
+            }
+            _ => Err(format!("Unsupported command: {}", command)),
+        };
+
+        let request_seq = request.get("seq").cloned().unwrap_or(Json::Null);
+        let mut response = json_object! {
+            "type" => "response",
+            "request_seq" => request_seq,
+            "success" => result.is_ok(),
+            "command" => command,
+        };
+        if let Json::Object(members) = &mut response {
+            match result {
+                Ok(Json::Null) => {}
+                Ok(body) => members.push(("body".to_string(), body)),
+                Err(message) => members.push(("message".to_string(), Json::from(message))),
+            }
+        }
+        let mut messages = vec![response];
+        messages.append(&mut events);
+        messages
+    }
+
+    fn launch(&mut self, arguments: &Json) -> Result<(), String> {
+        let program = arguments
+            .get("program")
+            .and_then(Json::as_str)
+            .ok_or("Missing program.")?;
+        let rom =
+            fs::read(program).map_err(|error| format!("Unable to read {}: {}", program, error))?;
+
+        let quirks = match arguments.get("quirks").and_then(Json::as_str) {
+            Some(name) => {
+                Quirks::from_preset(name).ok_or(format!("Unknown quirks preset: {}.", name))?
+            }
+            None => Quirks::new(),
+        };
+        self.chip_8 = Chip8::new();
+        self.chip_8.set_quirks(quirks);
+        self.chip_8
+            .try_load_rom(&rom, quirks.draw_mode == DrawMode::Wrap)
+            .map_err(|error| error.to_string())?;
+
+        if let Some(speed) = arguments.get("speed").and_then(Json::as_u64) {
+            self.cycles_per_frame = (speed as usize).max(1);
+        }
+        if let Some(path) = arguments.get("symbols").and_then(Json::as_str) {
+            let text = fs::read_to_string(path)
+                .map_err(|error| format!("Unable to read {}: {}", path, error))?;
+            self.symbols =
+                SymbolMap::parse(&text).map_err(|error| format!("{}: {}", path, error))?;
+            self.symbols_directory = Path::new(path)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+        }
+        self.stop_on_entry = arguments
+            .get("stopOnEntry")
+            .and_then(Json::as_bool)
+            .unwrap_or(false);
+        Ok(())
+    }
+
+    fn update_breakpoints(&mut self) {
+        self.debugger.clear_breakpoints();
+        let addresses = self
+            .source_breakpoints
+            .values()
+            .flatten()
+            .chain(&self.instruction_breakpoints);
+        for address in addresses {
+            self.debugger.add_breakpoint(*address);
+        }
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Json) -> Result<Json, String> {
+        let path = arguments
+            .get("source")
+            .and_then(|source| source.get("path"))
+            .and_then(Json::as_str)
+            .ok_or("Missing source path.")?
+            .to_string();
+        let lines = arguments
+            .get("breakpoints")
+            .and_then(Json::as_array)
+            .unwrap_or(&[]);
+
+        let mut addresses = Vec::new();
+        let mut breakpoints = Vec::new();
+        for line in lines.iter().filter_map(|breakpoint| breakpoint.get("line")) {
+            let line = line.as_u64().unwrap_or(0) as usize;
+            breakpoints.push(match self.symbols.resolve_line(&path, line) {
+                Some((line, mut line_addresses)) => {
+                    let breakpoint = json_object! {
+                        "verified" => true,
+                        "line" => line,
+                        "instructionReference" => format!("0x{:03X}", line_addresses[0]),
+                    };
+                    addresses.append(&mut line_addresses);
+                    breakpoint
+                }
+                None => json_object! {
+                    "verified" => false,
+                    "line" => line,
+                    "message" => "No code was assembled from this line.",
+                },
+            });
+        }
+        self.source_breakpoints.insert(path, addresses);
+        self.update_breakpoints();
+        Ok(json_object! { "breakpoints" => breakpoints })
+    }
+
+    fn set_instruction_breakpoints(&mut self, arguments: &Json) -> Result<Json, String> {
+        let requested = arguments
+            .get("breakpoints")
+            .and_then(Json::as_array)
+            .unwrap_or(&[]);
+        self.instruction_breakpoints.clear();
+        let mut breakpoints = Vec::new();
+        for breakpoint in requested {
+            let address = breakpoint
+                .get("instructionReference")
+                .and_then(Json::as_str)
+                .and_then(parse_reference)
+                .map(|address| {
+                    let offset = breakpoint
+                        .get("offset")
+                        .and_then(Json::as_f64)
+                        .unwrap_or(0.0);
+                    address + offset as i64
+                })
+                .filter(|address| (0..self.chip_8.memory.len() as i64).contains(address));
+            breakpoints.push(match address {
+                Some(address) => {
+                    self.instruction_breakpoints.push(address as u16);
+                    json_object! {
+                        "verified" => true,
+                        "instructionReference" => format!("0x{:03X}", address),
+                    }
+                }
+                None => json_object! {
+                    "verified" => false,
+                    "message" => "Invalid address.",
+                },
+            });
+        }
+        self.update_breakpoints();
+        Ok(json_object! { "breakpoints" => breakpoints })
+    }
+
+    fn frame(&self, id: usize, address: u16) -> Json {
+        let name = match self.symbols.enclosing_label(address) {
+            Some((label, name)) if label == address => name.to_string(),
+            Some((label, name)) => format!("{}+0x{:X}", name, address - label),
+            None => format!("0x{:03X}", address),
+        };
+        let mut frame = json_object! {
+            "id" => id,
+            "name" => name,
+            "instructionPointerReference" => format!("0x{:03X}", address),
+            "line" => 0,
+            "column" => 0,
+        };
+        if let (Json::Object(members), Some(location)) =
+            (&mut frame, self.symbols.location(address))
+        {
+            members.retain(|(name, _)| name != "line" && name != "column");
+            members.push(("source".to_string(), self.source(&location.path)));
+            members.push(("line".to_string(), Json::from(location.line)));
+            members.push(("column".to_string(), Json::from(1)));
+        }
+        frame
+    }
+
+    fn source(&self, path: &str) -> Json {
+        let path = self.symbols_directory.join(path);
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        json_object! {
+            "name" => name,
+            "path" => path.to_string_lossy().into_owned(),
+        }
+    }
+
+    fn stack_trace(&self, arguments: &Json) -> Json {
+        // The caller frames are at the call instructions before the return addresses.
+        let sp = self.chip_8.sp as usize;
+        let addresses: Vec<u16> = std::iter::once(self.chip_8.pc)
+            .chain(
+                self.chip_8.stack[..sp]
+                    .iter()
+                    .rev()
+                    .map(|address| address.wrapping_sub(2)),
+            )
+            .collect();
+        let start = arguments
+            .get("startFrame")
+            .and_then(Json::as_u64)
+            .unwrap_or(0) as usize;
+        let levels = match arguments.get("levels").and_then(Json::as_u64) {
+            Some(levels) if levels > 0 => levels as usize,
+            _ => addresses.len(),
+        };
+        let frames: Vec<Json> = addresses
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(levels)
+            .map(|(id, address)| self.frame(id, *address))
+            .collect();
+        json_object! {
+            "stackFrames" => frames,
+            "totalFrames" => addresses.len(),
+        }
+    }
+
+    fn variables(&self, arguments: &Json) -> Result<Json, String> {
+        let chip_8 = &self.chip_8;
+        let variable = |name: String, value: String| {
+            json_object! {
+                "name" => name,
+                "value" => value,
+                "variablesReference" => 0,
+            }
+        };
+        let variables = match arguments.get("variablesReference").and_then(Json::as_u64) {
+            Some(REGISTERS_REFERENCE) => {
+                let mut variables: Vec<Json> = chip_8
+                    .registers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| variable(format!("V{:X}", i), format!("0x{:02X}", value)))
+                    .collect();
+                let mut index = variable("I".to_string(), format!("0x{:03X}", chip_8.index));
+                if let Json::Object(members) = &mut index {
+                    members.push((
+                        "memoryReference".to_string(),
+                        Json::from(format!("0x{:03X}", chip_8.index)),
+                    ));
+                }
+                variables.push(index);
+                variables.push(variable("PC".to_string(), format!("0x{:03X}", chip_8.pc)));
+                variables.push(variable("SP".to_string(), format!("{}", chip_8.sp)));
+                variables
+            }
+            Some(TIMERS_REFERENCE) => vec![
+                variable("DT".to_string(), format!("{}", chip_8.delay_timer)),
+                variable("ST".to_string(), format!("{}", chip_8.sound_timer)),
+            ],
+            _ => return Err("Unknown variables reference.".to_string()),
+        };
+        Ok(json_object! { "variables" => variables })
+    }
+
+    fn set_variable(&mut self, arguments: &Json) -> Result<Json, String> {
+        let name = arguments
+            .get("name")
+            .and_then(Json::as_str)
+            .ok_or("Missing name.")?;
+        let text = arguments
+            .get("value")
+            .and_then(Json::as_str)
+            .ok_or("Missing value.")?;
+        let value = parse_reference(text.trim())
+            .filter(|value| *value >= 0)
+            .ok_or(format!("Invalid value: {}.", text))?;
+        let chip_8 = &mut self.chip_8;
+        let too_large = || format!("{} is too large for {}.", text, name);
+        let display = match name {
+            "I" | "PC" => {
+                let value = u16::try_from(value).map_err(|_| too_large())?;
+                if name == "I" {
+                    chip_8.index = value;
+                } else {
+                    chip_8.pc = value;
+                }
+                format!("0x{:03X}", value)
+            }
+            "SP" => {
+                if value as usize > chip_8.stack.len() {
+                    return Err(too_large());
+                }
+                chip_8.sp = value as u16;
+                format!("{}", value)
+            }
+            _ => {
+                let value = u8::try_from(value).map_err(|_| too_large())?;
+                match name {
+                    "DT" => chip_8.delay_timer = value,
+                    "ST" => chip_8.sound_timer = value,
+                    _ => {
+                        let register = name
+                            .strip_prefix('V')
+                            .and_then(|register| usize::from_str_radix(register, 16).ok())
+                            .filter(|register| *register < chip_8.registers.len())
+                            .ok_or(format!("Unknown variable: {}.", name))?;
+                        chip_8.registers[register] = value;
+                        return Ok(json_object! { "value" => format!("0x{:02X}", value) });
+                    }
+                }
+                format!("{}", value)
+            }
+        };
+        Ok(json_object! { "value" => display })
+    }
+
+    fn memory_address(&self, arguments: &Json) -> Result<i64, String> {
+        let reference = arguments
+            .get("memoryReference")
+            .and_then(Json::as_str)
+            .and_then(parse_reference)
+            .ok_or("Invalid memory reference.")?;
+        let offset = arguments
+            .get("offset")
+            .and_then(Json::as_f64)
+            .unwrap_or(0.0);
+        Ok(reference + offset as i64)
+    }
+
+    fn read_memory(&self, arguments: &Json) -> Result<Json, String> {
+        let address = self.memory_address(arguments)?;
+        let count = arguments.get("count").and_then(Json::as_u64).unwrap_or(0) as i64;
+        let memory = self.chip_8.memory();
+        let start = address.clamp(0, memory.len() as i64) as usize;
+        let end = (address + count).clamp(start as i64, memory.len() as i64) as usize;
+        Ok(json_object! {
+            "address" => format!("0x{:03X}", start),
+            "data" => encode_base64(&memory[start..end]),
+            "unreadableBytes" => count as usize - (end - start),
+        })
+    }
+
+    fn write_memory(&mut self, arguments: &Json) -> Result<Json, String> {
+        let address = self.memory_address(arguments)?;
+        let data = arguments
+            .get("data")
+            .and_then(Json::as_str)
+            .and_then(decode_base64)
+            .ok_or("Invalid data.")?;
+        if address < 0 {
+            return Err("Invalid address.".to_string());
+        }
+        self.chip_8
+            .write_memory(address as usize, &data)
+            .map_err(|error| error.to_string())?;
+        Ok(json_object! { "bytesWritten" => data.len() })
+    }
+
+    fn disassemble(&self, arguments: &Json) -> Result<Json, String> {
+        let address = self.memory_address(arguments)?;
+        let instruction_offset = arguments
+            .get("instructionOffset")
+            .and_then(Json::as_f64)
+            .unwrap_or(0.0) as i64;
+        let count = arguments
+            .get("instructionCount")
+            .and_then(Json::as_u64)
+            .ok_or("Missing instruction count.")? as i64;
+        let memory = self.chip_8.memory();
+
+        let start = address + instruction_offset * 2;
+        let instructions: Vec<Json> = (0..count)
+            .map(|i| {
+                let address = start + i * 2;
+                if !(0..memory.len() as i64).contains(&address) {
+                    return json_object! {
+                        "address" => format!("0x{:03X}", address.max(0)),
+                        "instruction" => "",
+                        "presentationHint" => "invalid",
+                    };
+                }
+                let address = address as usize;
+                let bytes: String = memory[address..(address + 2).min(memory.len())]
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect();
+                let mut instruction = json_object! {
+                    "address" => format!("0x{:03X}", address),
+                    "instructionBytes" => bytes,
+                    "instruction" => disassemble(memory, address),
+                };
+                if let (Json::Object(members), Some(location)) =
+                    (&mut instruction, self.symbols.location(address as u16))
+                {
+                    members.push(("location".to_string(), self.source(&location.path)));
+                    members.push(("line".to_string(), Json::from(location.line)));
+                }
+                if let (Json::Object(members), Some(label)) =
+                    (&mut instruction, self.symbols.label(address as u16))
+                {
+                    members.push(("symbol".to_string(), Json::from(label)));
+                }
+                instruction
+            })
+            .collect();
+        Ok(json_object! { "instructions" => instructions })
+    }
+
+    // Executes one instruction regardless of breakpoints and reports where execution stopped.
+    fn step(&mut self) -> Vec<Json> {
+        self.execution = Execution::Stopped;
+        match self.execute(true) {
+            Some(events) => events,
+            None => vec![self.stopped("step", None)],
+        }
+    }
+
+    // Executes one instruction, and returns the events to send if execution stopped.
+    fn execute(&mut self, ignore_breakpoints: bool) -> Option<Vec<Json>> {
+        if !self.chip_8.is_running {
+            return Some(self.exited());
+        }
+        let result = if ignore_breakpoints {
+            self.debugger.step(&mut self.chip_8).map(|()| None)
+        } else {
+            self.debugger.run(&mut self.chip_8, 1)
+        };
+        match result {
+            Ok(None) => {}
+            Ok(Some(_)) => return Some(vec![self.stopped("breakpoint", None)]),
+            Err(error) => return Some(vec![self.stopped("exception", Some(error.to_string()))]),
+        }
+        self.cycles += 1;
+        if self.cycles == self.cycles_per_frame {
+            self.cycles = 0;
+            self.chip_8.decrement_timers();
+        }
+        if !self.chip_8.is_running {
+            return Some(self.exited());
+        }
+        None
+    }
+
+    // Runs a frame of instructions while execution is running, and returns the events to send if
+    // execution stopped.
+    fn run_frame(&mut self) -> Vec<Json> {
+        for _ in 0..self.cycles_per_frame {
+            if self.execution == Execution::Stopped {
+                break;
+            }
+            if let Some(events) = self.execute(false) {
+                return events;
+            }
+            let has_stepped = match self.execution {
+                Execution::StepOver {
+                    stack_pointer,
+                    address,
+                } => self.chip_8.sp == stack_pointer && self.chip_8.pc == address,
+                Execution::StepOut { stack_pointer } => self.chip_8.sp < stack_pointer,
+                _ => false,
+            };
+            if has_stepped {
+                return vec![self.stopped("step", None)];
+            }
+        }
+        Vec::new()
+    }
+}
+
+impl Default for DapServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn event(event: &str, body: Json) -> Json {
+    json_object! {
+        "type" => "event",
+        "event" => event,
+        "body" => body,
+    }
+}
+
+// Reads a message with a `Content-Length` header, or returns `None` at the end of the input.
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.unwrap();
+    if length > MAX_MESSAGE_LENGTH {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Message of {} bytes is too large", length),
+        ));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    let text =
+        String::from_utf8(body).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+    Json::parse(&text)
+        .map(Some)
+        .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+}
+
+fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+// Parses a `0x` prefixed hex or decimal number.
+fn parse_reference(reference: &str) -> Option<i64> {
+    match reference
+        .strip_prefix("0x")
+        .or_else(|| reference.strip_prefix("0X"))
+    {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => reference.parse().ok(),
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut ret = String::new();
+    for chunk in bytes.chunks(3) {
+        let value = chunk.iter().enumerate().fold(0u32, |value, (i, byte)| {
+            value | (*byte as u32) << (16 - i * 8)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                ret.push(BASE64_ALPHABET[(value >> (18 - i * 6)) as usize & 0x3F] as char);
+            } else {
+                ret.push('=');
+            }
+        }
+    }
+    ret
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut ret = Vec::new();
+    let mut value = 0u32;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let digit = BASE64_ALPHABET.iter().position(|digit| *digit == c)? as u32;
+        value = (value << 6) | digit;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            ret.push((value >> bits) as u8);
+        }
+    }
+    Some(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn request(server: &mut DapServer, command: &str, arguments: Json) -> Vec<Json> {
+        let messages = server.handle_request(&json_object! {
+            "seq" => 1,
+            "type" => "request",
+            "command" => command,
+            "arguments" => arguments,
+        });
+        assert_eq!(
+            messages[0].get("success"),
+            Some(&Json::Bool(true)),
+            "{}",
+            messages[0]
+        );
+        messages
+    }
+
+    fn body(messages: &[Json]) -> &Json {
+        messages[0].get("body").unwrap()
+    }
+
+    fn stopped_reason(messages: &[Json]) -> Option<&str> {
+        messages
+            .iter()
+            .find(|message| message.get("event").and_then(Json::as_str) == Some("stopped"))
+            .and_then(|message| message.get("body"))
+            .and_then(|body| body.get("reason"))
+            .and_then(Json::as_str)
+    }
+
+    #[test]
+    fn test_base64() {
+        let cases: [&[u8]; 5] = [b"", b"a", b"ab", b"abc", b"\x00\xFF\x10\x20"];
+        for data in cases {
+            assert_eq!(decode_base64(&encode_base64(data)).as_deref(), Some(data));
+        }
+        assert_eq!(encode_base64(b"ab"), "YWI=");
+    }
+
+    #[test]
+    fn test_read_message() {
+        let mut input: &[u8] = b"Content-Length: 2\r\n\r\n{}Content-Length: 99999999999\r\n\r\n";
+        assert_eq!(read_message(&mut input).unwrap(), Some(json_object! {}));
+        let error = read_message(&mut input).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+
+    #[test]
+    fn test_session() {
+        let directory = env::temp_dir().join(format!("chipo-dap-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let rom_path = directory.join("test.ch8");
+        let symbols_path = directory.join("test.sym");
+        // 200: LD V0, 1; 202: CALL 208; 204: ADD V0, 1; 206: JP 206; 208: LD V1, 2; 20A: RET
+        fs::write(
+            &rom_path,
+            [
+                0x60, 0x01, 0x22, 0x08, 0x70, 0x01, 0x12, 0x06, 0x61, 0x02, 0x00, 0xEE,
+            ],
+        )
+        .unwrap();
+        fs::write(
+            &symbols_path,
+            "0x200 main\n0x200 test.8o:1\n0x202 test.8o:2\n0x204 test.8o:3\n0x206 test.8o:4\n\
+             0x208 sub\n0x208 test.8o:7\n0x20A test.8o:8\n",
+        )
+        .unwrap();
+
+        let mut server = DapServer::new();
+        request(&mut server, "initialize", json_object! {});
+        let messages = request(
+            &mut server,
+            "launch",
+            json_object! {
+                "program" => rom_path.to_string_lossy().into_owned(),
+                "symbols" => symbols_path.to_string_lossy().into_owned(),
+                "stopOnEntry" => true,
+            },
+        );
+        assert_eq!(
+            messages[1].get("event").and_then(Json::as_str),
+            Some("initialized")
+        );
+
+        let source_path = directory.join("test.8o").to_string_lossy().into_owned();
+        let messages = request(
+            &mut server,
+            "setBreakpoints",
+            json_object! {
+                "source" => json_object! { "path" => source_path.clone() },
+                "breakpoints" => vec![json_object! { "line" => 5 }, json_object! { "line" => 20 }],
+            },
+        );
+        assert_eq!(
+            body(&messages).to_string(),
+            "{\"breakpoints\":[{\"verified\":true,\"line\":7,\"instructionReference\":\"0x208\"},\
+             {\"verified\":false,\"line\":20,\"message\":\"No code was assembled from this line.\"}]}"
+        );
+
+        let messages = request(&mut server, "configurationDone", Json::Null);
+        assert_eq!(stopped_reason(&messages), Some("entry"));
+
+        request(&mut server, "continue", Json::Null);
+        assert_eq!(stopped_reason(&server.run_frame()), Some("breakpoint"));
+        let messages = request(&mut server, "stackTrace", json_object! { "threadId" => 1 });
+        let frames = body(&messages)
+            .get("stackFrames")
+            .and_then(Json::as_array)
+            .unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].get("name").and_then(Json::as_str), Some("sub"));
+        assert_eq!(frames[0].get("line").and_then(Json::as_u64), Some(7));
+        assert_eq!(
+            frames[0]
+                .get("source")
+                .and_then(|source| source.get("path")),
+            Some(&Json::from(source_path))
+        );
+        assert_eq!(
+            frames[1].get("name").and_then(Json::as_str),
+            Some("main+0x2")
+        );
+        assert_eq!(frames[1].get("line").and_then(Json::as_u64), Some(2));
+
+        let messages = request(&mut server, "stepOut", Json::Null);
+        assert_eq!(stopped_reason(&messages), None);
+        assert_eq!(stopped_reason(&server.run_frame()), Some("step"));
+        assert_eq!(server.chip_8.program_counter(), 0x204);
+
+        let messages = request(
+            &mut server,
+            "variables",
+            json_object! { "variablesReference" => REGISTERS_REFERENCE },
+        );
+        let variables = body(&messages)
+            .get("variables")
+            .and_then(Json::as_array)
+            .unwrap();
+        assert_eq!(
+            variables[1].get("value").and_then(Json::as_str),
+            Some("0x02")
+        );
+        assert_eq!(
+            variables[17].get("value").and_then(Json::as_str),
+            Some("0x204")
+        );
+        request(
+            &mut server,
+            "setVariable",
+            json_object! { "variablesReference" => 1, "name" => "VA", "value" => "0x2A" },
+        );
+        assert_eq!(server.chip_8.data_registers()[0xA], 0x2A);
+
+        let messages = request(
+            &mut server,
+            "writeMemory",
+            json_object! { "memoryReference" => "0x300", "data" => encode_base64(&[1, 2, 3]) },
+        );
+        assert_eq!(
+            body(&messages).get("bytesWritten").and_then(Json::as_u64),
+            Some(3)
+        );
+        let messages = request(
+            &mut server,
+            "readMemory",
+            json_object! { "memoryReference" => "0x300", "offset" => -1, "count" => 4 },
+        );
+        assert_eq!(
+            body(&messages).get("data").and_then(Json::as_str),
+            Some(encode_base64(&[0, 1, 2, 3]).as_str())
+        );
+
+        let messages = request(
+            &mut server,
+            "disassemble",
+            json_object! { "memoryReference" => "0x202", "instructionCount" => 1 },
+        );
+        let instructions = body(&messages)
+            .get("instructions")
+            .and_then(Json::as_array)
+            .unwrap();
+        assert_eq!(
+            instructions[0].get("instruction").and_then(Json::as_str),
+            Some("CALL 0x208")
+        );
+
+        let messages = request(&mut server, "next", Json::Null);
+        assert_eq!(stopped_reason(&messages), Some("step"));
+        assert_eq!(server.chip_8.data_registers()[0], 2);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+
+use crate::json::Json;
+use crate::palette::parse_color;
+use crate::{sha1, Button, Chip8, ControllerProfile, DrawMode, Palette, Quirks};
+
+// The table embedded by `RomDatabase::embedded`, in the format of `programs.json`.
+const EMBEDDED_DATABASE: &str = include_str!("database.json");
+
+/// A platform that a rom was written for.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Platform {
+    /// The original chip-8 interpreter on the COSMAC VIP, or a later chip-8 interpreter.
+    Chip8,
+    /// The CHIP-48 or SCHIP interpreters on the HP48.
+    SuperChip,
+    /// XO-CHIP, which chipo does not support.
+    XoChip,
+}
+
+impl Platform {
+    // Returns the platform and the quirks preset of a platform id of the chip-8 database. XO-CHIP
+    // has no preset since chipo does not support it.
+    fn from_id(id: &str) -> Option<(Platform, Option<&'static str>)> {
+        match id {
+            "originalChip8" | "hybridVIP" | "modernChip8" | "chip8x" => {
+                Some((Platform::Chip8, Some("chip-8")))
+            }
+            "chip48" | "superchip1" | "superchip" => {
+                Some((Platform::SuperChip, Some("schip-legacy")))
+            }
+            "xochip" => Some((Platform::XoChip, None)),
+            _ => None,
+        }
+    }
+}
+
+/// The metadata of a rom in a `RomDatabase`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RomInfo {
+    /// The title of the program.
+    pub title: String,
+    /// The authors of the program.
+    pub authors: Vec<String>,
+    /// The platforms that the rom runs on, from most to least preferred.
+    pub platforms: Vec<Platform>,
+    /// The quirks of the most preferred platform that chipo supports.
+    pub quirks: Option<Quirks>,
+    /// The recommended number of instructions per frame.
+    pub tickrate: Option<usize>,
+    /// The gamepad buttons that the rom uses.
+    pub controller_profile: Option<ControllerProfile>,
+    /// The colors of the rom.
+    pub palette: Option<Palette>,
+}
+
+/// An error that occurred while parsing a rom database.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DatabaseParseError {
+    /// A description of the error.
+    pub message: String,
+}
+
+impl fmt::Display for DatabaseParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl error::Error for DatabaseParseError {}
+
+/// A collection of rom metadata keyed by the SHA-1 hash of roms.
+///
+/// Databases are loaded from the `programs.json` file of the
+/// [chip-8 database](https://github.com/chip-8/chip-8-database), which is an array of programs
+/// with a `title`, optional `authors`, and `roms` keyed by their lowercase hex SHA-1 hash. Each
+/// rom may have `platforms`, `quirkyPlatforms`, `tickrate`, `keys`, and `colors`:
+///
+/// ```text
+/// [{
+///   "title": "Tetris",
+///   "authors": ["Fran Dachille"],
+///   "roms": {
+///     "0123456789abcdef0123456789abcdef01234567": {
+///       "platforms": ["superchip", "originalChip8"],
+///       "quirkyPlatforms": {"superchip": {"wrap": true}},
+///       "tickrate": 30,
+///       "keys": {"left": 5, "right": 6, "a": 4},
+///       "colors": {"pixels": ["#000000", "#ffffff"]}
+///     }
+///   }
+/// }]
+/// ```
+///
+/// The quirks of a rom are the preset of its first platform that chipo supports, where
+/// `quirkyPlatforms` may override `wrap`. The palette uses the first two `pixels` colors. Other
+/// quirks, keys, and fields are ignored.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RomDatabase {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl RomDatabase {
+    /// Constructs a new, empty `RomDatabase`.
+    pub fn new() -> Self {
+        RomDatabase {
+            roms: HashMap::new(),
+        }
+    }
+
+    /// Returns the small database that is embedded in chipo. It only has the roms that chipo is
+    /// tested with, so the full `programs.json` should be added with `extend` when it is
+    /// available.
+    pub fn embedded() -> Self {
+        RomDatabase::parse(EMBEDDED_DATABASE).expect("Expected the embedded database to be valid.")
+    }
+
+    /// Parses a database from the contents of `programs.json`.
+    pub fn parse(json: &str) -> Result<Self, DatabaseParseError> {
+        let error = |message: String| DatabaseParseError { message };
+        let programs = Json::parse(json).map_err(error)?;
+        let programs = programs
+            .as_array()
+            .ok_or_else(|| error(String::from("Expected an array of programs")))?;
+
+        let mut ret = RomDatabase::new();
+        for (index, program) in programs.iter().enumerate() {
+            parse_program(program, &mut ret)
+                .map_err(|message| error(format!("program {}: {}", index, message)))?;
+        }
+        Ok(ret)
+    }
+
+    /// Adds all roms of `other` to this database, replacing the metadata of roms that are in
+    /// both. This is useful for applying a user database on top of the embedded one.
+    pub fn extend(&mut self, other: RomDatabase) {
+        self.roms.extend(other.roms);
+    }
+
+    /// Sets the metadata of the rom with the SHA-1 hash `rom_hash`, which is a lowercase hex
+    /// string.
+    pub fn insert(&mut self, rom_hash: &str, info: RomInfo) {
+        self.roms.insert(rom_hash.to_lowercase(), info);
+    }
+
+    /// Returns the metadata of the rom with the SHA-1 hash `rom_hash`.
+    pub fn get(&self, rom_hash: &str) -> Option<&RomInfo> {
+        self.roms.get(&rom_hash.to_lowercase())
+    }
+
+    /// Returns the metadata of `rom`.
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.get(&sha1::hex_digest(rom))
+    }
+
+    /// Looks up the rom loaded in `chip_8` by `Chip8::rom_hash`, and sets its quirks and
+    /// controller profile if the database has them. The tickrate and palette are not part of
+    /// `Chip8`, so they should be applied by the frontend from the returned metadata.
+    pub fn configure(&self, chip_8: &mut Chip8) -> Option<&RomInfo> {
+        let info = self.get(&chip_8.rom_hash())?;
+        if let Some(quirks) = info.quirks {
+            chip_8.set_quirks(quirks);
+        }
+        if let Some(controller_profile) = info.controller_profile {
+            chip_8.set_controller_profile(controller_profile);
+        }
+        Some(info)
+    }
+}
+
+fn parse_program(program: &Json, database: &mut RomDatabase) -> Result<(), String> {
+    let title = program
+        .get("title")
+        .and_then(Json::as_str)
+        .ok_or("Expected a title")?;
+    let authors = match program.get("authors") {
+        Some(authors) => authors
+            .as_array()
+            .and_then(|authors| authors.iter().map(Json::as_str).collect::<Option<Vec<_>>>())
+            .ok_or("Expected the authors to be an array of strings")?
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        None => Vec::new(),
+    };
+    let roms = match program.get("roms") {
+        Some(Json::Object(roms)) => roms,
+        _ => return Err(String::from("Expected roms to be an object")),
+    };
+
+    for (rom_hash, rom) in roms {
+        if rom_hash.len() != 40 || !rom_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid rom hash: {}", rom_hash));
+        }
+        let info = parse_rom(rom, title, &authors)
+            .map_err(|message| format!("rom {}: {}", rom_hash.to_lowercase(), message))?;
+        database.insert(rom_hash, info);
+    }
+    Ok(())
+}
+
+fn parse_rom(rom: &Json, title: &str, authors: &[String]) -> Result<RomInfo, String> {
+    let mut platforms = Vec::new();
+    let mut quirks = None;
+    if let Some(ids) = rom.get("platforms") {
+        let ids = ids
+            .as_array()
+            .ok_or("Expected the platforms to be an array")?;
+        for id in ids {
+            let id = id.as_str().ok_or("Expected the platforms to be strings")?;
+            let (platform, preset) = match Platform::from_id(id) {
+                Some(platform) => platform,
+                None => continue,
+            };
+            platforms.push(platform);
+            if let (None, Some(preset)) = (quirks, preset) {
+                let mut preset_quirks = Quirks::from_preset(preset).unwrap();
+                let wrap = rom
+                    .get("quirkyPlatforms")
+                    .and_then(|quirky_platforms| quirky_platforms.get(id))
+                    .and_then(|platform_quirks| platform_quirks.get("wrap"))
+                    .and_then(Json::as_bool);
+                match wrap {
+                    Some(true) => preset_quirks.draw_mode = DrawMode::Wrap,
+                    Some(false) => preset_quirks.draw_mode = DrawMode::Clip,
+                    None => {}
+                }
+                quirks = Some(preset_quirks);
+            }
+        }
+    }
+
+    let tickrate = match rom.get("tickrate").map(Json::as_u64) {
+        Some(Some(tickrate)) if tickrate > 0 => Some(tickrate as usize),
+        Some(_) => {
+            return Err(String::from(
+                "Expected the tickrate to be a positive integer",
+            ))
+        }
+        None => None,
+    };
+
+    let controller_profile = match rom.get("keys") {
+        Some(Json::Object(keys)) => {
+            let mut profile = ControllerProfile::new();
+            for (name, key) in keys {
+                let key = match key.as_u64() {
+                    Some(key) if key < 16 => key as u8,
+                    _ => return Err(format!("Invalid hex key for {}", name)),
+                };
+                // Keys of the second player and other inputs have no button.
+                if let Some(button) = Button::from_name(&name.to_lowercase()) {
+                    profile.bind(button, key);
+                }
+            }
+            Some(profile)
+        }
+        Some(_) => return Err(String::from("Expected the keys to be an object")),
+        None => None,
+    };
+
+    let palette = match rom.get("colors").and_then(|colors| colors.get("pixels")) {
+        Some(pixels) => {
+            let pixels = pixels
+                .as_array()
+                .ok_or("Expected the pixel colors to be an array")?;
+            let color = |index: usize| {
+                pixels
+                    .get(index)
+                    .and_then(Json::as_str)
+                    .and_then(parse_color)
+                    .ok_or_else(|| format!("Invalid pixel color at index {}", index))
+            };
+            Some(Palette {
+                background: color(0)?,
+                foreground: color(1)?,
+            })
+        }
+        None => None,
+    };
+
+    Ok(RomInfo {
+        title: title.to_string(),
+        authors: authors.to_vec(),
+        platforms,
+        quirks,
+        tickrate,
+        controller_profile,
+        palette,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    #[test]
+    fn test_parse() {
+        let json = format!(
+            r##"[{{
+              "title": "Tetris",
+              "authors": ["Fran Dachille"],
+              "roms": {{
+                "{}": {{
+                  "platforms": ["megachip8", "superchip", "originalChip8"],
+                  "quirkyPlatforms": {{"superchip": {{"wrap": true, "shift": true}}}},
+                  "tickrate": 30,
+                  "keys": {{"left": 5, "right": 6, "a": 4, "player2Up": 1}},
+                  "colors": {{"pixels": ["#000000", "#FFB000"], "buzzer": "#ffffff"}}
+                }}
+              }}
+            }}, {{"title": "Empty", "roms": {{}}}}]"##,
+            HASH.to_uppercase(),
+        );
+        let database = RomDatabase::parse(&json).unwrap();
+        let info = database.get(HASH).unwrap();
+        assert_eq!(info.title, "Tetris");
+        assert_eq!(info.authors, vec![String::from("Fran Dachille")]);
+        assert_eq!(info.platforms, vec![Platform::SuperChip, Platform::Chip8]);
+        let mut quirks = Quirks::schip_legacy();
+        quirks.draw_mode = DrawMode::Wrap;
+        assert_eq!(info.quirks, Some(quirks));
+        assert_eq!(info.tickrate, Some(30));
+        let profile = info.controller_profile.unwrap();
+        assert_eq!(profile.get(Button::Left), Some(0x5));
+        assert_eq!(profile.get(Button::A), Some(0x4));
+        assert_eq!(profile.get(Button::Up), None);
+        assert_eq!(
+            info.palette,
+            Some(Palette {
+                background: 0x0000_0000,
+                foreground: 0x00FF_B000,
+            })
+        );
+        assert_eq!(database.get(&"0".repeat(40)), None);
+
+        let json = format!(
+            r#"[{{"title": "a", "roms": {{
+              "{}": {{"platforms": ["xochip"]}},
+              "{}": {{"platforms": ["xochip", "superchip"]}}
+            }}}}]"#,
+            HASH,
+            "1".repeat(40),
+        );
+        let database = RomDatabase::parse(&json).unwrap();
+        let info = database.get(HASH).unwrap();
+        assert_eq!(info.platforms, vec![Platform::XoChip]);
+        assert_eq!(info.quirks, None);
+        let info = database.get(&"1".repeat(40)).unwrap();
+        assert_eq!(info.quirks, Some(Quirks::schip_legacy()));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for &(json, expected) in &[
+            ("[", "Unexpected end of input at offset 1"),
+            ("{}", "Expected an array of programs"),
+            (r#"[{"roms": {}}]"#, "program 0: Expected a title"),
+            (
+                r#"[{"title": "a", "roms": {"tetris": {}}}]"#,
+                "program 0: Invalid rom hash: tetris",
+            ),
+        ] {
+            assert_eq!(
+                RomDatabase::parse(json).unwrap_err().message,
+                expected,
+                "{}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_rom_errors() {
+        for &(fields, expected) in &[
+            (r#""keys": {"up": 16}"#, "Invalid hex key for up"),
+            (
+                r#""tickrate": -1"#,
+                "Expected the tickrate to be a positive integer",
+            ),
+            (
+                r#""tickrate": 0"#,
+                "Expected the tickrate to be a positive integer",
+            ),
+            (
+                r##""colors": {"pixels": ["#000"]}"##,
+                "Invalid pixel color at index 0",
+            ),
+        ] {
+            let json = format!(
+                r#"[{{"title": "a", "roms": {{"{}": {{{}}}}}}}]"#,
+                HASH, fields
+            );
+            assert_eq!(
+                RomDatabase::parse(&json).unwrap_err().message,
+                format!("program 0: rom {}: {}", HASH, expected)
+            );
+        }
+    }
+
+    #[test]
+    fn test_configure() {
+        let rom = [0x12, 0x00];
+        let mut database = RomDatabase::embedded();
+        let mut user_database = RomDatabase::new();
+        user_database.insert(
+            &sha1::hex_digest(&rom),
+            RomInfo {
+                title: String::from("Loop"),
+                authors: Vec::new(),
+                platforms: vec![Platform::SuperChip],
+                quirks: Some(Quirks::schip_modern()),
+                tickrate: None,
+                controller_profile: Some(ControllerProfile::new()),
+                palette: None,
+            },
+        );
+        database.extend(user_database);
+
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&[0x00, 0xE0], true);
+        assert_eq!(database.configure(&mut chip_8), None);
+        assert_eq!(chip_8.controller_profile(), ControllerProfile::standard());
+
+        chip_8.load_rom(&rom, true);
+        assert_eq!(database.configure(&mut chip_8).unwrap().title, "Loop");
+        assert_eq!(chip_8.quirks(), Quirks::schip_modern());
+        assert_eq!(chip_8.controller_profile(), ControllerProfile::new());
+        assert_eq!(database.lookup(&rom).unwrap().title, "Loop");
+
+        let test_rom = std::fs::read("tests/TEST_ROM").unwrap();
+        let info = database.lookup(&test_rom).unwrap();
+        assert_eq!(info.title, "chipo test rom");
+        assert_eq!(info.platforms, vec![Platform::Chip8]);
+        assert_eq!(info.quirks, Some(Quirks::new()));
+    }
+}
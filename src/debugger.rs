@@ -0,0 +1,126 @@
+use std::collections::BTreeSet;
+
+use crate::{Chip8, Error};
+
+/// Breakpoints and execution control for debugging a rom.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    // The address of the breakpoint that execution last stopped at. The instruction at this
+    // address is executed when execution is resumed, instead of stopping at it again.
+    stopped_at: Option<u16>,
+}
+
+impl Debugger {
+    /// Constructs a new `Debugger` without any breakpoints.
+    pub fn new() -> Self {
+        Debugger {
+            breakpoints: BTreeSet::new(),
+            stopped_at: None,
+        }
+    }
+
+    /// Adds a breakpoint at `address`. Returns `false` if there already was a breakpoint.
+    pub fn add_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    /// Removes the breakpoint at `address`. Returns `false` if there was no breakpoint.
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// Adds a breakpoint at `address` if there is none, or removes it otherwise. Returns `true`
+    /// if there is a breakpoint at `address` afterwards.
+    pub fn toggle_breakpoint(&mut self, address: u16) -> bool {
+        if self.remove_breakpoint(address) {
+            return false;
+        }
+        self.add_breakpoint(address)
+    }
+
+    /// Returns `true` if there is a breakpoint at `address`.
+    pub fn has_breakpoint(&self, address: u16) -> bool {
+        self.breakpoints.contains(&address)
+    }
+
+    /// Returns an iterator over the addresses of the breakpoints in increasing order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().cloned()
+    }
+
+    /// Removes all breakpoints.
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Executes a single instruction regardless of breakpoints.
+    pub fn step(&mut self, chip_8: &mut Chip8) -> Result<(), Error> {
+        self.stopped_at = None;
+        chip_8.step()
+    }
+
+    /// Executes up to `cycles` instructions and stops before executing an instruction that has a
+    /// breakpoint. Returns the address of the breakpoint if execution stopped at one. If the
+    /// previous call stopped at a breakpoint, that instruction is executed first, so calling this
+    /// method again resumes execution.
+    pub fn run(&mut self, chip_8: &mut Chip8, cycles: usize) -> Result<Option<u16>, Error> {
+        for _ in 0..cycles {
+            let pc = chip_8.program_counter();
+            if self.has_breakpoint(pc) && self.stopped_at != Some(pc) {
+                self.stopped_at = Some(pc);
+                return Ok(Some(pc));
+            }
+            self.stopped_at = None;
+            chip_8.step()?;
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breakpoints() {
+        let mut debugger = Debugger::new();
+        assert!(debugger.add_breakpoint(0x204));
+        assert!(!debugger.add_breakpoint(0x204));
+        assert!(debugger.toggle_breakpoint(0x200));
+        assert!(!debugger.toggle_breakpoint(0x200));
+        assert!(debugger.toggle_breakpoint(0x202));
+        assert_eq!(
+            debugger.breakpoints().collect::<Vec<_>>(),
+            vec![0x202, 0x204]
+        );
+        assert!(debugger.remove_breakpoint(0x202));
+        assert!(!debugger.has_breakpoint(0x202));
+        debugger.clear_breakpoints();
+        assert_eq!(debugger.breakpoints().count(), 0);
+    }
+
+    #[test]
+    fn test_run() {
+        // Increments V0 in a loop.
+        let rom = [0x70, 0x01, 0x12, 0x00];
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&rom, true);
+
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x202);
+        assert_eq!(debugger.run(&mut chip_8, 10), Ok(Some(0x202)));
+        assert_eq!(chip_8.data_registers()[0], 1);
+        assert_eq!(debugger.run(&mut chip_8, 10), Ok(Some(0x202)));
+        assert_eq!(chip_8.data_registers()[0], 2);
+
+        assert_eq!(debugger.step(&mut chip_8), Ok(()));
+        assert_eq!(chip_8.program_counter(), 0x200);
+        assert_eq!(debugger.run(&mut chip_8, 1), Ok(None));
+        assert_eq!(debugger.run(&mut chip_8, 1), Ok(Some(0x202)));
+
+        debugger.remove_breakpoint(0x202);
+        assert_eq!(debugger.run(&mut chip_8, 10), Ok(None));
+        assert_eq!(chip_8.data_registers()[0], 8);
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt;
+
+use crate::random::XorShift;
+use crate::{Chip8, DrawMode, Error, Quirks, RomLints};
+
+// The number of frames that each key of the input script is held for.
+const FRAMES_PER_KEY: usize = 4;
+// The seeds of the input script and of `CXNN`, so that every preset sees the same run.
+const INPUT_SEED: u32 = 0x2545_F491;
+const RANDOM_SEED: u32 = 0x9E37_79B9;
+
+/// A rom run under one quirks preset by `QuirkDetection`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PresetRun {
+    /// The name of the preset, which is one of `Quirks::PRESETS`.
+    pub preset: &'static str,
+    /// The number of frames that ran to completion.
+    pub frames: usize,
+    /// The number of frames after which the screen differed from the frame before.
+    pub changed_frames: usize,
+    /// The number of frames in which every instruction ran at the same address, such as a `1NNN`
+    /// that jumps to itself or an `FX0A` that waits for a key.
+    pub stuck_frames: usize,
+    /// The address and the error of the instruction that stopped the run, if any.
+    pub fault: Option<(u16, Error)>,
+    /// The score of the run. Higher scores are more plausible.
+    pub score: i64,
+}
+
+impl fmt::Display for PresetRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: score {}, {} changed and {} stuck of {} frames",
+            self.preset, self.score, self.changed_frames, self.stuck_frames, self.frames
+        )?;
+        if let Some((address, error)) = &self.fault {
+            write!(f, ", {} at 0x{:03X}", error, address)?;
+        }
+        Ok(())
+    }
+}
+
+/// The runs of a rom under each quirks preset, and the preset that the rom most likely expects.
+///
+/// Each run presses the same pseudo-random sequence of keys and uses the same pseudo-random
+/// results for `CXNN`, so the runs only differ by their quirks and are reproducible.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct QuirkDetection {
+    runs: Vec<PresetRun>,
+    recommended: usize,
+}
+
+impl QuirkDetection {
+    /// Runs `rom` for `frames` frames of `cycles_per_frame` instructions under each of
+    /// `Quirks::PRESETS`. Returns an error if the rom does not fit in memory.
+    ///
+    /// A run scores a point for every frame that changed the screen and loses one for every frame
+    /// that was stuck at one address. A run that stops at an error loses `2 * frames` more points,
+    /// so that it always scores lower than the runs that did not. Ties are broken by the preset
+    /// that `RomLints` suggests, if any, and then by the order of `Quirks::PRESETS`.
+    pub fn analyze(rom: &[u8], frames: usize, cycles_per_frame: usize) -> Result<Self, Error> {
+        let mut runs = Vec::new();
+        for &preset in Quirks::PRESETS.iter() {
+            let quirks = Quirks::from_preset(preset).unwrap();
+            runs.push(run(rom, preset, quirks, frames, cycles_per_frame)?);
+        }
+
+        let best = runs.iter().map(|run| run.score).max().unwrap();
+        let suggested = RomLints::analyze(rom).suggested_preset();
+        let recommended = runs
+            .iter()
+            .position(|run| run.score == best && Some(run.preset) == suggested)
+            .or_else(|| runs.iter().position(|run| run.score == best))
+            .unwrap();
+        Ok(QuirkDetection { runs, recommended })
+    }
+
+    /// Returns the runs in the order of `Quirks::PRESETS`.
+    pub fn runs(&self) -> &[PresetRun] {
+        &self.runs
+    }
+
+    /// Returns the name of the preset with the most plausible run.
+    pub fn recommended_preset(&self) -> &'static str {
+        self.runs[self.recommended].preset
+    }
+}
+
+fn run(
+    rom: &[u8],
+    preset: &'static str,
+    quirks: Quirks,
+    frames: usize,
+    cycles_per_frame: usize,
+) -> Result<PresetRun, Error> {
+    let mut chip_8 = Chip8::new();
+    chip_8.set_quirks(quirks);
+    chip_8.set_seed(RANDOM_SEED);
+    chip_8.try_load_rom(rom, quirks.draw_mode == DrawMode::Wrap)?;
+
+    let mut input = XorShift::new(INPUT_SEED);
+    let mut held_key = None;
+    let mut screen = chip_8.screen.buffer();
+    let mut result = PresetRun {
+        preset,
+        frames: 0,
+        changed_frames: 0,
+        stuck_frames: 0,
+        fault: None,
+        score: 0,
+    };
+
+    'frames: for frame in 0..frames {
+        if frame % FRAMES_PER_KEY == 0 {
+            if let Some(key) = held_key.take() {
+                chip_8.release_hex_key(key);
+            }
+            // Leave a quarter of the periods without a key, so that roms can see it released.
+            let value = input.next();
+            if value % 4 != 0 {
+                let key = (value >> 8) as u8 & 0xF;
+                chip_8.press_hex_key(key);
+                held_key = Some(key);
+            }
+        }
+
+        let start = chip_8.pc;
+        let mut is_stuck = true;
+        for _ in 0..cycles_per_frame {
+            let pc = chip_8.pc;
+            is_stuck &= pc == start;
+            if let Err(error) = chip_8.step() {
+                result.fault = Some((pc, error));
+                break 'frames;
+            }
+        }
+        chip_8.decrement_timers();
+
+        let next_screen = chip_8.screen.buffer();
+        if next_screen != screen {
+            result.changed_frames += 1;
+            screen = next_screen;
+        }
+        if is_stuck && chip_8.pc == start {
+            result.stuck_frames += 1;
+        }
+        result.frames += 1;
+    }
+
+    result.score = result.changed_frames as i64 - result.stuck_frames as i64;
+    if result.fault.is_some() {
+        result.score -= 2 * frames as i64;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze() {
+        // 200: LD I, 0x20A; 202: LD V0, 60; 204: DRW V0, V0, 0; 206: ADD V0, 1; 208: JP 0x204;
+        // 20A: sprite data
+        let mut rom = vec![0xA2, 0x0A, 0x60, 0x3C, 0xD0, 0x00, 0x70, 0x01, 0x12, 0x04];
+        rom.extend_from_slice(&[0xFF; 32]);
+        let detection = QuirkDetection::analyze(&rom, 20, 10).unwrap();
+        let runs = detection.runs();
+        assert_eq!(runs.len(), Quirks::PRESETS.len());
+        // `DXY0` draws nothing in standard mode on the COSMAC VIP.
+        assert_eq!(runs[0].preset, "chip-8");
+        assert_eq!(runs[0].changed_frames, 0);
+        assert!(runs[1].changed_frames > 0);
+        assert!(runs[2].changed_frames > 0);
+        assert_eq!(detection.recommended_preset(), "schip-legacy");
+    }
+
+    #[test]
+    fn test_fault() {
+        // 200: LD V0, 0; 202: SKP V0; 204: JP 0x202; 206: RET
+        let rom = [0x60, 0x00, 0xE0, 0x9E, 0x12, 0x02, 0x00, 0xEE];
+        let detection = QuirkDetection::analyze(&rom, 60, 10).unwrap();
+        for run in detection.runs() {
+            assert_eq!(run.fault, Some((0x206, Error::StackUnderflow)));
+            assert!(run.frames < 60);
+            assert!(run.score < -60);
+        }
+        assert_eq!(detection.recommended_preset(), "chip-8");
+        assert_eq!(
+            detection.runs()[0].to_string(),
+            format!(
+                "chip-8: score {}, 0 changed and 0 stuck of {} frames, Stack underflow at 0x206",
+                detection.runs()[0].score,
+                detection.runs()[0].frames
+            )
+        );
+
+        // 200: JP 0x200
+        let detection = QuirkDetection::analyze(&[0x12, 0x00], 10, 10).unwrap();
+        assert_eq!(detection.runs()[0].stuck_frames, 10);
+        assert_eq!(detection.runs()[0].score, -10);
+    }
+}
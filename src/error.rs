@@ -0,0 +1,36 @@
+use std::error;
+use std::fmt;
+
+/// An error that occurred while loading or running a rom.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The rom with the given size in bytes does not fit in memory.
+    RomTooLarge(usize),
+    /// The opcode is not a valid chip-8 or SCHIP instruction.
+    UnknownOpcode(u16),
+    /// A subroutine was called when the stack was full.
+    StackOverflow,
+    /// A subroutine returned when the stack was empty.
+    StackUnderflow,
+    /// An instruction accessed memory past the end of memory, starting at the given address.
+    MemoryOutOfBounds(usize),
+    /// A save state could not be loaded because it is malformed.
+    InvalidState,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::RomTooLarge(size) => write!(f, "Rom of {} bytes does not fit in memory", size),
+            Error::UnknownOpcode(opcode) => write!(f, "Unrecognized opcode: {:04X}", opcode),
+            Error::StackOverflow => write!(f, "Stack overflow"),
+            Error::StackUnderflow => write!(f, "Stack underflow"),
+            Error::MemoryOutOfBounds(address) => {
+                write!(f, "Memory access out of bounds at {:04X}", address)
+            }
+            Error::InvalidState => write!(f, "Invalid save state"),
+        }
+    }
+}
+
+impl error::Error for Error {}
@@ -0,0 +1,386 @@
+//! A C API over `Chip8`. The header for this API is generated into `include/chipo.h` when the
+//! crate is built with the `generate-header` feature. Every function accepts null pointers and reports them
+//! with `ChipoResult::NullPointer` instead of dereferencing them.
+
+use std::convert::TryFrom;
+use std::os::raw::{c_char, c_int};
+use std::slice;
+
+use crate::{Chip8, Error, SCREEN_BUFFER_HEIGHT, SCREEN_BUFFER_WIDTH, STATE_SIZE};
+
+/// The result of a function of the C API.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ChipoResult {
+    /// The function succeeded.
+    Ok = 0,
+    /// A pointer argument was null.
+    NullPointer = 1,
+    /// The rom does not fit in memory.
+    RomTooLarge = 2,
+    /// The emulator encountered an opcode that is not a valid instruction.
+    UnknownOpcode = 3,
+    /// A subroutine was called when the stack was full.
+    StackOverflow = 4,
+    /// A subroutine returned when the stack was empty.
+    StackUnderflow = 5,
+    /// An instruction accessed memory past the end of memory.
+    MemoryOutOfBounds = 6,
+    /// The save state is malformed.
+    InvalidState = 7,
+    /// The output buffer is too small.
+    BufferTooSmall = 8,
+    /// The key is not a hex key.
+    InvalidKey = 9,
+}
+
+impl From<Error> for ChipoResult {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::RomTooLarge(_) => ChipoResult::RomTooLarge,
+            Error::UnknownOpcode(_) => ChipoResult::UnknownOpcode,
+            Error::StackOverflow => ChipoResult::StackOverflow,
+            Error::StackUnderflow => ChipoResult::StackUnderflow,
+            Error::MemoryOutOfBounds(_) => ChipoResult::MemoryOutOfBounds,
+            Error::InvalidState => ChipoResult::InvalidState,
+        }
+    }
+}
+
+impl From<Result<(), Error>> for ChipoResult {
+    fn from(result: Result<(), Error>) -> Self {
+        match result {
+            Ok(()) => ChipoResult::Ok,
+            Err(error) => error.into(),
+        }
+    }
+}
+
+// The descriptions of the values of `ChipoResult`, in order.
+const RESULT_MESSAGES: [&[u8]; 10] = [
+    b"Ok\0",
+    b"Null pointer\0",
+    b"Rom does not fit in memory\0",
+    b"Unrecognized opcode\0",
+    b"Stack overflow\0",
+    b"Stack underflow\0",
+    b"Memory access out of bounds\0",
+    b"Invalid save state\0",
+    b"Buffer too small\0",
+    b"Invalid hex key\0",
+];
+
+/// Returns a static, null-terminated description of the `ChipoResult` value `result`, or of an
+/// unknown result if `result` is not one. `result` is an `int` since C allows any value to be
+/// stored in an enum.
+#[no_mangle]
+pub extern "C" fn chipo_result_message(result: c_int) -> *const c_char {
+    let message = usize::try_from(result)
+        .ok()
+        .and_then(|result| RESULT_MESSAGES.get(result))
+        .copied()
+        .unwrap_or(b"Unknown result\0");
+    message.as_ptr() as *const c_char
+}
+
+/// Constructs a new emulator. The emulator must be freed with `chipo_free`.
+#[no_mangle]
+pub extern "C" fn chipo_new() -> *mut Chip8 {
+    Box::into_raw(Box::new(Chip8::new()))
+}
+
+/// Frees an emulator constructed by `chipo_new`. Does nothing if `chip_8` is null.
+///
+/// # Safety
+///
+/// `chip_8` must be null or a pointer returned by `chipo_new` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn chipo_free(chip_8: *mut Chip8) {
+    if !chip_8.is_null() {
+        drop(Box::from_raw(chip_8));
+    }
+}
+
+/// Loads the rom of `length` bytes at `rom`. If `should_wrap` is true, then sprites wrap around
+/// the edges of the screen, else they are clipped.
+///
+/// # Safety
+///
+/// `chip_8` must be null or a valid emulator, and `rom` must be null or point to `length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chipo_load_rom(
+    chip_8: *mut Chip8,
+    rom: *const u8,
+    length: usize,
+    should_wrap: bool,
+) -> ChipoResult {
+    match (chip_8.as_mut(), rom.is_null()) {
+        (Some(chip_8), false) => chip_8
+            .try_load_rom(slice::from_raw_parts(rom, length), should_wrap)
+            .into(),
+        _ => ChipoResult::NullPointer,
+    }
+}
+
+/// Runs `cycles` cycles and then decrements the timers once. Stops at the first invalid
+/// instruction.
+///
+/// # Safety
+///
+/// `chip_8` must be null or a valid emulator.
+#[no_mangle]
+pub unsafe extern "C" fn chipo_run_frame(chip_8: *mut Chip8, cycles: usize) -> ChipoResult {
+    match chip_8.as_mut() {
+        Some(chip_8) => chip_8.run_frame(cycles).into(),
+        None => ChipoResult::NullPointer,
+    }
+}
+
+/// Returns the number of bytes written by `chipo_screen`.
+#[no_mangle]
+pub extern "C" fn chipo_screen_size() -> usize {
+    SCREEN_BUFFER_WIDTH * SCREEN_BUFFER_HEIGHT / 8
+}
+
+/// Returns the width in pixels of the screen of the current mode, which is either 64 or 128.
+///
+/// # Safety
+///
+/// `chip_8` must be null or a valid emulator.
+#[no_mangle]
+pub unsafe extern "C" fn chipo_screen_width(chip_8: *const Chip8) -> usize {
+    chip_8.as_ref().map_or(0, Chip8::screen_width)
+}
+
+/// Returns the height in pixels of the screen of the current mode, which is either 32 or 64.
+///
+/// # Safety
+///
+/// `chip_8` must be null or a valid emulator.
+#[no_mangle]
+pub unsafe extern "C" fn chipo_screen_height(chip_8: *const Chip8) -> usize {
+    chip_8.as_ref().map_or(0, Chip8::screen_height)
+}
+
+/// Copies the screen into `buffer`, which must hold at least `chipo_screen_size()` bytes. The
+/// layout is the same as `Chip8::screen_buffer`: 128x64 pixels in row-major order, 8 pixels per
+/// byte with the leftmost pixel in the least significant bit. In standard mode, each pixel is a
+/// 2x2 block.
+///
+/// # Safety
+///
+/// `chip_8` must be null or a valid emulator, and `buffer` must be null or point to `length`
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chipo_screen(
+    chip_8: *const Chip8,
+    buffer: *mut u8,
+    length: usize,
+) -> ChipoResult {
+    match chip_8.as_ref() {
+        Some(chip_8) => copy_to_buffer(&chip_8.screen_buffer(), buffer, length),
+        None => ChipoResult::NullPointer,
+    }
+}
+
+/// Returns whether the last call to `chipo_run_frame` updated the screen.
+///
+/// # Safety
+///
+/// `chip_8` must be null or a valid emulator.
+#[no_mangle]
+pub unsafe extern "C" fn chipo_should_draw(chip_8: *const Chip8) -> bool {
+    chip_8.as_ref().map_or(false, Chip8::should_draw)
+}
+
+/// Returns whether the sound timer reached zero during the last call to `chipo_run_frame`.
+///
+/// # Safety
+///
+/// `chip_8` must be null or a valid emulator.
+#[no_mangle]
+pub unsafe extern "C" fn chipo_should_beep(chip_8: *const Chip8) -> bool {
+    chip_8.as_ref().map_or(false, Chip8::should_beep)
+}
+
+/// Presses the hex key `key`.
+///
+/// # Safety
+///
+/// `chip_8` must be null or a valid emulator.
+#[no_mangle]
+pub unsafe extern "C" fn chipo_press_key(chip_8: *mut Chip8, key: u8) -> ChipoResult {
+    match chip_8.as_mut() {
+        Some(_) if key >= 16 => ChipoResult::InvalidKey,
+        Some(chip_8) => {
+            chip_8.press_hex_key(key);
+            ChipoResult::Ok
+        }
+        None => ChipoResult::NullPointer,
+    }
+}
+
+/// Releases the hex key `key`.
+///
+/// # Safety
+///
+/// `chip_8` must be null or a valid emulator.
+#[no_mangle]
+pub unsafe extern "C" fn chipo_release_key(chip_8: *mut Chip8, key: u8) -> ChipoResult {
+    match chip_8.as_mut() {
+        Some(_) if key >= 16 => ChipoResult::InvalidKey,
+        Some(chip_8) => {
+            chip_8.release_hex_key(key);
+            ChipoResult::Ok
+        }
+        None => ChipoResult::NullPointer,
+    }
+}
+
+/// Returns the number of bytes written by `chipo_save_state`.
+#[no_mangle]
+pub extern "C" fn chipo_save_state_size() -> usize {
+    STATE_SIZE
+}
+
+/// Writes a save state into `buffer`, which must hold at least `chipo_save_state_size()` bytes.
+///
+/// # Safety
+///
+/// `chip_8` must be null or a valid emulator, and `buffer` must be null or point to `length`
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chipo_save_state(
+    chip_8: *const Chip8,
+    buffer: *mut u8,
+    length: usize,
+) -> ChipoResult {
+    match chip_8.as_ref() {
+        Some(chip_8) => copy_to_buffer(&chip_8.save_state(), buffer, length),
+        None => ChipoResult::NullPointer,
+    }
+}
+
+/// Restores the save state of `length` bytes at `state`. The emulator is not modified if the save
+/// state is malformed.
+///
+/// # Safety
+///
+/// `chip_8` must be null or a valid emulator, and `state` must be null or point to `length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chipo_load_state(
+    chip_8: *mut Chip8,
+    state: *const u8,
+    length: usize,
+) -> ChipoResult {
+    match (chip_8.as_mut(), state.is_null()) {
+        (Some(chip_8), false) => chip_8
+            .load_state(slice::from_raw_parts(state, length))
+            .into(),
+        _ => ChipoResult::NullPointer,
+    }
+}
+
+unsafe fn copy_to_buffer(data: &[u8], buffer: *mut u8, length: usize) -> ChipoResult {
+    if buffer.is_null() {
+        return ChipoResult::NullPointer;
+    }
+    if length < data.len() {
+        return ChipoResult::BufferTooSmall;
+    }
+    slice::from_raw_parts_mut(buffer, data.len()).copy_from_slice(data);
+    ChipoResult::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+    use std::ptr;
+
+    #[test]
+    fn test_ffi() {
+        unsafe {
+            let chip_8 = chipo_new();
+            let rom = [0x00, 0xFF, 0x60, 0x01, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x08];
+            assert_eq!(
+                chipo_load_rom(chip_8, rom.as_ptr(), rom.len(), true),
+                ChipoResult::Ok
+            );
+            assert_eq!(chipo_run_frame(chip_8, 4), ChipoResult::Ok);
+            assert_eq!(chipo_screen_width(chip_8), 128);
+            assert!(chipo_should_draw(chip_8));
+
+            let mut screen = vec![0; chipo_screen_size()];
+            assert_eq!(
+                chipo_screen(chip_8, screen.as_mut_ptr(), screen.len() - 1),
+                ChipoResult::BufferTooSmall,
+            );
+            assert_eq!(
+                chipo_screen(chip_8, screen.as_mut_ptr(), screen.len()),
+                ChipoResult::Ok
+            );
+            assert_eq!(screen, (*chip_8).screen_buffer());
+
+            assert_eq!(chipo_press_key(chip_8, 0x10), ChipoResult::InvalidKey);
+            assert_eq!(chipo_press_key(chip_8, 0xF), ChipoResult::Ok);
+
+            let mut state = vec![0; chipo_save_state_size()];
+            assert_eq!(
+                chipo_save_state(chip_8, state.as_mut_ptr(), state.len()),
+                ChipoResult::Ok
+            );
+            let other = chipo_new();
+            assert_eq!(
+                chipo_load_state(other, state.as_ptr(), state.len()),
+                ChipoResult::Ok
+            );
+            assert_eq!((*other).save_state(), state);
+            assert_eq!(
+                chipo_load_state(other, state.as_ptr(), 1),
+                ChipoResult::InvalidState
+            );
+
+            chipo_free(chip_8);
+            chipo_free(other);
+            chipo_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_null_pointers() {
+        unsafe {
+            let chip_8 = chipo_new();
+            assert_eq!(
+                chipo_run_frame(ptr::null_mut(), 1),
+                ChipoResult::NullPointer
+            );
+            assert_eq!(
+                chipo_load_rom(chip_8, ptr::null(), 0, true),
+                ChipoResult::NullPointer
+            );
+            assert_eq!(
+                chipo_screen(chip_8, ptr::null_mut(), 0),
+                ChipoResult::NullPointer
+            );
+            assert_eq!(chipo_screen_width(ptr::null()), 0);
+            chipo_free(chip_8);
+        }
+    }
+
+    #[test]
+    fn test_result_message() {
+        let message = |result| unsafe { CStr::from_ptr(chipo_result_message(result)) }.to_str();
+        assert_eq!(message(ChipoResult::Ok as c_int), Ok("Ok"));
+        assert_eq!(
+            message(ChipoResult::StackOverflow as c_int),
+            Ok("Stack overflow")
+        );
+        assert_eq!(
+            message(ChipoResult::InvalidKey as c_int),
+            Ok("Invalid hex key")
+        );
+        assert_eq!(message(10), Ok("Unknown result"));
+        assert_eq!(message(-1), Ok("Unknown result"));
+    }
+}
@@ -0,0 +1,460 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::Range;
+
+use crate::{disassemble, Instruction, SymbolMap, MEMORY_SIZE, PROGRAM_START};
+
+/// The kinds of edges between basic blocks.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EdgeKind {
+    /// The next instruction, including the return address of a `2NNN` call.
+    Next,
+    /// A `1NNN` jump.
+    Jump,
+    /// A skip over the next instruction by `3XKK`, `4XKK`, `5XY0`, `9XY0`, `EX9E`, or `EXA1`.
+    Skip,
+    /// A `2NNN` call.
+    Call,
+    /// A `BNNN` jump when `V0` is `0`. The other destinations depend on `V0` and are not followed.
+    JumpTable,
+}
+
+/// A sequence of instructions that is only entered at its first instruction and only left after
+/// its last instruction.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BasicBlock {
+    /// The address of the first instruction.
+    pub start: u16,
+    /// The address after the last instruction.
+    pub end: u16,
+    /// The addresses of the blocks that can run next, and how they are reached.
+    pub successors: Vec<(u16, EdgeKind)>,
+}
+
+/// A problem with the use of the stack.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StackIssue {
+    /// The `00EE` at `address` can be reached from `PROGRAM_START` without a call.
+    ReturnWithoutCall(u16),
+    /// The subroutine called at `call` never returns, and jumps into code that is reached from
+    /// elsewhere instead, so every call leaves an address on the stack.
+    CallWithoutReturn {
+        /// The address of the `2NNN` instruction.
+        call: u16,
+        /// The address of the subroutine.
+        subroutine: u16,
+    },
+}
+
+impl fmt::Display for StackIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackIssue::ReturnWithoutCall(address) => {
+                write!(f, "RET at 0x{:03X} can be reached without a call", address)
+            }
+            StackIssue::CallWithoutReturn { call, subroutine } => write!(
+                f,
+                "CALL at 0x{:03X} to 0x{:03X} never returns",
+                call, subroutine
+            ),
+        }
+    }
+}
+
+/// The control flow graph of a rom, found by following every instruction that can be reached
+/// from `PROGRAM_START` without running the rom.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ControlFlowGraph {
+    memory: Vec<u8>,
+    blocks: BTreeMap<u16, BasicBlock>,
+    subroutines: BTreeSet<u16>,
+    halts: Vec<u16>,
+    unresolved_jumps: Vec<u16>,
+    invalid_instructions: Vec<u16>,
+    unreachable: Vec<Range<u16>>,
+    stack_issues: Vec<StackIssue>,
+}
+
+// Returns the successors of the instruction at `address`, and whether it ends a basic block.
+fn successors(address: u16, instruction: Option<Instruction>) -> (Vec<(u16, EdgeKind)>, bool) {
+    let next = address.wrapping_add(2);
+    match instruction {
+        Some(Instruction::Jump(nnn)) => (vec![(nnn, EdgeKind::Jump)], true),
+        Some(Instruction::Call(nnn)) => (vec![(nnn, EdgeKind::Call), (next, EdgeKind::Next)], true),
+        Some(Instruction::JumpOffset(nnn)) => (vec![(nnn, EdgeKind::JumpTable)], true),
+        Some(Instruction::Return) | Some(Instruction::Exit) | None => (Vec::new(), true),
+        Some(
+            Instruction::SkipIfEqualByte(..)
+            | Instruction::SkipIfNotEqualByte(..)
+            | Instruction::SkipIfEqual(..)
+            | Instruction::SkipIfNotEqual(..)
+            | Instruction::SkipIfKey(..)
+            | Instruction::SkipIfNotKey(..),
+        ) => (
+            vec![
+                (next, EdgeKind::Next),
+                (address.wrapping_add(4), EdgeKind::Skip),
+            ],
+            true,
+        ),
+        Some(_) => (vec![(next, EdgeKind::Next)], false),
+    }
+}
+
+impl ControlFlowGraph {
+    /// Analyzes `rom` as if it was loaded at `PROGRAM_START`.
+    pub fn analyze(rom: &[u8]) -> Self {
+        let mut memory = vec![0; MEMORY_SIZE];
+        let start = PROGRAM_START as usize;
+        let rom_end = (start + rom.len()).min(MEMORY_SIZE);
+        memory[start..rom_end].copy_from_slice(&rom[..rom_end - start]);
+
+        let mut graph = ControlFlowGraph {
+            memory,
+            blocks: BTreeMap::new(),
+            subroutines: BTreeSet::new(),
+            halts: Vec::new(),
+            unresolved_jumps: Vec::new(),
+            invalid_instructions: Vec::new(),
+            unreachable: Vec::new(),
+            stack_issues: Vec::new(),
+        };
+
+        // Find the reachable instructions and the addresses that start basic blocks.
+        let mut visited = BTreeSet::new();
+        let mut leaders = BTreeSet::new();
+        let mut calls = Vec::new();
+        let mut pending = vec![PROGRAM_START];
+        leaders.insert(PROGRAM_START);
+        while let Some(address) = pending.pop() {
+            if !visited.insert(address) {
+                continue;
+            }
+            let instruction = graph.instruction(address);
+            let (next, is_end) = successors(address, instruction);
+            match instruction {
+                Some(Instruction::Jump(nnn)) if nnn == address => graph.halts.push(address),
+                Some(Instruction::Call(nnn)) => {
+                    graph.subroutines.insert(nnn);
+                    calls.push((address, nnn));
+                }
+                Some(Instruction::JumpOffset(_)) => graph.unresolved_jumps.push(address),
+                None => graph.invalid_instructions.push(address),
+                _ => {}
+            }
+            for (target, _) in &next {
+                if is_end {
+                    leaders.insert(*target);
+                }
+                pending.push(*target);
+            }
+        }
+
+        // Split the reachable instructions into basic blocks.
+        for &leader in &leaders {
+            if !visited.contains(&leader) {
+                continue;
+            }
+            let mut address = leader;
+            loop {
+                let (next, is_end) = successors(address, graph.instruction(address));
+                let following = address.wrapping_add(2);
+                if is_end || leaders.contains(&following) || !visited.contains(&following) {
+                    graph.blocks.insert(
+                        leader,
+                        BasicBlock {
+                            start: leader,
+                            end: following,
+                            successors: next,
+                        },
+                    );
+                    break;
+                }
+                address = following;
+            }
+        }
+
+        // Bytes of the rom that are not part of a reachable instruction.
+        let mut covered = vec![false; MEMORY_SIZE + 1];
+        for address in visited.iter().filter(|a| (**a as usize) < MEMORY_SIZE) {
+            covered[*address as usize] = true;
+            covered[*address as usize + 1] = true;
+        }
+        let mut range_start = None;
+        for (address, is_covered) in covered.iter().enumerate().take(rom_end + 1).skip(start) {
+            match (*is_covered || address == rom_end, range_start) {
+                (true, Some(first)) => {
+                    graph.unreachable.push(first as u16..address as u16);
+                    range_start = None;
+                }
+                (false, None) => range_start = Some(address),
+                _ => {}
+            }
+        }
+
+        graph.check_stack(&calls);
+        graph.halts.sort_unstable();
+        graph.unresolved_jumps.sort_unstable();
+        graph.invalid_instructions.sort_unstable();
+        graph
+    }
+
+    /// Returns the basic blocks in ascending order of address.
+    pub fn blocks(&self) -> impl Iterator<Item = &BasicBlock> {
+        self.blocks.values()
+    }
+
+    /// Returns the basic block that starts at `address`.
+    pub fn block(&self, address: u16) -> Option<&BasicBlock> {
+        self.blocks.get(&address)
+    }
+
+    /// Returns the addresses of the subroutines called with `2NNN` in ascending order.
+    pub fn subroutines(&self) -> impl Iterator<Item = u16> + '_ {
+        self.subroutines.iter().copied()
+    }
+
+    /// Returns the addresses of the `1NNN` instructions that jump to themselves, which roms use to
+    /// halt.
+    pub fn halts(&self) -> &[u16] {
+        &self.halts
+    }
+
+    /// Returns the addresses of the `BNNN` instructions, whose destinations depend on `V0`.
+    pub fn unresolved_jumps(&self) -> &[u16] {
+        &self.unresolved_jumps
+    }
+
+    /// Returns the addresses of the reachable opcodes that are not instructions.
+    pub fn invalid_instructions(&self) -> &[u16] {
+        &self.invalid_instructions
+    }
+
+    /// Returns the ranges of the rom that are not part of any reachable instruction, which are
+    /// either data, such as sprites, or dead code. Code that is only reached through `BNNN` jump
+    /// tables is also included.
+    pub fn unreachable(&self) -> &[Range<u16>] {
+        &self.unreachable
+    }
+
+    /// Returns the problems with the use of the stack.
+    pub fn stack_issues(&self) -> &[StackIssue] {
+        &self.stack_issues
+    }
+
+    /// Writes the graph to `out` in the Graphviz DOT format. Blocks are labeled with their
+    /// disassembly, and with the labels of `symbols` if given.
+    pub fn write_dot(&self, out: &mut impl Write, symbols: Option<&SymbolMap>) -> io::Result<()> {
+        writeln!(out, "digraph rom {{")?;
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];")?;
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            if let Some(name) = symbols.and_then(|symbols| symbols.label(block.start)) {
+                label.push_str(&format!("{}:\\l", escape(name)));
+            }
+            for address in (block.start..block.end).step_by(2) {
+                label.push_str(&format!(
+                    "{:03X} {}\\l",
+                    address,
+                    disassemble(&self.memory, address as usize)
+                ));
+            }
+            let mut attributes = format!("label=\"{}\"", label);
+            if self.subroutines.contains(&block.start) || block.start == PROGRAM_START {
+                attributes.push_str(", style=bold");
+            }
+            if self.halts.contains(&(block.end.wrapping_sub(2))) {
+                attributes.push_str(", peripheries=2");
+            }
+            writeln!(out, "    b{:03X} [{}];", block.start, attributes)?;
+        }
+        for block in self.blocks.values() {
+            for (target, kind) in &block.successors {
+                let attributes = match kind {
+                    EdgeKind::Next | EdgeKind::Jump => "",
+                    EdgeKind::Skip => " [label=\"skip\"]",
+                    EdgeKind::Call => " [style=dashed, label=\"call\"]",
+                    EdgeKind::JumpTable => " [style=dotted, label=\"V0\"]",
+                };
+                writeln!(
+                    out,
+                    "    b{:03X} -> b{:03X}{};",
+                    block.start, target, attributes
+                )?;
+            }
+        }
+        writeln!(out, "}}")
+    }
+
+    fn instruction(&self, address: u16) -> Option<Instruction> {
+        match (
+            self.memory.get(address as usize),
+            self.memory.get(address as usize + 1),
+        ) {
+            (Some(high), Some(low)) => Instruction::decode(u16::from_be_bytes([*high, *low])),
+            _ => None,
+        }
+    }
+
+    // Returns the blocks reachable from `entry` without following calls, and the addresses of the
+    // `00EE` instructions among them.
+    fn function(&self, entry: u16) -> (BTreeSet<u16>, Vec<u16>) {
+        let mut visited = BTreeSet::new();
+        let mut returns = Vec::new();
+        let mut pending = vec![entry];
+        while let Some(address) = pending.pop() {
+            let block = match self.blocks.get(&address) {
+                Some(block) if visited.insert(address) => block,
+                _ => continue,
+            };
+            if self.instruction(block.end.wrapping_sub(2)) == Some(Instruction::Return) {
+                returns.push(block.end.wrapping_sub(2));
+            }
+            for (target, kind) in &block.successors {
+                if *kind != EdgeKind::Call {
+                    pending.push(*target);
+                }
+            }
+        }
+        (visited, returns)
+    }
+
+    fn check_stack(&mut self, calls: &[(u16, u16)]) {
+        let (main, mut returns) = self.function(PROGRAM_START);
+        returns.sort_unstable();
+        self.stack_issues
+            .extend(returns.into_iter().map(StackIssue::ReturnWithoutCall));
+
+        let functions: BTreeMap<u16, (BTreeSet<u16>, Vec<u16>)> = self
+            .subroutines
+            .iter()
+            .map(|entry| (*entry, self.function(*entry)))
+            .collect();
+        let mut calls = calls.to_vec();
+        calls.sort_unstable();
+        for (call, subroutine) in calls {
+            let (blocks, returns) = &functions[&subroutine];
+            let is_shared = blocks.iter().any(|block| {
+                main.contains(block)
+                    || functions
+                        .iter()
+                        .any(|(entry, (other, _))| *entry != subroutine && other.contains(block))
+            });
+            if returns.is_empty() && is_shared {
+                self.stack_issues
+                    .push(StackIssue::CallWithoutReturn { call, subroutine });
+            }
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 200: CALL 0x20A; 202: SE V0, 0; 204: JP 0x200; 206: JP V0, 0x210; 208: JP 0x208;
+    // 20A: LD V0, 1; 20C: RET; 20E: sprite data; 210: JP 0x208
+    const ROM: [u8; 18] = [
+        0x22, 0x0A, 0x30, 0x00, 0x12, 0x00, 0xB2, 0x10, 0x12, 0x08, 0x60, 0x01, 0x00, 0xEE, 0xF0,
+        0x90, 0x12, 0x08,
+    ];
+
+    #[test]
+    fn test_analyze() {
+        let graph = ControlFlowGraph::analyze(&ROM);
+        let blocks: Vec<_> = graph.blocks().cloned().collect();
+        assert_eq!(
+            blocks,
+            vec![
+                BasicBlock {
+                    start: 0x200,
+                    end: 0x202,
+                    successors: vec![(0x20A, EdgeKind::Call), (0x202, EdgeKind::Next)],
+                },
+                BasicBlock {
+                    start: 0x202,
+                    end: 0x204,
+                    successors: vec![(0x204, EdgeKind::Next), (0x206, EdgeKind::Skip)],
+                },
+                BasicBlock {
+                    start: 0x204,
+                    end: 0x206,
+                    successors: vec![(0x200, EdgeKind::Jump)],
+                },
+                BasicBlock {
+                    start: 0x206,
+                    end: 0x208,
+                    successors: vec![(0x210, EdgeKind::JumpTable)],
+                },
+                BasicBlock {
+                    start: 0x208,
+                    end: 0x20A,
+                    successors: vec![(0x208, EdgeKind::Jump)],
+                },
+                BasicBlock {
+                    start: 0x20A,
+                    end: 0x20E,
+                    successors: vec![],
+                },
+                BasicBlock {
+                    start: 0x210,
+                    end: 0x212,
+                    successors: vec![(0x208, EdgeKind::Jump)],
+                },
+            ]
+        );
+        assert_eq!(graph.subroutines().collect::<Vec<_>>(), vec![0x20A]);
+        assert_eq!(graph.halts(), &[0x208]);
+        assert_eq!(graph.unresolved_jumps(), &[0x206]);
+        assert_eq!(graph.unreachable().to_vec(), vec![0x20E..0x210]);
+        assert!(graph.stack_issues().is_empty());
+        assert!(graph.invalid_instructions().is_empty());
+    }
+
+    #[test]
+    fn test_stack_issues() {
+        // 200: CALL 0x204; 202: JP 0x200; 204: JP 0x202
+        let graph = ControlFlowGraph::analyze(&[0x22, 0x04, 0x12, 0x00, 0x12, 0x02]);
+        assert_eq!(
+            graph.stack_issues(),
+            &[StackIssue::CallWithoutReturn {
+                call: 0x200,
+                subroutine: 0x204
+            }]
+        );
+
+        // 200: SE V0, 0; 202: JP 0x202; 204: RET
+        let graph = ControlFlowGraph::analyze(&[0x30, 0x00, 0x12, 0x02, 0x00, 0xEE]);
+        assert_eq!(
+            graph.stack_issues(),
+            &[StackIssue::ReturnWithoutCall(0x204)]
+        );
+
+        // 200: LD V0, 0; 202: invalid
+        let graph = ControlFlowGraph::analyze(&[0x60, 0x00, 0x50, 0x01]);
+        assert_eq!(graph.invalid_instructions(), &[0x202]);
+        assert_eq!(graph.blocks().count(), 1);
+    }
+
+    #[test]
+    fn test_dot() {
+        let graph = ControlFlowGraph::analyze(&ROM);
+        let mut symbols = SymbolMap::new();
+        symbols.insert_label(0x200, "main");
+        let mut out = Vec::new();
+        graph.write_dot(&mut out, Some(&symbols)).unwrap();
+        let dot = String::from_utf8(out).unwrap();
+        assert!(dot.starts_with("digraph rom {\n"));
+        assert!(dot.contains("    b200 [label=\"main:\\l200 CALL 0x20A\\l\", style=bold];\n"));
+        assert!(dot.contains("    b208 [label=\"208 JP 0x208\\l\", peripheries=2];\n"));
+        assert!(dot.contains("    b200 -> b20A [style=dashed, label=\"call\"];\n"));
+        assert!(dot.contains("    b202 -> b206 [label=\"skip\"];\n"));
+        assert!(dot.contains("    b206 -> b210 [style=dotted, label=\"V0\"];\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
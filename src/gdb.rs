@@ -0,0 +1,593 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+use crate::{Chip8, Debugger, Error, REGISTER_COUNT, STACK_SIZE};
+
+// Signals reported in stop replies.
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+const EINVAL: &str = "E16";
+const EFAULT: &str = "E0e";
+
+// The byte sent by GDB to interrupt a running target.
+const INTERRUPT: u8 = 0x03;
+
+const PACKET_SIZE: usize = 0x1000;
+
+// The sizes in bytes of V0 to VF, I, PC, SP, DT, and ST.
+const REGISTER_SIZES: [usize; REGISTER_COUNT + 5] = [
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 1, 1, 1,
+];
+
+/// A GDB remote serial protocol server that debugs a `Chip8` over a TCP connection.
+///
+/// The registers are numbered as follows, and registers wider than a byte are little-endian:
+///
+/// | Number | Register | Size in bytes |
+/// |--------|----------|---------------|
+/// | 0-15   | V0-VF    | 1             |
+/// | 16     | I        | 2             |
+/// | 17     | PC       | 2             |
+/// | 18     | SP       | 1             |
+/// | 19     | DT       | 1             |
+/// | 20     | ST       | 1             |
+///
+/// The layout is also described to GDB by a target description. Single-stepping, software and
+/// hardware breakpoints, and memory reads and writes are supported, and the delay and sound timers
+/// are decremented once every `cycles_per_frame` instructions.
+#[derive(Clone, Debug)]
+pub struct GdbServer {
+    debugger: Debugger,
+    cycles_per_frame: usize,
+    // The number of instructions executed since the timers were last decremented.
+    cycles: usize,
+    no_ack: bool,
+}
+
+// What to do after handling a packet.
+#[derive(Debug, Eq, PartialEq)]
+enum Action {
+    Reply(String),
+    Resume { step: bool },
+    StartNoAckMode,
+    Detach,
+    Kill,
+}
+
+impl GdbServer {
+    /// Constructs a new `GdbServer` that decrements the timers every `cycles_per_frame`
+    /// instructions.
+    pub fn new(cycles_per_frame: usize) -> Self {
+        GdbServer {
+            debugger: Debugger::new(),
+            cycles_per_frame: cycles_per_frame.max(1),
+            cycles: 0,
+            no_ack: false,
+        }
+    }
+
+    /// Returns the debugger that holds the breakpoints.
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    /// Returns the debugger that holds the breakpoints mutably, for example to add breakpoints
+    /// before GDB connects.
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    /// Serves GDB on `stream` until it detaches, kills the target, or closes the connection.
+    pub fn serve(&mut self, chip_8: &mut Chip8, mut stream: TcpStream) -> io::Result<()> {
+        self.no_ack = false;
+        while let Some(packet) = self.read_packet(&mut stream)? {
+            let reply = match self.handle_packet(chip_8, &packet) {
+                Action::Reply(reply) => reply,
+                Action::Resume { step } => self.resume(chip_8, &mut stream, step)?,
+                Action::StartNoAckMode => {
+                    self.write_packet(&mut stream, "OK")?;
+                    self.no_ack = true;
+                    continue;
+                }
+                Action::Detach => return self.write_packet(&mut stream, "OK"),
+                Action::Kill => return Ok(()),
+            };
+            self.write_packet(&mut stream, &reply)?;
+        }
+        Ok(())
+    }
+
+    // Reads the next packet, or returns `None` if the connection is closed.
+    fn read_packet(&mut self, stream: &mut TcpStream) -> io::Result<Option<String>> {
+        loop {
+            match read_byte(stream)? {
+                Some(b'$') => {}
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match read_byte(stream)? {
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                    None => return Ok(None),
+                }
+            }
+            let mut checksum = [0; 2];
+            stream.read_exact(&mut checksum)?;
+
+            let is_valid = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+                == Some(self::checksum(&data));
+            if !self.no_ack {
+                stream.write_all(if is_valid { b"+" } else { b"-" })?;
+            }
+            if is_valid || self.no_ack {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+        }
+    }
+
+    fn write_packet(&mut self, stream: &mut TcpStream, data: &str) -> io::Result<()> {
+        let mut escaped = Vec::with_capacity(data.len());
+        for byte in data.bytes() {
+            if let b'#' | b'$' | b'}' | b'*' = byte {
+                escaped.extend_from_slice(&[b'}', byte ^ 0x20]);
+            } else {
+                escaped.push(byte);
+            }
+        }
+        let mut packet = vec![b'$'];
+        packet.extend_from_slice(&escaped);
+        packet.extend_from_slice(format!("#{:02x}", checksum(&escaped)).as_bytes());
+
+        loop {
+            stream.write_all(&packet)?;
+            if self.no_ack {
+                return Ok(());
+            }
+            // Retransmit until the packet is acknowledged.
+            loop {
+                match read_byte(stream)? {
+                    Some(b'+') | None => return Ok(()),
+                    Some(b'-') => break,
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+
+    fn handle_packet(&mut self, chip_8: &mut Chip8, packet: &str) -> Action {
+        let mut chars = packet.chars();
+        let command = chars.next();
+        let args = chars.as_str();
+        let reply = match command {
+            Some('?') => stop_reply(SIGTRAP),
+            Some('g') => encode_hex(
+                &(0..REGISTER_SIZES.len())
+                    .flat_map(|n| read_register(chip_8, n).unwrap())
+                    .collect::<Vec<_>>(),
+            ),
+            Some('G') => match decode_hex(args) {
+                Some(ref bytes) if bytes.len() == REGISTER_SIZES.iter().sum::<usize>() => {
+                    write_registers(chip_8, bytes)
+                }
+                _ => EINVAL.to_string(),
+            },
+            Some('p') => match parse_hex(args).and_then(|n| read_register(chip_8, n)) {
+                Some(bytes) => encode_hex(&bytes),
+                None => EINVAL.to_string(),
+            },
+            Some('P') => {
+                let register = args.split_once('=').and_then(|(n, value)| {
+                    let n = parse_hex(n)?;
+                    let value = decode_hex(value)?;
+                    write_register(chip_8, n, &value)
+                });
+                match register {
+                    Some(()) => "OK".to_string(),
+                    None => EINVAL.to_string(),
+                }
+            }
+            Some('m') => match parse_range(args) {
+                Some((address, length)) => {
+                    let memory = chip_8.memory();
+                    if address
+                        .checked_add(length)
+                        .map_or(false, |end| end <= memory.len())
+                    {
+                        encode_hex(&memory[address..address + length])
+                    } else {
+                        EFAULT.to_string()
+                    }
+                }
+                None => EINVAL.to_string(),
+            },
+            Some('M') => {
+                let write = args.split_once(':').and_then(|(range, data)| {
+                    let (address, length) = parse_range(range)?;
+                    let data = decode_hex(data)?;
+                    if data.len() == length {
+                        Some((address, data))
+                    } else {
+                        None
+                    }
+                });
+                match write {
+                    Some((address, data)) => match chip_8.write_memory(address, &data) {
+                        Ok(()) => "OK".to_string(),
+                        Err(_) => EFAULT.to_string(),
+                    },
+                    None => EINVAL.to_string(),
+                }
+            }
+            Some('c') => return Action::Resume { step: false },
+            Some('s') => return Action::Resume { step: true },
+            Some(kind @ ('Z' | 'z')) => {
+                let mut fields = args.split(',');
+                let breakpoint_type = fields.next();
+                match (breakpoint_type, fields.next().and_then(parse_hex)) {
+                    // Software and hardware breakpoints are handled the same way.
+                    (Some("0" | "1"), Some(address)) if address <= u16::MAX as usize => {
+                        if kind == 'Z' {
+                            self.debugger.add_breakpoint(address as u16);
+                        } else {
+                            self.debugger.remove_breakpoint(address as u16);
+                        }
+                        "OK".to_string()
+                    }
+                    (Some("0" | "1"), _) => EINVAL.to_string(),
+                    _ => String::new(),
+                }
+            }
+            Some('D') => return Action::Detach,
+            Some('k') => return Action::Kill,
+            Some('H') | Some('T') => "OK".to_string(),
+            Some('q') => self.handle_query(args),
+            Some('Q') if args == "StartNoAckMode" => return Action::StartNoAckMode,
+            Some('v') if args == "Kill" || args.starts_with("Kill;") => return Action::Kill,
+            _ => String::new(),
+        };
+        Action::Reply(reply)
+    }
+
+    fn handle_query(&self, query: &str) -> String {
+        if query.starts_with("Supported") {
+            return format!(
+                "PacketSize={:x};qXfer:features:read+;QStartNoAckMode+;swbreak+;hwbreak+",
+                PACKET_SIZE
+            );
+        }
+        if let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            let (offset, length) = match parse_range(range) {
+                Some(range) => range,
+                None => return EINVAL.to_string(),
+            };
+            let description = target_description();
+            let start = offset.min(description.len());
+            let end = start.saturating_add(length).min(description.len());
+            let prefix = if end == description.len() { 'l' } else { 'm' };
+            return format!("{}{}", prefix, &description[start..end]);
+        }
+        match query {
+            "Attached" => "1".to_string(),
+            "C" => "QC1".to_string(),
+            "fThreadInfo" => "m1".to_string(),
+            "sThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    // Resumes execution and returns the stop reply. Continuing stops at breakpoints, errors, the
+    // exit instruction, or when GDB interrupts it.
+    fn resume(
+        &mut self,
+        chip_8: &mut Chip8,
+        stream: &mut TcpStream,
+        step: bool,
+    ) -> io::Result<String> {
+        if step {
+            return Ok(self
+                .execute(chip_8, 1, true)
+                .unwrap_or_else(|| stop_reply(SIGTRAP)));
+        }
+        loop {
+            if let Some(reply) = self.execute(chip_8, self.cycles_per_frame, false) {
+                return Ok(reply);
+            }
+            if is_interrupted(stream)? {
+                return Ok(stop_reply(SIGINT));
+            }
+        }
+    }
+
+    // Executes up to `cycles` instructions and returns a stop reply if execution stopped early.
+    fn execute(&mut self, chip_8: &mut Chip8, cycles: usize, step: bool) -> Option<String> {
+        for _ in 0..cycles {
+            if !chip_8.is_running {
+                return Some("W00".to_string());
+            }
+            let result = if step {
+                self.debugger.step(chip_8).map(|()| None)
+            } else {
+                self.debugger.run(chip_8, 1)
+            };
+            match result {
+                Ok(None) => {}
+                Ok(Some(_)) => return Some(stop_reply(SIGTRAP)),
+                Err(error) => return Some(stop_reply(signal(error))),
+            }
+            self.cycles += 1;
+            if self.cycles == self.cycles_per_frame {
+                self.cycles = 0;
+                chip_8.decrement_timers();
+            }
+        }
+        None
+    }
+}
+
+fn read_byte(stream: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    loop {
+        match stream.read(&mut byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(byte[0])),
+            Err(error) if error.kind() == ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+// Returns `true` if GDB sent an interrupt or closed the connection, without blocking.
+fn is_interrupted(stream: &mut TcpStream) -> io::Result<bool> {
+    stream.set_nonblocking(true)?;
+    let mut byte = [0];
+    let result = stream.read(&mut byte);
+    stream.set_nonblocking(false)?;
+    match result {
+        Ok(0) => Ok(true),
+        Ok(_) => Ok(byte[0] == INTERRUPT),
+        Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter()
+        .fold(0, |checksum, byte| checksum.wrapping_add(*byte))
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{:02x}", signal)
+}
+
+fn signal(error: Error) -> u8 {
+    match error {
+        Error::UnknownOpcode(_) => SIGILL,
+        _ => SIGSEGV,
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn parse_hex(hex: &str) -> Option<usize> {
+    usize::from_str_radix(hex, 16).ok()
+}
+
+// Parses `ADDRESS,LENGTH`.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (address, length) = range.split_once(',')?;
+    Some((parse_hex(address)?, parse_hex(length)?))
+}
+
+fn read_register(chip_8: &Chip8, n: usize) -> Option<Vec<u8>> {
+    Some(match n {
+        0..=15 => vec![chip_8.registers[n]],
+        16 => chip_8.index.to_le_bytes().to_vec(),
+        17 => chip_8.pc.to_le_bytes().to_vec(),
+        18 => vec![chip_8.sp as u8],
+        19 => vec![chip_8.delay_timer],
+        20 => vec![chip_8.sound_timer],
+        _ => return None,
+    })
+}
+
+fn write_register(chip_8: &mut Chip8, n: usize, bytes: &[u8]) -> Option<()> {
+    if REGISTER_SIZES.get(n) != Some(&bytes.len()) {
+        return None;
+    }
+    match n {
+        0..=15 => chip_8.registers[n] = bytes[0],
+        16 => chip_8.index = u16::from_le_bytes([bytes[0], bytes[1]]),
+        17 => chip_8.pc = u16::from_le_bytes([bytes[0], bytes[1]]),
+        18 if bytes[0] as usize <= STACK_SIZE => chip_8.sp = bytes[0] as u16,
+        19 => chip_8.delay_timer = bytes[0],
+        20 => chip_8.sound_timer = bytes[0],
+        _ => return None,
+    }
+    Some(())
+}
+
+fn write_registers(chip_8: &mut Chip8, bytes: &[u8]) -> String {
+    // Validate the stack pointer before modifying any registers.
+    if bytes[18] as usize > STACK_SIZE {
+        return EINVAL.to_string();
+    }
+    let mut offset = 0;
+    for (n, size) in REGISTER_SIZES.iter().enumerate() {
+        write_register(chip_8, n, &bytes[offset..offset + size]);
+        offset += size;
+    }
+    "OK".to_string()
+}
+
+fn target_description() -> String {
+    let mut registers = String::new();
+    for n in 0..REGISTER_COUNT {
+        registers += &format!(
+            "<reg name=\"v{:x}\" bitsize=\"8\" type=\"uint8\" regnum=\"{}\"/>",
+            n, n
+        );
+    }
+    registers += "<reg name=\"i\" bitsize=\"16\" type=\"data_ptr\"/>";
+    registers += "<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>";
+    registers += "<reg name=\"sp\" bitsize=\"8\" type=\"uint8\"/>";
+    registers += "<reg name=\"dt\" bitsize=\"8\" type=\"uint8\"/>";
+    registers += "<reg name=\"st\" bitsize=\"8\" type=\"uint8\"/>";
+    format!(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\"><feature name=\"org.chipo.chip8\">{}</feature></target>",
+        registers
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn packet(data: &str) -> String {
+        format!("${}#{:02x}", data, checksum(data.as_bytes()))
+    }
+
+    fn handle(server: &mut GdbServer, chip_8: &mut Chip8, packet: &str) -> String {
+        match server.handle_packet(chip_8, packet) {
+            Action::Reply(reply) => reply,
+            action => panic!("unexpected action: {:?}", action),
+        }
+    }
+
+    #[test]
+    fn test_handle_packet() {
+        let mut server = GdbServer::new(10);
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&[0x6A, 0x12, 0xA3, 0x45, 0x22, 0x08], false);
+
+        assert_eq!(handle(&mut server, &mut chip_8, "?"), "S05");
+        assert_eq!(
+            handle(&mut server, &mut chip_8, "g"),
+            format!("{}{}", "00".repeat(16), "00000002000000")
+        );
+        assert_eq!(handle(&mut server, &mut chip_8, "p11"), "0002");
+        assert_eq!(handle(&mut server, &mut chip_8, "p15"), EINVAL);
+        assert_eq!(handle(&mut server, &mut chip_8, "P3=7f"), "OK");
+        assert_eq!(chip_8.data_registers()[3], 0x7F);
+        assert_eq!(handle(&mut server, &mut chip_8, "P12=11"), EINVAL);
+        assert_eq!(handle(&mut server, &mut chip_8, "m200,4"), "6a12a345");
+        assert_eq!(handle(&mut server, &mut chip_8, "mfff,2"), EFAULT);
+        assert_eq!(handle(&mut server, &mut chip_8, "M300,2:abcd"), "OK");
+        assert_eq!(chip_8.memory_range(0x300, 2), vec![0xAB, 0xCD]);
+        assert_eq!(handle(&mut server, &mut chip_8, "M300,2:ab"), EINVAL);
+        assert_eq!(handle(&mut server, &mut chip_8, "Z0,204,2"), "OK");
+        assert!(server.debugger().has_breakpoint(0x204));
+        assert_eq!(handle(&mut server, &mut chip_8, "Z2,204,2"), "");
+        assert!(handle(
+            &mut server,
+            &mut chip_8,
+            "qXfer:features:read:target.xml:0,10"
+        )
+        .starts_with("m<?xml"));
+        assert_eq!(handle(&mut server, &mut chip_8, "vCont?"), "");
+
+        let registers = format!("{}{}", "01".repeat(16), "45030802020304");
+        assert_eq!(
+            handle(&mut server, &mut chip_8, &format!("G{}", registers)),
+            "OK"
+        );
+        assert_eq!(chip_8.index(), 0x345);
+        assert_eq!(chip_8.program_counter(), 0x208);
+        assert_eq!(chip_8.stack_pointer(), 2);
+        assert_eq!(chip_8.delay_timer(), 3);
+        assert_eq!(handle(&mut server, &mut chip_8, "g"), registers);
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut server = GdbServer::new(2);
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(
+            &[0x60, 0x01, 0xF0, 0x15, 0x70, 0x01, 0x12, 0x04, 0x50, 0x01],
+            false,
+        );
+        server.debugger_mut().add_breakpoint(0x206);
+
+        assert_eq!(
+            server.execute(&mut chip_8, 100, false),
+            Some("S05".to_string())
+        );
+        assert_eq!(chip_8.program_counter(), 0x206);
+        assert_eq!(chip_8.delay_timer(), 0);
+        // Stepping ignores the breakpoint and continuing resumes from it.
+        assert_eq!(server.execute(&mut chip_8, 1, true), None);
+        assert_eq!(chip_8.program_counter(), 0x204);
+        assert_eq!(
+            server.execute(&mut chip_8, 100, false),
+            Some("S05".to_string())
+        );
+        assert_eq!(chip_8.data_registers()[0], 3);
+
+        chip_8.pc = 0x208;
+        assert_eq!(
+            server.execute(&mut chip_8, 1, true),
+            Some("S04".to_string())
+        );
+    }
+
+    #[test]
+    fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            let mut exchange = |data: &str| {
+                stream.write_all(packet(data).as_bytes()).unwrap();
+                // The acknowledgement, the reply, and its checksum.
+                let mut response = Vec::new();
+                while response.len() < 3 || response[response.len() - 3] != b'#' {
+                    response.push(read_byte(&mut stream).unwrap().unwrap());
+                }
+                stream.write_all(b"+").unwrap();
+                String::from_utf8(response).unwrap()
+            };
+            vec![
+                exchange("Z0,204,2"),
+                exchange("c"),
+                exchange("p11"),
+                exchange("D"),
+            ]
+        });
+
+        let mut server = GdbServer::new(10);
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&[0x60, 0x01, 0x70, 0x01, 0x12, 0x02], false);
+        let (stream, _) = listener.accept().unwrap();
+        server.serve(&mut chip_8, stream).unwrap();
+
+        assert_eq!(
+            client.join().unwrap(),
+            ["OK", "S05", "0402", "OK"]
+                .iter()
+                .map(|reply| format!("+{}", packet(reply)))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,324 @@
+// A minimal GIF decoder that returns the palette indices of each frame, for reading cartridges.
+
+const MAX_CODES: usize = 4096;
+const MAX_CODE_SIZE: usize = 12;
+// The prefix of the codes that are a single index.
+const NO_PREFIX: u16 = u16::MAX;
+// The largest number of palette indices that are decoded from a GIF, which is checked before
+// decompressing each frame. Cartridges store each byte of their payload in two indices, so this
+// holds 8 MiB of source and options.
+const MAX_INDICES: usize = 16 << 20;
+
+pub struct Gif {
+    // The palette indices of each frame in row-major order.
+    pub frames: Vec<Vec<u8>>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn read_bytes(&mut self, count: usize) -> Result<&[u8], String> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + count)
+            .ok_or_else(|| String::from("Unexpected end of GIF"))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, String> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    // Reads data sub-blocks until the empty block that ends them.
+    fn read_sub_blocks(&mut self) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        loop {
+            let size = self.read_u8()? as usize;
+            if size == 0 {
+                return Ok(data);
+            }
+            data.extend_from_slice(self.read_bytes(size)?);
+        }
+    }
+
+    fn skip_color_table(&mut self, flags: u8) -> Result<(), String> {
+        if flags & 0x80 != 0 {
+            self.read_bytes(3 << ((flags & 0x07) + 1))?;
+        }
+        Ok(())
+    }
+}
+
+impl Gif {
+    pub fn decode(data: &[u8]) -> Result<Gif, String> {
+        let mut reader = Reader { data, position: 0 };
+        let signature = reader.read_bytes(6)?;
+        if signature != b"GIF87a" && signature != b"GIF89a" {
+            return Err(String::from("Not a GIF"));
+        }
+        let width = reader.read_u16()? as usize;
+        let height = reader.read_u16()? as usize;
+        if width * height > MAX_INDICES {
+            return Err(format!("GIF is too large: {}x{}", width, height));
+        }
+        let flags = reader.read_u8()?;
+        reader.read_bytes(2)?;
+        reader.skip_color_table(flags)?;
+
+        let mut frames: Vec<Vec<u8>> = Vec::new();
+        loop {
+            match reader.read_u8()? {
+                // Extensions, such as graphic controls and comments.
+                0x21 => {
+                    reader.read_u8()?;
+                    reader.read_sub_blocks()?;
+                }
+                0x2C => {
+                    reader.read_bytes(4)?;
+                    let frame_width = reader.read_u16()? as usize;
+                    let frame_height = reader.read_u16()? as usize;
+                    let flags = reader.read_u8()?;
+                    reader.skip_color_table(flags)?;
+                    let min_code_size = reader.read_u8()? as usize;
+                    let data = reader.read_sub_blocks()?;
+
+                    // Cartridges only have frames that cover the whole image.
+                    if (frame_width, frame_height) != (width, height) {
+                        return Err(format!(
+                            "Frame size {}x{} does not match the GIF size {}x{}",
+                            frame_width, frame_height, width, height
+                        ));
+                    }
+                    let size = frame_width * frame_height;
+                    if (frames.len() + 1) * size > MAX_INDICES {
+                        return Err(String::from("GIF has too many frames"));
+                    }
+                    let mut indices = decompress(&data, min_code_size, size)?;
+                    if indices.len() < size {
+                        return Err(String::from("Truncated image data"));
+                    }
+                    if flags & 0x40 != 0 {
+                        indices = deinterlace(&indices, frame_width, frame_height);
+                    }
+                    frames.push(indices);
+                }
+                0x3B => break,
+                block => return Err(format!("Unknown block: 0x{:02X}", block)),
+            }
+        }
+
+        Ok(Gif { frames })
+    }
+}
+
+// Decompresses the LZW encoded palette indices of a frame, stopping after `size` indices.
+fn decompress(data: &[u8], min_code_size: usize, size: usize) -> Result<Vec<u8>, String> {
+    if !(1..MAX_CODE_SIZE).contains(&min_code_size) {
+        return Err(format!("Invalid LZW code size: {}", min_code_size));
+    }
+    let clear_code = 1 << min_code_size;
+    let end_code = clear_code + 1;
+
+    // Each code is a string of indices, stored as the code of its prefix and its last index.
+    let mut prefixes = [NO_PREFIX; MAX_CODES];
+    let mut suffixes = [0u8; MAX_CODES];
+    let mut lengths = [1usize; MAX_CODES];
+    for (code, suffix) in suffixes.iter_mut().enumerate().take(clear_code) {
+        *suffix = code as u8;
+    }
+    let first_index = |prefixes: &[u16], suffixes: &[u8], mut code: usize| {
+        while prefixes[code] != NO_PREFIX {
+            code = prefixes[code] as usize;
+        }
+        suffixes[code]
+    };
+
+    let mut indices = Vec::new();
+    let mut code_size = min_code_size + 1;
+    let mut next_code = clear_code + 2;
+    let mut previous: Option<usize> = None;
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    let mut bytes = data.iter();
+
+    while indices.len() < size {
+        while bit_count < code_size {
+            let byte = match bytes.next() {
+                Some(byte) => *byte,
+                // Some encoders omit the end code.
+                None => return Ok(indices),
+            };
+            bits |= (byte as u32) << bit_count;
+            bit_count += 8;
+        }
+        let code = (bits & ((1 << code_size) - 1)) as usize;
+        bits >>= code_size;
+        bit_count -= code_size;
+
+        if code == clear_code {
+            code_size = min_code_size + 1;
+            next_code = clear_code + 2;
+            previous = None;
+            continue;
+        }
+        if code == end_code {
+            return Ok(indices);
+        }
+
+        let first = match previous {
+            _ if code < clear_code || (code > end_code && code < next_code) => {
+                first_index(&prefixes, &suffixes, code)
+            }
+            // The code that is about to be added, which starts and ends with the same index.
+            Some(previous) if code == next_code => first_index(&prefixes, &suffixes, previous),
+            _ => return Err(format!("Invalid LZW code: {}", code)),
+        };
+        if let Some(previous) = previous {
+            if next_code < MAX_CODES {
+                prefixes[next_code] = previous as u16;
+                suffixes[next_code] = first;
+                lengths[next_code] = lengths[previous] + 1;
+                next_code += 1;
+                if next_code == 1 << code_size && code_size < MAX_CODE_SIZE {
+                    code_size += 1;
+                }
+            }
+        }
+
+        let start = indices.len();
+        indices.resize(start + lengths[code], 0);
+        let mut current = code;
+        for index in indices[start..].iter_mut().rev() {
+            *index = suffixes[current];
+            current = prefixes[current] as usize;
+        }
+        previous = Some(code);
+    }
+    indices.truncate(size);
+    Ok(indices)
+}
+
+// Reorders the rows of an interlaced frame, which are stored in four passes.
+fn deinterlace(indices: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut ret = vec![0; indices.len()];
+    let rows = [(0, 8), (4, 8), (2, 4), (1, 2)]
+        .iter()
+        .flat_map(|&(start, step)| (start..height).step_by(step));
+    for (source, row) in rows.enumerate() {
+        ret[row * width..(row + 1) * width]
+            .copy_from_slice(&indices[source * width..(source + 1) * width]);
+    }
+    ret
+}
+
+// Encodes frames of palette indices with a 256 color palette. Codes stay 9 bits long by clearing
+// the table before it grows, which is valid but does not compress.
+#[cfg(test)]
+pub fn encode(width: u16, height: u16, frames: &[Vec<u8>]) -> Vec<u8> {
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend_from_slice(&width.to_le_bytes());
+    gif.extend_from_slice(&height.to_le_bytes());
+    gif.extend_from_slice(&[0xF7, 0, 0]);
+    for color in 0..=255u8 {
+        gif.extend_from_slice(&[color, color, color]);
+    }
+    // A comment, which is skipped.
+    gif.extend_from_slice(&[0x21, 0xFE, 2, b'h', b'i', 0]);
+
+    for frame in frames {
+        gif.push(0x2C);
+        gif.extend_from_slice(&[0, 0, 0, 0]);
+        gif.extend_from_slice(&width.to_le_bytes());
+        gif.extend_from_slice(&height.to_le_bytes());
+        gif.extend_from_slice(&[0, 8]);
+
+        let mut codes = Vec::new();
+        for chunk in frame.chunks(254) {
+            codes.push(256);
+            codes.extend(chunk.iter().map(|&index| index as u32));
+        }
+        codes.push(257);
+        let mut data = Vec::new();
+        let mut bits = 0u32;
+        let mut bit_count = 0;
+        for code in codes {
+            bits |= code << bit_count;
+            bit_count += 9;
+            while bit_count >= 8 {
+                data.push(bits as u8);
+                bits >>= 8;
+                bit_count -= 8;
+            }
+        }
+        if bit_count > 0 {
+            data.push(bits as u8);
+        }
+        for block in data.chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend_from_slice(block);
+        }
+        gif.push(0);
+    }
+    gif.push(0x3B);
+    gif
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let frames = vec![
+            (0..=255).chain(0..=255).collect::<Vec<u8>>(),
+            (0..512).map(|i| (i % 7) as u8).collect(),
+        ];
+        let gif = Gif::decode(&encode(32, 16, &frames)).unwrap();
+        assert_eq!(gif.frames, frames);
+
+        // Frames stop at their size even if the image data has more indices.
+        let gif = Gif::decode(&encode(2, 1, &[vec![3; 1000]])).unwrap();
+        assert_eq!(gif.frames, vec![vec![3, 3]]);
+
+        assert!(Gif::decode(b"PNG").is_err());
+        assert!(Gif::decode(&encode(32, 16, &frames)[..40]).is_err());
+
+        let mut gif = encode(2, 1, &[vec![3; 2]]);
+        gif[6] = 3;
+        assert_eq!(
+            Gif::decode(&gif).err().unwrap(),
+            "Frame size 2x1 does not match the GIF size 3x1"
+        );
+        gif[6..10].copy_from_slice(&[0xFF; 4]);
+        assert_eq!(
+            Gif::decode(&gif).err().unwrap(),
+            "GIF is too large: 65535x65535"
+        );
+    }
+
+    #[test]
+    fn test_decompress() {
+        // A 2-bit code size with codes that grow from 3 to 5 bits, including codes that are used
+        // in the same step that they are added.
+        let data = [0x8C, 0x2F, 0x99, 0x30, 0x21, 0x03, 0x42, 0x01];
+        assert_eq!(
+            decompress(&data, 2, 100).unwrap(),
+            vec![1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 0, 3, 1, 2, 3, 0, 1]
+        );
+        assert_eq!(decompress(&data, 2, 5).unwrap(), vec![1; 5]);
+        assert!(decompress(&[0x3C, 0x00], 2, 100).is_err());
+        assert_eq!(
+            deinterlace(&[0, 4, 2, 6, 1, 3, 5, 7], 1, 8),
+            vec![0, 1, 2, 3, 4, 5, 6, 7]
+        );
+    }
+}
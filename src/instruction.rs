@@ -0,0 +1,330 @@
+use std::fmt;
+
+/// A decoded chip-8 or SCHIP instruction. Registers are identified by their index, so `VX` is
+/// `X`.
+///
+/// Instructions are displayed with the mnemonics of Cowgod's Chip-8 Technical Reference, such as
+/// `LD V1, 0x0A` and `DRW V0, V1, 5`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Instruction {
+    /// `00CN`: Scroll the screen down by `N` rows.
+    ScrollDown(usize),
+    /// `00E0`: Clear the screen.
+    ClearScreen,
+    /// `00EE`: Return from a subroutine.
+    Return,
+    /// `00FB`: Scroll the screen right by 4 pixels.
+    ScrollRight,
+    /// `00FC`: Scroll the screen left by 4 pixels.
+    ScrollLeft,
+    /// `00FD`: Exit the interpreter.
+    Exit,
+    /// `00FE`: Switch to standard mode.
+    LowResolution,
+    /// `00FF`: Switch to super mode.
+    HighResolution,
+    /// `1NNN`: Jump to `NNN`.
+    Jump(u16),
+    /// `2NNN`: Call the subroutine at `NNN`.
+    Call(u16),
+    /// `3XKK`: Skip the next instruction if `VX == KK`.
+    SkipIfEqualByte(usize, u8),
+    /// `4XKK`: Skip the next instruction if `VX != KK`.
+    SkipIfNotEqualByte(usize, u8),
+    /// `5XY0`: Skip the next instruction if `VX == VY`.
+    SkipIfEqual(usize, usize),
+    /// `6XKK`: Set `VX` to `KK`.
+    LoadByte(usize, u8),
+    /// `7XKK`: Add `KK` to `VX`.
+    AddByte(usize, u8),
+    /// `8XY0`: Set `VX` to `VY`.
+    Load(usize, usize),
+    /// `8XY1`: Set `VX` to `VX | VY`.
+    Or(usize, usize),
+    /// `8XY2`: Set `VX` to `VX & VY`.
+    And(usize, usize),
+    /// `8XY3`: Set `VX` to `VX ^ VY`.
+    Xor(usize, usize),
+    /// `8XY4`: Add `VY` to `VX` and set `VF` to the carry.
+    Add(usize, usize),
+    /// `8XY5`: Subtract `VY` from `VX` and set `VF` to the inverted borrow.
+    Sub(usize, usize),
+    /// `8XY6`: Shift `VX` right by one and set `VF` to the shifted out bit.
+    ShiftRight(usize, usize),
+    /// `8XY7`: Set `VX` to `VY - VX` and set `VF` to the inverted borrow.
+    SubNegated(usize, usize),
+    /// `8XYE`: Shift `VX` left by one and set `VF` to the shifted out bit.
+    ShiftLeft(usize, usize),
+    /// `9XY0`: Skip the next instruction if `VX != VY`.
+    SkipIfNotEqual(usize, usize),
+    /// `ANNN`: Set `I` to `NNN`.
+    LoadIndex(u16),
+    /// `BNNN`: Jump to `NNN + V0`.
+    JumpOffset(u16),
+    /// `CXKK`: Set `VX` to a random byte masked by `KK`.
+    Random(usize, u8),
+    /// `DXYN`: Draw the sprite of `N` rows at `I` at `(VX, VY)`.
+    Draw(usize, usize, usize),
+    /// `EX9E`: Skip the next instruction if the key `VX` is pressed.
+    SkipIfKey(usize),
+    /// `EXA1`: Skip the next instruction if the key `VX` is not pressed.
+    SkipIfNotKey(usize),
+    /// `FX07`: Set `VX` to the delay timer.
+    LoadDelayTimer(usize),
+    /// `FX0A`: Wait for a key and store it in `VX`.
+    WaitForKey(usize),
+    /// `FX15`: Set the delay timer to `VX`.
+    SetDelayTimer(usize),
+    /// `FX18`: Set the sound timer to `VX`.
+    SetSoundTimer(usize),
+    /// `FX1E`: Add `VX` to `I`.
+    AddIndex(usize),
+    /// `FX29`: Set `I` to the small font character `VX`.
+    LoadFont(usize),
+    /// `FX30`: Set `I` to the large font character `VX`.
+    LoadLargeFont(usize),
+    /// `FX33`: Store the binary-coded decimal of `VX` at `I`.
+    StoreBcd(usize),
+    /// `FX55`: Store `V0` to `VX` at `I`.
+    StoreRegisters(usize),
+    /// `FX65`: Load `V0` to `VX` from `I`.
+    LoadRegisters(usize),
+    /// `FX75`: Store `V0` to `VX` in the RPL user flags.
+    StoreFlags(usize),
+    /// `FX85`: Load `V0` to `VX` from the RPL user flags.
+    LoadFlags(usize),
+}
+
+impl Instruction {
+    /// Decodes `opcode`. Returns `None` if `opcode` is not an instruction supported by the
+    /// emulator.
+    pub fn decode(opcode: u16) -> Option<Self> {
+        let tokens = (
+            (opcode & 0xF000) >> 12,
+            (opcode & 0x0F00) >> 8,
+            (opcode & 0x00F0) >> 4,
+            opcode & 0x000F,
+        );
+
+        let x = tokens.1 as usize;
+        let y = tokens.2 as usize;
+        let nnn = opcode & 0x0FFF;
+        let kk = (opcode & 0x00FF) as u8;
+        let n = (opcode & 0x000F) as usize;
+
+        let ret = match tokens {
+            (0x0, 0x0, 0xC, _) => Instruction::ScrollDown(n),
+            (0x0, 0x0, 0xE, 0x0) => Instruction::ClearScreen,
+            (0x0, 0x0, 0xE, 0xE) => Instruction::Return,
+            (0x0, 0x0, 0xF, 0xB) => Instruction::ScrollRight,
+            (0x0, 0x0, 0xF, 0xC) => Instruction::ScrollLeft,
+            (0x0, 0x0, 0xF, 0xD) => Instruction::Exit,
+            (0x0, 0x0, 0xF, 0xE) => Instruction::LowResolution,
+            (0x0, 0x0, 0xF, 0xF) => Instruction::HighResolution,
+            (0x1, _, _, _) => Instruction::Jump(nnn),
+            (0x2, _, _, _) => Instruction::Call(nnn),
+            (0x3, _, _, _) => Instruction::SkipIfEqualByte(x, kk),
+            (0x4, _, _, _) => Instruction::SkipIfNotEqualByte(x, kk),
+            (0x5, _, _, 0x0) => Instruction::SkipIfEqual(x, y),
+            (0x6, _, _, _) => Instruction::LoadByte(x, kk),
+            (0x7, _, _, _) => Instruction::AddByte(x, kk),
+            (0x8, _, _, 0x0) => Instruction::Load(x, y),
+            (0x8, _, _, 0x1) => Instruction::Or(x, y),
+            (0x8, _, _, 0x2) => Instruction::And(x, y),
+            (0x8, _, _, 0x3) => Instruction::Xor(x, y),
+            (0x8, _, _, 0x4) => Instruction::Add(x, y),
+            (0x8, _, _, 0x5) => Instruction::Sub(x, y),
+            (0x8, _, _, 0x6) => Instruction::ShiftRight(x, y),
+            (0x8, _, _, 0x7) => Instruction::SubNegated(x, y),
+            (0x8, _, _, 0xE) => Instruction::ShiftLeft(x, y),
+            (0x9, _, _, 0x0) => Instruction::SkipIfNotEqual(x, y),
+            (0xA, _, _, _) => Instruction::LoadIndex(nnn),
+            (0xB, _, _, _) => Instruction::JumpOffset(nnn),
+            (0xC, _, _, _) => Instruction::Random(x, kk),
+            (0xD, _, _, _) => Instruction::Draw(x, y, n),
+            (0xE, _, 0x9, 0xE) => Instruction::SkipIfKey(x),
+            (0xE, _, 0xA, 0x1) => Instruction::SkipIfNotKey(x),
+            (0xF, _, 0x0, 0x7) => Instruction::LoadDelayTimer(x),
+            (0xF, _, 0x0, 0xA) => Instruction::WaitForKey(x),
+            (0xF, _, 0x1, 0x5) => Instruction::SetDelayTimer(x),
+            (0xF, _, 0x1, 0x8) => Instruction::SetSoundTimer(x),
+            (0xF, _, 0x1, 0xE) => Instruction::AddIndex(x),
+            (0xF, _, 0x2, 0x9) => Instruction::LoadFont(x),
+            (0xF, _, 0x3, 0x0) => Instruction::LoadLargeFont(x),
+            (0xF, _, 0x3, 0x3) => Instruction::StoreBcd(x),
+            (0xF, _, 0x5, 0x5) => Instruction::StoreRegisters(x),
+            (0xF, _, 0x6, 0x5) => Instruction::LoadRegisters(x),
+            (0xF, _, 0x7, 0x5) if x < 8 => Instruction::StoreFlags(x),
+            (0xF, _, 0x8, 0x5) if x < 8 => Instruction::LoadFlags(x),
+            _ => return None,
+        };
+        Some(ret)
+    }
+
+    /// Returns the opcode pattern of the instruction, such as `8XY4` or `DXYN`.
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            Instruction::ScrollDown(..) => "00CN",
+            Instruction::ClearScreen => "00E0",
+            Instruction::Return => "00EE",
+            Instruction::ScrollRight => "00FB",
+            Instruction::ScrollLeft => "00FC",
+            Instruction::Exit => "00FD",
+            Instruction::LowResolution => "00FE",
+            Instruction::HighResolution => "00FF",
+            Instruction::Jump(..) => "1NNN",
+            Instruction::Call(..) => "2NNN",
+            Instruction::SkipIfEqualByte(..) => "3XKK",
+            Instruction::SkipIfNotEqualByte(..) => "4XKK",
+            Instruction::SkipIfEqual(..) => "5XY0",
+            Instruction::LoadByte(..) => "6XKK",
+            Instruction::AddByte(..) => "7XKK",
+            Instruction::Load(..) => "8XY0",
+            Instruction::Or(..) => "8XY1",
+            Instruction::And(..) => "8XY2",
+            Instruction::Xor(..) => "8XY3",
+            Instruction::Add(..) => "8XY4",
+            Instruction::Sub(..) => "8XY5",
+            Instruction::ShiftRight(..) => "8XY6",
+            Instruction::SubNegated(..) => "8XY7",
+            Instruction::ShiftLeft(..) => "8XYE",
+            Instruction::SkipIfNotEqual(..) => "9XY0",
+            Instruction::LoadIndex(..) => "ANNN",
+            Instruction::JumpOffset(..) => "BNNN",
+            Instruction::Random(..) => "CXKK",
+            Instruction::Draw(..) => "DXYN",
+            Instruction::SkipIfKey(..) => "EX9E",
+            Instruction::SkipIfNotKey(..) => "EXA1",
+            Instruction::LoadDelayTimer(..) => "FX07",
+            Instruction::WaitForKey(..) => "FX0A",
+            Instruction::SetDelayTimer(..) => "FX15",
+            Instruction::SetSoundTimer(..) => "FX18",
+            Instruction::AddIndex(..) => "FX1E",
+            Instruction::LoadFont(..) => "FX29",
+            Instruction::LoadLargeFont(..) => "FX30",
+            Instruction::StoreBcd(..) => "FX33",
+            Instruction::StoreRegisters(..) => "FX55",
+            Instruction::LoadRegisters(..) => "FX65",
+            Instruction::StoreFlags(..) => "FX75",
+            Instruction::LoadFlags(..) => "FX85",
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::ScrollDown(n) => write!(f, "SCD {}", n),
+            Instruction::ClearScreen => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::LowResolution => write!(f, "LOW"),
+            Instruction::HighResolution => write!(f, "HIGH"),
+            Instruction::Jump(nnn) => write!(f, "JP 0x{:03X}", nnn),
+            Instruction::Call(nnn) => write!(f, "CALL 0x{:03X}", nnn),
+            Instruction::SkipIfEqualByte(x, kk) => write!(f, "SE V{:X}, 0x{:02X}", x, kk),
+            Instruction::SkipIfNotEqualByte(x, kk) => write!(f, "SNE V{:X}, 0x{:02X}", x, kk),
+            Instruction::SkipIfEqual(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::LoadByte(x, kk) => write!(f, "LD V{:X}, 0x{:02X}", x, kk),
+            Instruction::AddByte(x, kk) => write!(f, "ADD V{:X}, 0x{:02X}", x, kk),
+            Instruction::Load(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::Add(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubNegated(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipIfNotEqual(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadIndex(nnn) => write!(f, "LD I, 0x{:03X}", nnn),
+            Instruction::JumpOffset(nnn) => write!(f, "JP V0, 0x{:03X}", nnn),
+            Instruction::Random(x, kk) => write!(f, "RND V{:X}, 0x{:02X}", x, kk),
+            Instruction::Draw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipIfKey(x) => write!(f, "SKP V{:X}", x),
+            Instruction::SkipIfNotKey(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::LoadDelayTimer(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitForKey(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelayTimer(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSoundTimer(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddIndex(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::LoadFont(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::LoadLargeFont(x) => write!(f, "LD HF, V{:X}", x),
+            Instruction::StoreBcd(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::StoreRegisters(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::LoadRegisters(x) => write!(f, "LD V{:X}, [I]", x),
+            Instruction::StoreFlags(x) => write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags(x) => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
+
+/// Returns the disassembly of the 2-byte opcode at `address` in `memory`. Opcodes that are not
+/// instructions are displayed as data, such as `DW 0x5001`.
+pub fn disassemble(memory: &[u8], address: usize) -> String {
+    let opcode = match (memory.get(address), memory.get(address + 1)) {
+        (Some(high), Some(low)) => u16::from_be_bytes([*high, *low]),
+        (Some(high), None) => return format!("DB 0x{:02X}", high),
+        _ => return String::new(),
+    };
+    match Instruction::decode(opcode) {
+        Some(instruction) => instruction.to_string(),
+        None => format!("DW 0x{:04X}", opcode),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Chip8, Error};
+
+    #[test]
+    fn test_decode_matches_emulator() {
+        for opcode in 0..=0xFFFFu16 {
+            let mut chip_8 = Chip8::new();
+            chip_8.load_rom(&opcode.to_be_bytes(), true);
+            let is_unknown = chip_8.step() == Err(Error::UnknownOpcode(opcode));
+            assert_eq!(
+                Instruction::decode(opcode).is_none(),
+                is_unknown,
+                "{:04X}",
+                opcode,
+            );
+        }
+    }
+
+    #[test]
+    fn test_pattern() {
+        for opcode in 0..=0xFFFFu16 {
+            if let Some(instruction) = Instruction::decode(opcode) {
+                let digits = format!("{:04X}", opcode);
+                let pattern = instruction.pattern();
+                assert!(
+                    pattern
+                        .chars()
+                        .zip(digits.chars())
+                        .all(|(p, d)| !p.is_ascii_hexdigit() || p == d),
+                    "{} {}",
+                    pattern,
+                    digits,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let memory = [
+            0x00, 0xE0, 0x6A, 0x0F, 0xDA, 0xB5, 0xF3, 0x55, 0x50, 0x01, 0x12,
+        ];
+        assert_eq!(disassemble(&memory, 0), "CLS");
+        assert_eq!(disassemble(&memory, 2), "LD VA, 0x0F");
+        assert_eq!(disassemble(&memory, 4), "DRW VA, VB, 5");
+        assert_eq!(disassemble(&memory, 6), "LD [I], V3");
+        assert_eq!(disassemble(&memory, 8), "DW 0x5001");
+        assert_eq!(disassemble(&memory, 10), "DB 0x12");
+        assert_eq!(disassemble(&memory, 11), "");
+    }
+}
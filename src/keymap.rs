@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+const KEY_COUNT: u8 = 16;
+
+// The hex keys of the COSMAC VIP keypad in row-major order.
+const KEYPAD_LAYOUT: [u8; KEY_COUNT as usize] = [
+    0x1, 0x2, 0x3, 0xC, //
+    0x4, 0x5, 0x6, 0xD, //
+    0x7, 0x8, 0x9, 0xE, //
+    0xA, 0x0, 0xB, 0xF, //
+];
+
+const PHYSICAL_KEYS: [&str; KEY_COUNT as usize] = [
+    "Digit1", "Digit2", "Digit3", "Digit4", //
+    "KeyQ", "KeyW", "KeyE", "KeyR", //
+    "KeyA", "KeyS", "KeyD", "KeyF", //
+    "KeyZ", "KeyX", "KeyC", "KeyV", //
+];
+
+const QWERTY_KEYS: [&str; KEY_COUNT as usize] = [
+    "1", "2", "3", "4", //
+    "q", "w", "e", "r", //
+    "a", "s", "d", "f", //
+    "z", "x", "c", "v", //
+];
+
+const AZERTY_KEYS: [&str; KEY_COUNT as usize] = [
+    "&", "é", "\"", "'", //
+    "a", "z", "e", "r", //
+    "q", "s", "d", "f", //
+    "w", "x", "c", "v", //
+];
+
+const DVORAK_KEYS: [&str; KEY_COUNT as usize] = [
+    "1", "2", "3", "4", //
+    "'", ",", ".", "p", //
+    "a", "o", "e", "u", //
+    ";", "q", "j", "k", //
+];
+
+/// A mapping from host key identifiers to keys of the hex keypad.
+///
+/// A host key identifier is any string that a frontend uses to identify a key, such as the value
+/// of `KeyboardEvent.code` or `KeyboardEvent.key` in a browser, or a scancode. The presets map the
+/// 4x4 block of keys below and including `1` to `4` on the host keyboard to the layout of the
+/// COSMAC VIP keypad:
+///
+/// ```text
+/// 1 2 3 C
+/// 4 5 6 D
+/// 7 8 9 E
+/// A 0 B F
+/// ```
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct KeyMap {
+    bindings: HashMap<String, u8>,
+}
+
+impl KeyMap {
+    fn from_host_keys(host_keys: &[&str; KEY_COUNT as usize]) -> Self {
+        let mut key_map = KeyMap::new();
+        for (host_key, key) in host_keys.iter().zip(KEYPAD_LAYOUT.iter()) {
+            key_map.bind(host_key, *key);
+        }
+        key_map
+    }
+
+    /// Returns an iterator over the bindings as pairs of host key identifiers and hex keys.
+    pub fn bindings(&self) -> impl Iterator<Item = (&str, u8)> {
+        self.bindings
+            .iter()
+            .map(|(host_key, key)| (host_key.as_str(), *key))
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl KeyMap {
+    /// Constructs a new, empty `KeyMap`.
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        KeyMap {
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Constructs a new `KeyMap` that uses the `KeyboardEvent.code` values of the keys, such as
+    /// `"KeyQ"`. These identify physical keys, so the mapping works with any keyboard layout.
+    pub fn physical() -> Self {
+        Self::from_host_keys(&PHYSICAL_KEYS)
+    }
+
+    /// Constructs a new `KeyMap` that uses the lowercase characters produced by a QWERTY
+    /// keyboard, such as the value of `KeyboardEvent.key`.
+    pub fn qwerty() -> Self {
+        Self::from_host_keys(&QWERTY_KEYS)
+    }
+
+    /// Constructs a new `KeyMap` that uses the lowercase characters produced by a French AZERTY
+    /// keyboard, such as the value of `KeyboardEvent.key`.
+    pub fn azerty() -> Self {
+        Self::from_host_keys(&AZERTY_KEYS)
+    }
+
+    /// Constructs a new `KeyMap` that uses the lowercase characters produced by a Dvorak
+    /// keyboard, such as the value of `KeyboardEvent.key`.
+    pub fn dvorak() -> Self {
+        Self::from_host_keys(&DVORAK_KEYS)
+    }
+
+    /// Binds `host_key` to the hex key `key`, replacing any previous binding of `host_key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is not a hex key.
+    pub fn bind(&mut self, host_key: &str, key: u8) {
+        assert!(key < KEY_COUNT, "Invalid hex key: {}", key);
+        self.bindings.insert(host_key.to_string(), key);
+    }
+
+    /// Removes the binding of `host_key`.
+    pub fn unbind(&mut self, host_key: &str) {
+        self.bindings.remove(host_key);
+    }
+
+    /// Returns the hex key bound to `host_key`. If `host_key` is not bound, the binding of its
+    /// lowercase form is returned, so that characters typed with Shift or Caps Lock match the
+    /// lowercase presets.
+    pub fn get(&self, host_key: &str) -> Option<u8> {
+        self.bindings
+            .get(host_key)
+            .or_else(|| self.bindings.get(&host_key.to_lowercase()))
+            .cloned()
+    }
+
+    /// Adds all bindings of `overrides` to this key map, replacing any existing bindings of the
+    /// same host keys. This is useful for applying rom specific controls on top of a preset.
+    pub fn apply_overrides(&mut self, overrides: &KeyMap) {
+        for (host_key, key) in overrides.bindings.iter() {
+            self.bindings.insert(host_key.clone(), *key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        for key_map in &[
+            KeyMap::physical(),
+            KeyMap::qwerty(),
+            KeyMap::azerty(),
+            KeyMap::dvorak(),
+        ] {
+            let mut keys: Vec<u8> = key_map.bindings().map(|(_, key)| key).collect();
+            keys.sort();
+            assert_eq!(keys, (0..KEY_COUNT).collect::<Vec<u8>>());
+        }
+
+        assert_eq!(KeyMap::qwerty().get("x"), Some(0x0));
+        assert_eq!(KeyMap::azerty().get("a"), Some(0x4));
+        assert_eq!(KeyMap::dvorak().get("o"), Some(0x8));
+        assert_eq!(KeyMap::physical().get("KeyV"), Some(0xF));
+
+        assert_eq!(KeyMap::qwerty().get("X"), Some(0x0));
+        assert_eq!(KeyMap::azerty().get("É"), Some(0x2));
+        assert_eq!(KeyMap::physical().get("keyv"), None);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut key_map = KeyMap::qwerty();
+        let mut overrides = KeyMap::new();
+        overrides.bind("ArrowUp", 0x5);
+        overrides.bind("q", 0x8);
+        key_map.apply_overrides(&overrides);
+        key_map.unbind("w");
+
+        assert_eq!(key_map.get("ArrowUp"), Some(0x5));
+        assert_eq!(key_map.get("q"), Some(0x8));
+        assert_eq!(key_map.get("w"), None);
+        assert_eq!(key_map.get("e"), Some(0x6));
+    }
+}
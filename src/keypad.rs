@@ -1,36 +1,62 @@
-const KEY_COUNT: usize = 16;
+use std::collections::VecDeque;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum KeyEvent {
+    Pressed(usize),
+    Released(usize),
+}
 
 pub struct Keypad {
     keys: u16,
+    events: VecDeque<KeyEvent>,
 }
 
 impl Keypad {
     pub fn new() -> Self {
-        Keypad { keys: 0 }
+        Keypad {
+            keys: 0,
+            events: VecDeque::new(),
+        }
     }
 
     pub fn clear(&mut self) {
         self.keys = 0;
+        self.events.clear();
+    }
+
+    pub fn keys(&self) -> u16 {
+        self.keys
+    }
+
+    pub fn set_keys(&mut self, keys: u16) {
+        self.keys = keys;
+        self.events.clear();
     }
 
     pub fn is_pressed(&self, index: usize) -> bool {
         (self.keys & (1 << index)) != 0
     }
 
-    pub fn poll_key(&self) -> Option<usize> {
-        for i in 0..KEY_COUNT {
-            if self.is_pressed(i) {
-                return Some(i);
-            }
-        }
-        None
+    // Returns the oldest key transition that has not been polled yet.
+    pub fn poll_event(&mut self) -> Option<KeyEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn clear_events(&mut self) {
+        self.events.clear();
     }
 
     pub fn press_key(&mut self, index: usize) {
+        if !self.is_pressed(index) {
+            self.events.push_back(KeyEvent::Pressed(index));
+        }
         self.keys |= 1 << index;
     }
 
     pub fn release_key(&mut self, index: usize) {
+        if self.is_pressed(index) {
+            self.events.push_back(KeyEvent::Released(index));
+        }
         self.keys &= !(1 << index);
     }
 }
@@ -20,6 +20,12 @@
 //!
 //! Example JavaScript usage: [`chipo-web`](https://gitlab.com/jeffrey-xiao/chipo-web).
 //!
+//! The package includes TypeScript definitions. Methods such as `screen_buffer`, `data_registers`,
+//! `stack`, and `memory_range` return copies as typed arrays, so they remain valid when the
+//! WebAssembly memory grows. Consumers that need to avoid the copies can build with the `pointer-api`
+//! feature, which adds `screen` and `registers` methods that return raw pointers into the
+//! WebAssembly memory.
+//!
 //! ## Rust Usage
 //!
 //! Add this to your `Cargo.toml`:
@@ -35,6 +41,401 @@
 //! extern crate chipo;
 //! ```
 //!
+//! ## C Usage
+//!
+//! Build with the `ffi` feature to add a C API to the shared library, which is declared in the
+//! header `include/chipo.h`:
+//!
+//! ```text
+//! $ cargo build --release --features ffi
+//! ```
+//!
+//! After changing the API, set `CHIPO_GENERATE_HEADER` and enable the `generate-header` feature to
+//! regenerate the header:
+//!
+//! ```text
+//! $ CHIPO_GENERATE_HEADER=1 cargo build --features generate-header
+//! ```
+//!
+//! Every function returns a `ChipoResult` error code or a plain value. Buffers are owned by the
+//! caller: `chipo_screen_size()` and `chipo_save_state_size()` return the sizes of the buffers that
+//! `chipo_screen` and `chipo_save_state` write into.
+//!
+//! ```c
+//! Chipo *chipo = chipo_new();
+//! if (chipo_load_rom(chipo, rom, rom_length, true) != CHIPO_RESULT_OK) {
+//!     /* ... */
+//! }
+//! while (chipo_run_frame(chipo, 10) == CHIPO_RESULT_OK) {
+//!     chipo_screen(chipo, screen, chipo_screen_size());
+//!     /* ... */
+//! }
+//! chipo_free(chipo);
+//! ```
+//!
+//! ## Libretro Usage
+//!
+//! Build with the `libretro` feature to export the libretro API from the shared library, which can
+//! then be loaded as a core by RetroArch and other libretro frontends:
+//!
+//! ```text
+//! $ cargo build --release --features libretro
+//! $ retroarch -L target/release/libchipo.so rom.ch8
+//! ```
+//!
+//! The core supports save states, so rewind and netplay work out of the box. Port 1 can be a gamepad,
+//! which uses the standard controller profile, or a keyboard, which uses the QWERTY key map. The core
+//! options select the quirks preset, the number of instructions per frame, and the palette.
+//!
+//! ## Terminal Usage
+//!
+//! Build with the `cli` feature to install the `chipo` binary, which runs a rom in a terminal:
+//!
+//! ```text
+//! $ cargo install chipo --features cli
+//! $ chipo --quirks schip-modern --speed 30 rom.ch8
+//! ```
+//!
+//! The screen is drawn with Unicode half-blocks, or braille characters with `--braille`, so it works
+//! over SSH. The 4x4 block of keys from `1` to `V` is the hex keypad and the sound timer rings the
+//! terminal bell. Run `chipo --help` for the flags that select the quirks preset, speed, palette, and
+//! whether sprites wrap or clip.
+//!
+//! ## Debugger Usage
+//!
+//! The `cli` feature also installs `chipo-debug`, which shows the disassembly around the program
+//! counter, the registers, the call stack, a memory dump, and the screen of a paused rom:
+//!
+//! ```text
+//! $ chipo-debug --break 2a4 rom.ch8
+//! ```
+//!
+//! Press `F10` to step, `F5` to continue or pause, `F9` to toggle a breakpoint, `F2` to write bytes to
+//! memory, and `F3` to scroll the memory dump to an address. The same breakpoints and disassembler are
+//! available to Rust code through `Debugger` and `disassemble`:
+//!
+//! ```rust
+//! use chipo::{disassemble, Chip8, Debugger};
+//!
+//! let mut chip_8 = Chip8::new();
+//! chip_8.load_rom(&[0x60, 0x05, 0x70, 0x01, 0x12, 0x02], false);
+//! let mut debugger = Debugger::new();
+//! debugger.add_breakpoint(0x204);
+//! assert_eq!(debugger.run(&mut chip_8, 10), Ok(Some(0x204)));
+//! assert_eq!(disassemble(chip_8.memory(), 0x204), "JP 0x202");
+//! ```
+//!
+//! ## GDB Usage
+//!
+//! The `cli` feature also installs `chipo-gdb`, which serves a rom over the GDB remote serial protocol
+//! so that GDB and other tools that speak it can step through the rom, set breakpoints, and read and
+//! write registers and memory:
+//!
+//! ```text
+//! $ chipo-gdb --port 1234 rom.ch8
+//! $ gdb -ex 'target remote 127.0.0.1:1234'
+//! ```
+//!
+//! The registers are numbered V0 to VF from 0 to 15, followed by I, PC, SP, DT, and ST. I and PC are
+//! 16-bit little-endian values and the others are 8-bit. `GdbServer` serves a `Chip8` from Rust code.
+//!
+//! ## Editor Debugging
+//!
+//! The `cli` feature also installs `chipo-dap`, a Debug Adapter Protocol server for debugging roms in
+//! VS Code and other editors. Configure it as the debug adapter executable and launch a rom with a
+//! `launch` configuration like this:
+//!
+//! ```json
+//! {
+//!     "program": "${workspaceFolder}/game.ch8",
+//!     "symbols": "${workspaceFolder}/game.sym",
+//!     "quirks": "schip-modern",
+//!     "stopOnEntry": true
+//! }
+//! ```
+//!
+//! Breakpoints can be set on the source lines listed in the symbol map, which maps addresses to
+//! `path:line` source lines and labels:
+//!
+//! ```text
+//! 0x200 main
+//! 0x200 game.8o:12
+//! 0x202 game.8o:13
+//! ```
+//!
+//! The registers and timers are shown as variables, the call stack as stack frames, and memory and
+//! disassembly views are supported. `DapServer` and `SymbolMap` are also available to Rust code.
+//!
+//! ## Tracing
+//!
+//! `Tracer` records every executed instruction with the registers, index, and timers that it changed,
+//! in a ring buffer and optionally to a writer in a stable text or JSON lines format, so traces can be
+//! diffed between versions of chipo or against other emulators:
+//!
+//! ```rust
+//! use chipo::{Chip8, TraceFormat, Tracer};
+//! use std::io;
+//!
+//! let mut chip_8 = Chip8::new();
+//! chip_8.load_rom(&[0x6A, 0x12, 0xA3, 0x45], false);
+//! let mut tracer = Tracer::with_writer(100, io::stdout(), TraceFormat::Text);
+//! chip_8.run_frame_observed(2, &mut tracer).unwrap();
+//! tracer.flush().unwrap();
+//! // 200 6A12 LD VA, 0x12 | VA=12
+//! // 202 A345 LD I, 0x345 | I=345
+//! ```
+//!
+//! `Chip8::step_observed` and `Chip8::run_frame_observed` notify a `StepObserver` before and after
+//! every executed instruction and at the end of each frame. `Tracer`, `Profiler`, and `Coverage` are
+//! observers, and other tools can implement the trait to watch a rom in the same way.
+//!
+//! The `cli` feature also installs `chipo-trace`, which writes the trace of a rom running headless for
+//! a number of frames:
+//!
+//! ```text
+//! $ chipo-trace --frames 600 --format json rom.ch8 > trace.jsonl
+//! ```
+//!
+//! ## Differential Testing
+//!
+//! `ReferenceTrace` loads a trace of another emulator, with the state before every instruction as CSV
+//! or JSON, and replays the same rom in lockstep to find the first instruction that behaves
+//! differently. This is useful to check which quirks match a known-good implementation:
+//!
+//! ```text
+//! pc,opcode,v0,v1,v2,v3,v4,v5,v6,v7,v8,v9,va,vb,vc,vd,ve,vf,i,sp
+//! 200,6181,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0
+//! 202,8106,0,81,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0
+//! 204,1204,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0
+//! ```
+//!
+//! The optional `dt` and `st` columns are compared if present, and the optional `memory` column lists
+//! bytes to compare, such as `300=0C 301=FF`. JSON traces use the same keys, either as an array of
+//! objects or one object per line. The `cli` feature also installs `chipo-diff`:
+//!
+//! ```text
+//! $ chipo-diff --quirks schip-legacy rom.ch8 reference.csv
+//! Diverged at step 2 after the instruction at 0x202: V1 is 0x40, expected 0x00, VF is 0x01, expected 0x00
+//! ```
+//!
+//! ## Profiling
+//!
+//! `Profiler` counts the instructions executed per address and per opcode class, the instructions
+//! spent inside each `2NNN` subroutine with and without the subroutines it calls, and the backward
+//! jumps that form loops, to find what makes a rom slow at a given number of instructions per frame:
+//!
+//! ```rust
+//! use chipo::{Chip8, Profiler};
+//!
+//! let mut chip_8 = Chip8::new();
+//! chip_8.load_rom(&[0x60, 0x03, 0x70, 0xFF, 0x30, 0x00, 0x12, 0x02, 0x12, 0x08], false);
+//! let mut profiler = Profiler::new();
+//! chip_8.run_frame_observed(20, &mut profiler).unwrap();
+//! let hottest = profiler.hottest_loops(1)[0];
+//! assert_eq!((hottest.start, hottest.end, hottest.iterations), (0x202, 0x206, 2));
+//! ```
+//!
+//! The `cli` feature also installs `chipo-profile`, which runs a rom headless and prints a report,
+//! naming subroutines and loops with the labels of a symbol map:
+//!
+//! ```text
+//! $ chipo-profile --frames 600 --symbols game.sym rom.ch8
+//! ```
+//!
+//! ## Coverage
+//!
+//! `Coverage` records which bytes of memory were executed as instructions, read as sprites or data by
+//! `DXYN` and `FX65`, and written by `FX33` and `FX55`. It writes an annotated disassembly, and an
+//! lcov tracefile of the source lines of a symbol map that coverage tools can display:
+//!
+//! ```rust
+//! use chipo::{Chip8, Coverage};
+//!
+//! let mut chip_8 = Chip8::new();
+//! chip_8.load_rom(&[0xA2, 0x06, 0xD0, 0x11, 0x12, 0x04, 0xF0], false);
+//! let mut coverage = Coverage::new();
+//! chip_8.run_frame_observed(4, &mut coverage).unwrap();
+//! assert!(coverage.is_executed(0x204));
+//! assert!(coverage.is_read(0x206));
+//! ```
+//!
+//! Writes to bytes that were executed and the execution of bytes that were written are recorded as
+//! self-modifications with the address of the writing instruction, which separates code from data
+//! in roms that patch their own instructions with `FX33` and `FX55`.
+//!
+//! The `cli` feature also installs `chipo-coverage`, which runs a rom headless and prints its
+//! coverage:
+//!
+//! ```text
+//! $ chipo-coverage --frames 600 --symbols game.sym --lcov game.info rom.ch8
+//! ```
+//!
+//! ## Control Flow Analysis
+//!
+//! `ControlFlowGraph` follows every instruction that can be reached from `0x200` without running the
+//! rom, through skips, jumps, calls, and returns, and splits them into basic blocks. `BNNN` jumps
+//! depend on `V0`, so only `NNN` is followed and the jump is reported as unresolved. The analysis
+//! also reports the ranges of the rom that are never reached, the `1NNN` instructions that jump to
+//! themselves to halt, returns without a call, and subroutines that jump away instead of returning:
+//!
+//! ```rust
+//! use chipo::ControlFlowGraph;
+//!
+//! // 200: CALL 0x206; 202: JP 0x202; 204: sprite data; 206: RET
+//! let graph = ControlFlowGraph::analyze(&[0x22, 0x06, 0x12, 0x02, 0xF0, 0x90, 0x00, 0xEE]);
+//! assert_eq!(graph.halts(), &[0x202]);
+//! assert_eq!(graph.unreachable()[0], 0x204..0x206);
+//! assert!(graph.stack_issues().is_empty());
+//! ```
+//!
+//! The `cli` feature also installs `chipo-cfg`, which prints these findings and writes the graph in
+//! the Graphviz DOT format:
+//!
+//! ```text
+//! $ chipo-cfg --symbols game.sym --dot game.dot rom.ch8
+//! $ dot -Tsvg game.dot > game.svg
+//! ```
+//!
+//! ## Linting
+//!
+//! Interpreters disagree on a few behaviours, such as whether `8XY6` shifts `VX` or `VY` and whether
+//! `FX55` increments `I`. `RomLints` finds the reachable instructions whose result depends on these
+//! behaviours, along with SCHIP instructions, and suggests the quirks preset that the rom most likely
+//! expects. The presets do not differ in shifts, `FX55` and `FX65`, or `BNNN`, so no preset is
+//! suggested for roms that rely on them:
+//!
+//! ```rust
+//! use chipo::{LintKind, Quirks, RomLints};
+//!
+//! // 200: SHR V1, V2; 202: JP 0x202
+//! let lints = RomLints::analyze(&[0x81, 0x26, 0x12, 0x02]);
+//! assert_eq!(lints.lints()[0].kind, LintKind::AmbiguousShift);
+//! assert_eq!(lints.suggested_preset(), None);
+//!
+//! // 200: HIGH; 202: JP 0x202
+//! let lints = RomLints::analyze(&[0x00, 0xFF, 0x12, 0x02]);
+//! let quirks = lints.suggested_preset().and_then(Quirks::from_preset);
+//! assert_eq!(quirks, Some(Quirks::schip_legacy()));
+//! ```
+//!
+//! The `cli` feature also installs `chipo-lint`, which prints the suggested preset, or `none`, and the
+//! lints of each rom:
+//!
+//! ```text
+//! $ chipo-lint roms/*.ch8
+//! ```
+//!
+//! ## Quirk Detection
+//!
+//! `QuirkDetection` runs a rom headlessly under each of `Quirks::PRESETS` while pressing a
+//! reproducible pseudo-random sequence of keys, and scores each run. Runs score higher for every
+//! frame that changes the screen, lower for every frame stuck at one address, and much lower if they
+//! stop at an error such as an unknown opcode or a stack fault. The preset of the best run is
+//! recommended, and ties are broken by the preset that `RomLints` suggests:
+//!
+//! ```rust
+//! use chipo::QuirkDetection;
+//!
+//! // 200: LD I, 0x20A; 202: LD V0, 60; 204: DRW V0, V0, 0; 206: ADD V0, 1; 208: JP 0x204;
+//! // 20A: sprite data
+//! let mut rom = vec![0xA2, 0x0A, 0x60, 0x3C, 0xD0, 0x00, 0x70, 0x01, 0x12, 0x04];
+//! rom.extend_from_slice(&[0xFF; 32]);
+//! let detection = QuirkDetection::analyze(&rom, 60, 10).unwrap();
+//! assert_eq!(detection.recommended_preset(), "schip-legacy");
+//! ```
+//!
+//! The `cli` feature also installs `chipo-detect`, which prints the recommended preset and the runs
+//! of each rom:
+//!
+//! ```text
+//! $ chipo-detect --frames 1200 roms/*.ch8
+//! ```
+//!
+//! ## Rom Database
+//!
+//! `RomDatabase` identifies roms by their SHA-1 hash and looks up their title, authors, platforms,
+//! quirks, tickrate, controller profile, and palette. It reads the `programs.json` format of the
+//! [chip-8 database](https://github.com/chip-8/chip-8-database), either from the small table
+//! embedded in chipo or from a user file. `RomDatabase::configure` sets the quirks and controller
+//! profile of the loaded rom, and returns the metadata so that the frontend can apply the rest:
+//!
+//! ```rust
+//! use chipo::{Chip8, Quirks, RomDatabase};
+//!
+//! let rom = [0x00, 0xFF, 0x12, 0x02];
+//! let mut database = RomDatabase::embedded();
+//! database.extend(
+//!     RomDatabase::parse(
+//!         r#"[{
+//!             "title": "Halt",
+//!             "roms": {
+//!                 "7b3f3d97549d49af8dc765be630b7a2e17a9af15": {
+//!                     "platforms": ["superchip"],
+//!                     "tickrate": 30
+//!                 }
+//!             }
+//!         }]"#,
+//!     )
+//!     .unwrap(),
+//! );
+//!
+//! let mut chip_8 = Chip8::new();
+//! chip_8.load_rom(&rom, true);
+//! let info = database.configure(&mut chip_8).unwrap();
+//! assert_eq!(info.tickrate, Some(30));
+//! assert_eq!(chip_8.quirks(), Quirks::schip_legacy());
+//! ```
+//!
+//! `chipo` uses the quirks, speed, and palette of the rom database unless they are set on the command
+//! line, and `--database PATH` adds a user file, such as the full `programs.json` of the chip-8
+//! database, to the embedded table. The arrow keys, space, and enter press the d-pad, `A`, and start
+//! buttons of the controller profile of the rom, or of the standard profile.
+//!
+//! ## Octo Cartridges
+//!
+//! Octo shares programs as cartridges, which are GIF images that hide the source and options of a
+//! program in the low bits of their palette indices. `Octocart` decodes a cartridge into its Octo
+//! source and `OctoOptions`, which holds the tickrate, quirks, colors, and screen rotation. The
+//! options convert to the closest `Quirks` and to a `Palette`, and `OctoOptions::unsupported_quirks`
+//! lists the quirks that chipo cannot emulate.
+//!
+//! Cartridge support is decode-only. chipo does not assemble Octo source, so it cannot run a
+//! cartridge directly. The source has to be assembled by Octo, and the assembled rom is loaded with
+//! the options of the cartridge:
+//!
+//! ```rust
+//! use chipo::{Chip8, DrawMode, Octocart};
+//! use std::error::Error;
+//! use std::fs;
+//!
+//! fn load(cart_path: &str, rom_path: &str) -> Result<Chip8, Box<dyn Error>> {
+//!     let cart = Octocart::parse(&fs::read(cart_path)?)?;
+//!     println!("{}", cart.source);
+//!     for (name, value) in cart.options.unsupported_quirks() {
+//!         eprintln!("Unsupported quirk: {} {}", name, value);
+//!     }
+//!     let quirks = cart.options.quirks();
+//!     let mut chip_8 = Chip8::new();
+//!     chip_8.set_quirks(quirks);
+//!     chip_8.try_load_rom(&fs::read(rom_path)?, quirks.draw_mode == DrawMode::Wrap)?;
+//!     Ok(chip_8)
+//! }
+//! ```
+//!
+//! The `cli` feature also installs `chipo-cart-extract`, which only extracts a cartridge. It writes
+//! the source and reports the options along with the `chipo` options that run the assembled rom with
+//! the same settings:
+//!
+//! ```text
+//! $ chipo-cart-extract --source game.8o game.gif
+//! Tickrate: 20
+//! Quirks: shift, clip
+//! Colors: fill #FFCC00, fill 2 default, blend default, background #996600, buzz default, quiet default
+//! Screen rotation: 0
+//! Unsupported quirks: loadStoreQuirks false
+//! chipo options: --quirks schip-modern --clip --speed 20
+//! ```
+//!
 //! ## Changelog
 //!
 //! See [CHANGELOG](CHANGELOG.md) for more details.
@@ -57,22 +458,81 @@ use cfg_if::cfg_if;
 
 cfg_if! {
     if #[cfg(target_arch = "wasm32")] {
-        fn generate_u8() -> u8 {
-            (js_sys::Math::random() * 256.0).floor() as u8
+        fn generate_seed() -> u32 {
+            (js_sys::Math::random() * 4_294_967_296.0).floor() as u32
         }
     } else {
-        fn generate_u8() -> u8 {
+        fn generate_seed() -> u32 {
             rand::thread_rng().gen()
         }
     }
 }
 
+mod controller;
+mod coverage;
+mod dap;
+mod database;
+mod debugger;
+mod detect;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod flow;
+mod gdb;
+mod gif;
+mod instruction;
+mod json;
+mod keymap;
 mod keypad;
+#[cfg(feature = "libretro")]
+mod libretro;
+mod lint;
+mod observer;
+mod octocart;
+mod palette;
+mod profiler;
+mod quirks;
+mod random;
+mod reference;
 mod screen;
+mod sha1;
+mod state;
+mod symbols;
+#[cfg(feature = "cli")]
+pub mod terminal;
+mod text;
+mod trace;
 
-use crate::keypad::Keypad;
-use crate::screen::{Screen, ScreenMode};
-#[cfg(all(target_arch = "wasm32", console_error_panic_hook))]
+pub use crate::controller::{Button, ControllerProfile, ControllerProfiles, ProfileParseError};
+pub use crate::coverage::{Coverage, SelfModification, SelfModificationKind};
+pub use crate::dap::DapServer;
+pub use crate::database::{DatabaseParseError, Platform, RomDatabase, RomInfo};
+pub use crate::debugger::Debugger;
+pub use crate::detect::{PresetRun, QuirkDetection};
+pub use crate::error::Error;
+pub use crate::flow::{BasicBlock, ControlFlowGraph, EdgeKind, StackIssue};
+pub use crate::gdb::GdbServer;
+pub use crate::instruction::{disassemble, Instruction};
+pub use crate::keymap::KeyMap;
+pub use crate::lint::{Lint, LintKind, RomLints};
+pub use crate::observer::StepObserver;
+pub use crate::octocart::{OctoOptions, Octocart, OctocartError};
+pub use crate::palette::Palette;
+pub use crate::profiler::{LoopProfile, Profiler, SubroutineProfile};
+pub use crate::quirks::{DrawMode, LargeSprite, Quirks};
+pub use crate::reference::{
+    Difference, Divergence, ReferenceStep, ReferenceTrace, TraceParseError,
+};
+pub use crate::screen::{DirtyRegion, ScreenMode};
+pub use crate::state::STATE_SIZE;
+pub use crate::symbols::{SourceLocation, SymbolMap, SymbolParseError};
+pub use crate::text::TextStyle;
+pub use crate::trace::{TraceEntry, TraceFormat, Tracer};
+
+use crate::keypad::{KeyEvent, Keypad};
+use crate::random::XorShift;
+use crate::screen::Screen;
+#[cfg(all(target_arch = "wasm32", feature = "console_error_panic_hook"))]
 use console_error_panic_hook::set_once;
 #[cfg(not(target_arch = "wasm32"))]
 use rand::Rng;
@@ -85,6 +545,11 @@ const REGISTER_COUNT: usize = 16;
 const PROGRAM_START: u16 = 0x200;
 const SUPER_MODE_RPL_FLAG_COUNT: usize = 8;
 
+/// The width in pixels of the buffer returned by [`Chip8::screen_buffer`].
+pub const SCREEN_BUFFER_WIDTH: usize = screen::SUPER_SCREEN_WIDTH;
+/// The height in pixels of the buffer returned by [`Chip8::screen_buffer`].
+pub const SCREEN_BUFFER_HEIGHT: usize = screen::SUPER_SCREEN_HEIGHT;
+
 const FONTSET: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -123,12 +588,6 @@ const SUPER_FONTSET: [u8; 160] = [
     0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
 ];
 
-#[derive(Copy, Clone, Eq, PartialEq)]
-enum DrawMode {
-    Clip,
-    Wrap,
-}
-
 /// A chip-8 emulator.
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 pub struct Chip8 {
@@ -142,18 +601,25 @@ pub struct Chip8 {
     stack: [u16; STACK_SIZE],
     sp: u16,
     keypad: Keypad,
+    key_map: KeyMap,
+    controller_profile: ControllerProfile,
+    waiting_keys: u16,
     super_mode_rpl_flags: [u8; SUPER_MODE_RPL_FLAG_COUNT],
+    random: XorShift,
+    rom_hash: [u8; 20],
     should_draw: bool,
     should_beep: bool,
     is_running: bool,
-    draw_mode: DrawMode,
+    quirks: Quirks,
 }
 
+// The wasm bindings of the deprecated methods call them, so they are allowed here.
+#[allow(deprecated)]
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 impl Chip8 {
     /// Constructs a new `Chip8`.
     pub fn new() -> Self {
-        #[cfg(all(target_arch = "wasm32", console_error_panic_hook))]
+        #[cfg(all(target_arch = "wasm32", feature = "console_error_panic_hook"))]
         set_once();
 
         Chip8 {
@@ -167,11 +633,16 @@ impl Chip8 {
             stack: [0; STACK_SIZE],
             sp: 0,
             keypad: Keypad::new(),
+            key_map: KeyMap::qwerty(),
+            controller_profile: ControllerProfile::standard(),
+            waiting_keys: 0,
             super_mode_rpl_flags: [0; SUPER_MODE_RPL_FLAG_COUNT],
+            random: XorShift::new(generate_seed()),
+            rom_hash: sha1::digest(&[]),
             should_draw: false,
             should_beep: false,
             is_running: true,
-            draw_mode: DrawMode::Wrap,
+            quirks: Quirks::new(),
         }
     }
 
@@ -199,8 +670,7 @@ impl Chip8 {
 
         self.index = 0;
         self.pc = PROGRAM_START;
-        self.screen.clear_screen();
-        self.screen.set_mode(ScreenMode::Standard);
+        self.screen.reset();
         self.should_draw = true;
         self.delay_timer = 0;
         self.sound_timer = 0;
@@ -212,6 +682,7 @@ impl Chip8 {
         self.sp = 0;
 
         self.keypad.clear();
+        self.waiting_keys = 0;
 
         for i in self.super_mode_rpl_flags.iter_mut() {
             *i = 0;
@@ -222,36 +693,40 @@ impl Chip8 {
 
     /// Loads a rom and sets the drawing mode of the emulator. If `should_wrap` is true, then all
     /// pixels drawn outside of the drawable area will wrap to the other side, else they will be
-    /// ignored.
+    /// ignored. This overrides `Quirks::draw_mode`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rom does not fit in memory.
     pub fn load_rom(&mut self, rom: &[u8], should_wrap: bool) {
-        self.initialize();
-        self.draw_mode = {
-            if should_wrap {
-                DrawMode::Wrap
-            } else {
-                DrawMode::Clip
-            }
-        };
+        if let Err(error) = self.try_load_rom(rom, should_wrap) {
+            panic!("{}", error);
+        }
+    }
 
-        for (i, byte) in rom.iter().enumerate() {
-            self.memory[i + PROGRAM_START as usize] = *byte;
+    fn check_memory(&self, start: usize, length: usize) -> Result<(), Error> {
+        if start + length > MEMORY_SIZE {
+            return Err(Error::MemoryOutOfBounds(start));
         }
+        Ok(())
     }
 
-    fn fetch_opcode(&self) -> u16 {
-        (u16::from(self.memory[self.pc as usize]) << 8)
-            | u16::from(self.memory[(self.pc + 1) as usize])
+    fn fetch_opcode(&self) -> Result<u16, Error> {
+        self.check_memory(self.pc as usize, 2)?;
+        Ok((u16::from(self.memory[self.pc as usize]) << 8)
+            | u16::from(self.memory[(self.pc + 1) as usize]))
     }
 
-    /// Runs one fetch-decode-execute cycle.
+    /// Runs one fetch-decode-execute cycle. Key presses and releases that happened since the
+    /// previous cycle are only visible to `FX0A` during this cycle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the instruction is invalid. See `step` for details.
     pub fn execute_cycle(&mut self) {
-        if !self.is_running {
-            return;
+        if let Err(error) = self.step() {
+            panic!("{}", error);
         }
-        let opcode = self.fetch_opcode();
-        self.pc += 2;
-
-        self.process_opcode(opcode);
     }
 
     /// Decrement the delay and sound timer by one tick.
@@ -268,75 +743,83 @@ impl Chip8 {
         }
     }
 
-    fn process_opcode(&mut self, opcode: u16) {
+    fn process_opcode(&mut self, opcode: u16) -> Result<(), Error> {
         self.should_beep = false;
         self.should_draw = false;
 
-        let tokens = (
-            (opcode & 0xF000) >> 12,
-            (opcode & 0x0F00) >> 8,
-            (opcode & 0x00F0) >> 4,
-            opcode & 0x000F,
-        );
-
-        let x = tokens.1 as usize;
-        let y = tokens.2 as usize;
-        let nnn = opcode & 0x0FFF;
-        let kk = (opcode & 0x00FF) as u8;
-        let n = (opcode & 0x000F) as usize;
-
-        match tokens {
-            (0x0, 0x0, 0xC, _) => {
-                self.screen.scroll_down(n);
+        let instruction = match Instruction::decode(opcode) {
+            Some(instruction) => instruction,
+            None => return Err(Error::UnknownOpcode(opcode)),
+        };
+        match instruction {
+            Instruction::ScrollDown(n) => {
+                self.screen
+                    .scroll_down(n, self.quirks.half_scroll_in_standard_mode);
                 self.should_draw = true;
             }
-            (0x0, 0x0, 0xE, 0x0) => {
+            Instruction::ClearScreen => {
                 self.screen.clear_screen();
                 self.should_draw = true;
             }
-            (0x0, 0x0, 0xE, 0xE) => {
+            Instruction::Return => {
+                if self.sp == 0 {
+                    return Err(Error::StackUnderflow);
+                }
                 self.sp -= 1;
                 self.pc = self.stack[self.sp as usize];
             }
-            (0x0, 0x0, 0xF, 0xB) => {
-                self.screen.scroll_right();
+            Instruction::ScrollRight => {
+                self.screen
+                    .scroll_right(self.quirks.half_scroll_in_standard_mode);
+                self.should_draw = true;
+            }
+            Instruction::ScrollLeft => {
+                self.screen
+                    .scroll_left(self.quirks.half_scroll_in_standard_mode);
+                self.should_draw = true;
+            }
+            Instruction::Exit => self.is_running = false,
+            Instruction::LowResolution => {
+                self.screen
+                    .set_mode(ScreenMode::Standard, self.quirks.clear_on_mode_switch);
                 self.should_draw = true;
             }
-            (0x0, 0x0, 0xF, 0xC) => {
-                self.screen.scroll_left();
+            Instruction::HighResolution => {
+                self.screen
+                    .set_mode(ScreenMode::Super, self.quirks.clear_on_mode_switch);
                 self.should_draw = true;
             }
-            (0x0, 0x0, 0xF, 0xD) => self.is_running = false,
-            (0x0, 0x0, 0xF, 0xE) => self.screen.set_mode(ScreenMode::Standard),
-            (0x0, 0x0, 0xF, 0xF) => self.screen.set_mode(ScreenMode::Super),
-            (0x1, _, _, _) => self.pc = nnn,
-            (0x2, _, _, _) => {
+            Instruction::Jump(nnn) => self.pc = nnn,
+            Instruction::Call(nnn) => {
+                if self.sp as usize == STACK_SIZE {
+                    return Err(Error::StackOverflow);
+                }
                 self.stack[self.sp as usize] = self.pc;
                 self.sp += 1;
                 self.pc = nnn;
             }
-            (0x3, _, _, _) => {
+            Instruction::SkipIfEqualByte(x, kk) => {
                 if self.registers[x] == kk {
                     self.pc += 2;
                 }
             }
-            (0x4, _, _, _) => {
+            Instruction::SkipIfNotEqualByte(x, kk) => {
                 if self.registers[x] != kk {
                     self.pc += 2;
                 }
             }
-            (0x5, _, _, 0x0) => {
+            Instruction::SkipIfEqual(x, y) => {
                 if self.registers[x] == self.registers[y] {
                     self.pc += 2;
                 }
             }
-            (0x6, _, _, _) => self.registers[x] = kk,
-            (0x7, _, _, _) => self.registers[x] = self.registers[x].wrapping_add(kk),
-            (0x8, _, _, 0x0) => self.registers[x] = self.registers[y],
-            (0x8, _, _, 0x1) => self.registers[x] |= self.registers[y],
-            (0x8, _, _, 0x2) => self.registers[x] &= self.registers[y],
-            (0x8, _, _, 0x3) => self.registers[x] ^= self.registers[y],
-            (0x8, _, _, 0x4) => {
+            Instruction::LoadByte(x, kk) => self.registers[x] = kk,
+            Instruction::AddByte(x, kk) => self.registers[x] = self.registers[x].wrapping_add(kk),
+            Instruction::Load(x, y) => self.registers[x] = self.registers[y],
+            Instruction::Or(x, y) => self.registers[x] |= self.registers[y],
+            Instruction::And(x, y) => self.registers[x] &= self.registers[y],
+            Instruction::Xor(x, y) => self.registers[x] ^= self.registers[y],
+            Instruction::Add(x, y) => {
                 let (res, overflow) = self.registers[x].overflowing_add(self.registers[y]);
                 self.registers[x] = res;
                 if overflow {
@@ -345,7 +828,7 @@ impl Chip8 {
                     self.registers[15] = 0;
                 }
             }
-            (0x8, _, _, 0x5) => {
+            Instruction::Sub(x, y) => {
                 let (res, underflow) = self.registers[x].overflowing_sub(self.registers[y]);
                 self.registers[x] = res;
                 if underflow {
@@ -354,11 +837,11 @@ impl Chip8 {
                     self.registers[15] = 1;
                 }
             }
-            (0x8, _, _, 0x6) => {
+            Instruction::ShiftRight(x, _) => {
                 self.registers[15] = self.registers[x] & 1;
                 self.registers[x] >>= 1;
             }
-            (0x8, _, _, 0x7) => {
+            Instruction::SubNegated(x, y) => {
                 let (res, underflow) = self.registers[y].overflowing_sub(self.registers[x]);
                 self.registers[x] = res;
                 if underflow {
@@ -367,136 +850,311 @@ impl Chip8 {
                     self.registers[15] = 1;
                 }
             }
-            (0x8, _, _, 0xE) => {
+            Instruction::ShiftLeft(x, _) => {
                 self.registers[15] = self.registers[x] >> 7;
                 self.registers[x] <<= 1;
             }
-            (0x9, _, _, 0x0) => {
+            Instruction::SkipIfNotEqual(x, y) => {
                 if self.registers[x] != self.registers[y] {
                     self.pc += 2;
                 }
             }
-            (0xA, _, _, _) => self.index = nnn,
-            (0xB, _, _, _) => self.pc = u16::from(self.registers[0]) + nnn,
-            (0xC, _, _, _) => {
-                self.registers[x] = generate_u8() & kk;
+            Instruction::LoadIndex(nnn) => self.index = nnn,
+            Instruction::JumpOffset(nnn) => self.pc = u16::from(self.registers[0]) + nnn,
+            Instruction::Random(x, kk) => {
+                self.registers[x] = self.random.next() as u8 & kk;
             }
-            (0xD, _, _, _) => {
-                self.registers[15] = 0;
-
-                let (rows, cols) = {
-                    if self.screen.get_mode() == ScreenMode::Super && n == 0 {
-                        (16, 16)
-                    } else {
-                        (n, 8)
-                    }
+            Instruction::Draw(x, y, n) => {
+                let is_super_mode = self.screen.get_mode() == ScreenMode::Super;
+                let (rows, cols) = match (n, is_super_mode, self.quirks.large_sprite) {
+                    (0, true, _) | (0, false, LargeSprite::Square) => (16, 16),
+                    (0, false, LargeSprite::Tall) => (16, 8),
+                    _ => (n, 8),
                 };
 
-                for row in 0..rows {
-                    for col in 0..cols {
-                        let col_index = cols / 8;
-                        let bitcode = self.memory[self.index as usize + row * col_index + col / 8];
-                        if bitcode & (0x80 >> (col % 8)) == 0 {
-                            continue;
-                        }
+                let height = self.screen.height();
+                let width = self.screen.width();
+                let origin_row = self.registers[y] as usize % height;
+                let origin_col = self.registers[x] as usize % width;
+                self.check_memory(self.index as usize, rows * cols / 8)?;
+                let should_wrap = self.quirks.draw_mode == DrawMode::Wrap;
 
-                        let mut row = self.registers[y] as usize + row;
-                        let mut col = self.registers[x] as usize + col;
-                        match self.draw_mode {
-                            DrawMode::Clip => {
-                                if row > self.screen.height() || col > self.screen.width() {
-                                    continue;
-                                }
-                            }
-                            DrawMode::Wrap => {
-                                row %= self.screen.height();
-                                col %= self.screen.width();
+                let should_count_rows = is_super_mode && self.quirks.count_collided_rows;
+                let mut collided_rows = 0;
+                for row in 0..rows {
+                    let mut screen_row = origin_row + row;
+                    if screen_row >= height {
+                        if !should_wrap {
+                            // SCHIP 1.1 counts the rows clipped by the bottom of the screen as
+                            // collisions, but they are not collisions otherwise.
+                            if should_count_rows {
+                                collided_rows += 1;
                             }
+                            continue;
                         }
+                        screen_row %= height;
+                    }
 
-                        if self.screen.get_pixel(row, col) {
-                            self.registers[15] = 1;
-                        }
-                        self.screen.flip_pixel(row, col);
+                    let offset = self.index as usize + row * cols / 8;
+                    let bits = if cols == 16 {
+                        u16::from_be_bytes([self.memory[offset], self.memory[offset + 1]])
+                    } else {
+                        u16::from(self.memory[offset])
+                    };
+                    if self
+                        .screen
+                        .draw_sprite_row(screen_row, origin_col, bits, cols, should_wrap)
+                    {
+                        collided_rows += 1;
                     }
                 }
 
+                self.registers[15] = if should_count_rows {
+                    collided_rows
+                } else {
+                    (collided_rows > 0) as u8
+                };
                 self.should_draw = true;
             }
-            (0xE, _, 0x9, 0xE) => {
+            Instruction::SkipIfKey(x) => {
                 if self.keypad.is_pressed(self.registers[x] as usize) {
                     self.pc += 2;
                 }
             }
-            (0xE, _, 0xA, 0x1) => {
+            Instruction::SkipIfNotKey(x) => {
                 if !self.keypad.is_pressed(self.registers[x] as usize) {
                     self.pc += 2;
                 }
             }
-            (0xF, _, 0x0, 0x7) => self.registers[x] = self.delay_timer,
-            (0xF, _, 0x0, 0xA) => {
+            Instruction::LoadDelayTimer(x) => self.registers[x] = self.delay_timer,
+            Instruction::WaitForKey(x) => {
                 self.pc -= 2;
-                if let Some(index) = self.keypad.poll_key() {
-                    self.registers[x] = index as u8;
+                while let Some(event) = self.keypad.poll_event() {
+                    let key = match event {
+                        KeyEvent::Pressed(key) if !self.quirks.wait_for_key_release => key,
+                        KeyEvent::Pressed(key) => {
+                            self.waiting_keys |= 1 << key;
+                            continue;
+                        }
+                        KeyEvent::Released(key) if self.waiting_keys & (1 << key) != 0 => key,
+                        KeyEvent::Released(_) => continue,
+                    };
+                    self.registers[x] = key as u8;
+                    self.waiting_keys = 0;
                     self.pc += 2;
+                    break;
                 }
             }
-            (0xF, _, 0x1, 0x5) => self.delay_timer = self.registers[x],
-            (0xF, _, 0x1, 0x8) => self.sound_timer = self.registers[x],
-            (0xF, _, 0x1, 0xE) => self.index += u16::from(self.registers[x]),
-            (0xF, _, 0x2, 0x9) => self.index = u16::from(self.registers[x]) * 5,
-            (0xF, _, 0x3, 0x0) => self.index = u16::from(self.registers[x]) * 10 + 80,
-            (0xF, _, 0x3, 0x3) => {
+            Instruction::SetDelayTimer(x) => self.delay_timer = self.registers[x],
+            Instruction::SetSoundTimer(x) => self.sound_timer = self.registers[x],
+            Instruction::AddIndex(x) => {
+                self.index = self.index.wrapping_add(u16::from(self.registers[x]))
+            }
+            Instruction::LoadFont(x) => self.index = u16::from(self.registers[x]) * 5,
+            Instruction::LoadLargeFont(x) => self.index = u16::from(self.registers[x]) * 10 + 80,
+            Instruction::StoreBcd(x) => {
+                self.check_memory(self.index as usize, 3)?;
                 self.memory[self.index as usize] = self.registers[x] / 100;
                 self.memory[self.index as usize + 1] = ((self.registers[x]) / 10) % 10;
                 self.memory[self.index as usize + 2] = self.registers[x] % 10;
             }
-            (0xF, _, 0x5, 0x5) => {
+            Instruction::StoreRegisters(x) => {
+                self.check_memory(self.index as usize, x + 1)?;
                 for i in 0..=x {
                     self.memory[self.index as usize + i] = self.registers[i];
                 }
             }
-            (0xF, _, 0x6, 0x5) => {
+            Instruction::LoadRegisters(x) => {
+                self.check_memory(self.index as usize, x + 1)?;
                 for i in 0..=x {
                     self.registers[i] = self.memory[self.index as usize + i];
                 }
             }
-            (0xF, _, 0x7, 0x5) => {
+            Instruction::StoreFlags(x) => {
                 self.super_mode_rpl_flags[..=x].clone_from_slice(&self.registers[..=x])
             }
-            (0xF, _, 0x8, 0x5) => {
+            Instruction::LoadFlags(x) => {
                 self.registers[..=x].clone_from_slice(&self.super_mode_rpl_flags[..=x])
             }
-            _ => panic!("Unrecognized opcode: {}", opcode),
         }
+
+        Ok(())
     }
 
-    /// Returns a pointer to a byte array that represents the screen. The screen will have
-    /// `screen_width * screen_height / 8` bytes in row-major order. Each byte represents 8 bits in
-    /// little-endian. `1` represents that the pixel is black, while `0` represents that the pixel
-    /// is white.
+    /// Returns a copy of the screen as a byte array. Regardless of the screen mode, the screen
+    /// will have `SCREEN_BUFFER_WIDTH * SCREEN_BUFFER_HEIGHT / 8` bytes in row-major order, where
+    /// `SCREEN_BUFFER_WIDTH` is 128 and `SCREEN_BUFFER_HEIGHT` is 64. In standard mode, each pixel
+    /// is represented as a 2x2 block. Each byte represents 8 bits in little-endian. `1` represents
+    /// that the pixel is black, while `0` represents that the pixel is white.
+    pub fn screen_buffer(&self) -> Vec<u8> {
+        self.screen.buffer()
+    }
+
+    /// Returns the width in pixels of the buffer returned by `screen_buffer`.
+    pub fn screen_buffer_width(&self) -> usize {
+        SCREEN_BUFFER_WIDTH
+    }
+
+    /// Returns the height in pixels of the buffer returned by `screen_buffer`.
+    pub fn screen_buffer_height(&self) -> usize {
+        SCREEN_BUFFER_HEIGHT
+    }
+
+    /// Returns a pointer to the same byte array as `screen_buffer` without copying it. The pointer
+    /// is invalidated when the WebAssembly memory grows, and the byte order is only correct on
+    /// little-endian targets.
+    #[cfg(feature = "pointer-api")]
     pub fn screen(&self) -> *const u8 {
         self.screen.pixels()
     }
 
-    /// Returns the width of the screen in pixels.
+    /// Returns the current screen mode.
+    pub fn screen_mode(&self) -> ScreenMode {
+        self.screen.get_mode()
+    }
+
+    /// Returns the width of the screen in pixels of the current screen mode.
     pub fn screen_width(&self) -> usize {
         self.screen.width()
     }
 
-    /// Returns the height of the screen in pixels.
+    /// Returns the height of the screen in pixels of the current screen mode.
     pub fn screen_height(&self) -> usize {
         self.screen.height()
     }
 
-    /// Sets the state of a key to be pressed. `index` is the index of the key in row-major order.
-    pub fn press_key(&mut self, index: usize) {
-        self.keypad.press_key(index);
+    /// Returns the quirks used by the emulator.
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Sets the quirks used by the emulator. The quirks are kept when a new rom is loaded.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Seeds the random numbers of `CXNN`, so that runs with the same seed and input are
+    /// reproducible. `new` uses a random seed, and the seed is kept when a new rom is loaded.
+    pub fn set_seed(&mut self, seed: u32) {
+        self.random = XorShift::new(seed);
+    }
+
+    /// Sets the state of the hex key `key` to be pressed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is not a hex key.
+    #[deprecated(note = "Use `press_hex_key` instead.")]
+    pub fn press_key(&mut self, key: usize) {
+        assert!(key < 16, "Invalid hex key: {}", key);
+        self.keypad.press_key(key);
+    }
+
+    /// Sets the state of the hex key `key` to be released.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is not a hex key.
+    #[deprecated(note = "Use `release_hex_key` instead.")]
+    pub fn release_key(&mut self, key: usize) {
+        assert!(key < 16, "Invalid hex key: {}", key);
+        self.keypad.release_key(key);
+    }
+
+    /// Sets the state of the hex key `key` to be pressed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is not a hex key.
+    pub fn press_hex_key(&mut self, key: u8) {
+        assert!(key < 16, "Invalid hex key: {}", key);
+        self.keypad.press_key(key as usize);
+    }
+
+    /// Sets the state of the hex key `key` to be released.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is not a hex key.
+    pub fn release_hex_key(&mut self, key: u8) {
+        assert!(key < 16, "Invalid hex key: {}", key);
+        self.keypad.release_key(key as usize);
+    }
+
+    /// Returns the key map used to translate host keys. Defaults to `KeyMap::qwerty`.
+    pub fn key_map(&self) -> KeyMap {
+        self.key_map.clone()
+    }
+
+    /// Sets the key map used to translate host keys. The key map is kept when a new rom is
+    /// loaded.
+    pub fn set_key_map(&mut self, key_map: KeyMap) {
+        self.key_map = key_map;
+    }
+
+    /// Sets the state of the hex key bound to `host_key` in the key map to be pressed. Returns
+    /// `false` if `host_key` is not bound.
+    pub fn press_host_key(&mut self, host_key: &str) -> bool {
+        match self.key_map.get(host_key) {
+            Some(key) => {
+                self.press_hex_key(key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets the state of the hex key bound to `host_key` in the key map to be released. Returns
+    /// `false` if `host_key` is not bound.
+    pub fn release_host_key(&mut self, host_key: &str) -> bool {
+        match self.key_map.get(host_key) {
+            Some(key) => {
+                self.release_hex_key(key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the controller profile used to translate gamepad buttons. Defaults to
+    /// `ControllerProfile::standard`.
+    pub fn controller_profile(&self) -> ControllerProfile {
+        self.controller_profile
     }
 
-    /// Sets the state of a key to be released. `index` is the index of the key in row-major order.
-    pub fn release_key(&mut self, index: usize) {
-        self.keypad.release_key(index);
+    /// Sets the controller profile used to translate gamepad buttons. The profile is kept when a
+    /// new rom is loaded, so it should be looked up with `rom_hash` after loading a rom.
+    pub fn set_controller_profile(&mut self, controller_profile: ControllerProfile) {
+        self.controller_profile = controller_profile;
+    }
+
+    /// Sets the state of the hex key bound to `button` in the controller profile to be pressed.
+    /// Returns `false` if `button` is not bound.
+    pub fn press_button(&mut self, button: Button) -> bool {
+        match self.controller_profile.get(button) {
+            Some(key) => {
+                self.press_hex_key(key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets the state of the hex key bound to `button` in the controller profile to be released.
+    /// Returns `false` if `button` is not bound.
+    pub fn release_button(&mut self, button: Button) -> bool {
+        match self.controller_profile.get(button) {
+            Some(key) => {
+                self.release_hex_key(key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the SHA-1 hash of the loaded rom as a lowercase hex string.
+    pub fn rom_hash(&self) -> String {
+        sha1::to_hex(&self.rom_hash)
     }
 
     /// Returns `true` if the screen has been updated and should be redrawn.
@@ -504,6 +1162,14 @@ impl Chip8 {
         self.should_draw
     }
 
+    /// Returns the regions of the screen that have changed since the last call to this method and
+    /// resets them. Consecutive changed rows are merged into a single region, so frontends can
+    /// redraw only the areas that actually changed. After a change of screen mode or loading a
+    /// rom, the entire screen is reported as dirty.
+    pub fn take_dirty_regions(&mut self) -> Vec<DirtyRegion> {
+        self.screen.take_dirty_regions()
+    }
+
     /// Returns `true` if the a beep should be made.
     pub fn should_beep(&self) -> bool {
         self.should_beep
@@ -519,10 +1185,112 @@ impl Chip8 {
         self.index
     }
 
-    /// Returns a pointer to a byte array that represents the 16 data registers.
+    /// Returns a copy of the 16 data registers.
+    pub fn data_registers(&self) -> Vec<u8> {
+        self.registers.to_vec()
+    }
+
+    /// Returns a pointer to a byte array that represents the 16 data registers. The pointer is
+    /// invalidated when the WebAssembly memory grows.
+    #[cfg(feature = "pointer-api")]
     pub fn registers(&self) -> *const u8 {
         self.registers.as_ptr()
     }
+
+    /// Returns a copy of the 16 entries of the stack.
+    pub fn stack(&self) -> Vec<u16> {
+        self.stack.to_vec()
+    }
+
+    /// Returns the value of the stack pointer, which is the number of entries on the stack.
+    pub fn stack_pointer(&self) -> u16 {
+        self.sp
+    }
+
+    /// Returns the value of the delay timer.
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    /// Returns the value of the sound timer.
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// Returns a copy of `length` bytes of memory starting at `start`. The range is truncated to
+    /// the 4096 bytes of memory.
+    pub fn memory_range(&self, start: usize, length: usize) -> Vec<u8> {
+        let start = start.min(MEMORY_SIZE);
+        let end = start.saturating_add(length).min(MEMORY_SIZE);
+        self.memory[start..end].to_vec()
+    }
+}
+
+impl Chip8 {
+    /// Returns the 4096 bytes of memory.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Writes `data` to memory starting at `address`. Returns an error and leaves memory
+    /// unmodified if `data` does not fit in memory.
+    pub fn write_memory(&mut self, address: usize, data: &[u8]) -> Result<(), Error> {
+        self.check_memory(address, data.len())?;
+        self.memory[address..address + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Loads a rom like `load_rom`, but returns an error instead of panicking if the rom does not
+    /// fit in memory.
+    pub fn try_load_rom(&mut self, rom: &[u8], should_wrap: bool) -> Result<(), Error> {
+        if rom.len() > MEMORY_SIZE - PROGRAM_START as usize {
+            return Err(Error::RomTooLarge(rom.len()));
+        }
+
+        self.initialize();
+        self.quirks.draw_mode = {
+            if should_wrap {
+                DrawMode::Wrap
+            } else {
+                DrawMode::Clip
+            }
+        };
+
+        for (i, byte) in rom.iter().enumerate() {
+            self.memory[i + PROGRAM_START as usize] = *byte;
+        }
+        self.rom_hash = sha1::digest(rom);
+        Ok(())
+    }
+
+    /// Runs one fetch-decode-execute cycle like `execute_cycle`, but returns an error instead of
+    /// panicking if the instruction is invalid, overflows or underflows the stack, or accesses
+    /// memory out of bounds. When an error is returned, the program counter points at the
+    /// instruction that caused it and no other registers or memory are modified.
+    pub fn step(&mut self) -> Result<(), Error> {
+        if !self.is_running {
+            self.keypad.clear_events();
+            return Ok(());
+        }
+
+        let pc = self.pc;
+        let opcode = self.fetch_opcode()?;
+        self.pc += 2;
+
+        let ret = self.process_opcode(opcode);
+        if ret.is_err() {
+            self.pc = pc;
+        }
+        self.keypad.clear_events();
+        ret
+    }
+
+    /// Runs `cycles` cycles and then decrements the timers once, which emulates a single frame.
+    /// Stops at the first cycle that returns an error. Afterwards, `should_draw` returns `true` if
+    /// any of the cycles updated the screen.
+    pub fn run_frame(&mut self, cycles: usize) -> Result<(), Error> {
+        self.run_frame_observed(cycles, &mut ())
+    }
 }
 
 impl Default for Chip8 {
@@ -537,7 +1305,521 @@ mod tests {
     use std::collections::hash_map::DefaultHasher;
     use std::fs;
     use std::hash::Hasher;
-    use std::slice;
+
+    const SPRITE_START: u16 = 0x300;
+
+    // Loads a rom that runs `opcodes` and then loops forever, with `sprite` at `SPRITE_START`.
+    fn run(quirks: Quirks, should_wrap: bool, opcodes: &[u16], sprite: &[u8]) -> Chip8 {
+        let mut rom = Vec::new();
+        for opcode in opcodes {
+            rom.extend_from_slice(&opcode.to_be_bytes());
+        }
+        let end = PROGRAM_START + rom.len() as u16;
+        rom.extend_from_slice(&(0x1000 | end).to_be_bytes());
+        rom.resize((SPRITE_START - PROGRAM_START) as usize, 0);
+        rom.extend_from_slice(sprite);
+
+        let mut chip_8 = Chip8::new();
+        chip_8.set_quirks(quirks);
+        chip_8.load_rom(&rom, should_wrap);
+        for _ in 0..opcodes.len() {
+            chip_8.execute_cycle();
+        }
+        chip_8
+    }
+
+    // Draws a single pixel sprite at (8, 4), and then runs `opcodes`.
+    fn run_with_pixel(quirks: Quirks, opcodes: &[u16]) -> Chip8 {
+        let mut all_opcodes = vec![0x6008, 0x6104, 0xA000 | SPRITE_START, 0xD011];
+        all_opcodes.extend_from_slice(opcodes);
+        run(quirks, false, &all_opcodes, &[0x80])
+    }
+
+    // Returns the set pixels of the screen buffer as (row, col) pairs.
+    fn lit_pixels(chip_8: &Chip8) -> Vec<(usize, usize)> {
+        let screen = chip_8.screen_buffer();
+        let mut pixels = Vec::new();
+        for row in 0..SCREEN_BUFFER_HEIGHT {
+            for col in 0..SCREEN_BUFFER_WIDTH {
+                let index = row * SCREEN_BUFFER_WIDTH + col;
+                if screen[index / 8] & (1 << (index % 8)) != 0 {
+                    pixels.push((row, col));
+                }
+            }
+        }
+        pixels
+    }
+
+    // Returns the set pixels of the screen as (row, col) pairs in pixels of the current mode.
+    fn lit_screen_pixels(chip_8: &Chip8) -> Vec<(usize, usize)> {
+        let scale = SCREEN_BUFFER_WIDTH / chip_8.screen_width();
+        let mut pixels: Vec<(usize, usize)> = lit_pixels(chip_8)
+            .into_iter()
+            .map(|(row, col)| (row / scale, col / scale))
+            .collect();
+        pixels.sort();
+        pixels.dedup();
+        pixels
+    }
+
+    // Draws `sprite` at (`col`, `row`) with `DXYN`, where `N` is `sprite_rows`.
+    fn draw(
+        quirks: Quirks,
+        should_wrap: bool,
+        is_super_mode: bool,
+        (col, row): (u8, u8),
+        sprite_rows: u16,
+        sprite: &[u8],
+    ) -> Chip8 {
+        let mut opcodes = Vec::new();
+        if is_super_mode {
+            opcodes.push(0x00FF);
+        }
+        opcodes.extend_from_slice(&[
+            0x6000 | u16::from(col),
+            0x6100 | u16::from(row),
+            0xA000 | SPRITE_START,
+            0xD010 | sprite_rows,
+        ]);
+        run(quirks, should_wrap, &opcodes, sprite)
+    }
+
+    fn block(row: usize, col: usize) -> Vec<(usize, usize)> {
+        vec![
+            (row, col),
+            (row, col + 1),
+            (row + 1, col),
+            (row + 1, col + 1),
+        ]
+    }
+
+    #[test]
+    fn test_standard_mode_layout() {
+        let chip_8 = run_with_pixel(Quirks::new(), &[]);
+        assert_eq!(lit_pixels(&chip_8), block(8, 16));
+    }
+
+    #[test]
+    fn test_standard_mode_scroll() {
+        let chip_8 = run_with_pixel(Quirks::new(), &[0x00C2]);
+        assert_eq!(lit_pixels(&chip_8), block(12, 16));
+
+        let chip_8 = run_with_pixel(Quirks::new(), &[0x00FB]);
+        assert_eq!(lit_pixels(&chip_8), block(8, 24));
+
+        let chip_8 = run_with_pixel(Quirks::new(), &[0x00FC]);
+        assert_eq!(lit_pixels(&chip_8), block(8, 8));
+    }
+
+    #[test]
+    fn test_super_mode_scroll() {
+        let chip_8 = run_with_pixel(Quirks::new(), &[0x00FF, 0x00E0, 0xD011]);
+        assert_eq!(lit_pixels(&chip_8), vec![(4, 8)]);
+
+        let chip_8 = run_with_pixel(Quirks::new(), &[0x00FF, 0x00E0, 0xD011, 0x00C2]);
+        assert_eq!(lit_pixels(&chip_8), vec![(6, 8)]);
+
+        let chip_8 = run_with_pixel(Quirks::new(), &[0x00FF, 0x00E0, 0xD011, 0x00FB]);
+        assert_eq!(lit_pixels(&chip_8), vec![(4, 12)]);
+
+        let chip_8 = run_with_pixel(Quirks::new(), &[0x00FF, 0x00E0, 0xD011, 0x00FC]);
+        assert_eq!(lit_pixels(&chip_8), vec![(4, 4)]);
+    }
+
+    #[test]
+    fn test_scroll_off_screen() {
+        let chip_8 = run_with_pixel(Quirks::new(), &[0x00CF, 0x00CF, 0x00CF]);
+        assert_eq!(lit_pixels(&chip_8), vec![]);
+
+        let chip_8 = run_with_pixel(Quirks::new(), &[0x00FC, 0x00FC, 0x00FC]);
+        assert_eq!(lit_pixels(&chip_8), vec![]);
+    }
+
+    #[test]
+    fn test_legacy_standard_mode_scroll() {
+        let quirks = Quirks::schip_legacy();
+        let chip_8 = run_with_pixel(quirks, &[0x00C2]);
+        assert_eq!(lit_pixels(&chip_8), block(10, 16));
+
+        let chip_8 = run_with_pixel(quirks, &[0x00C1]);
+        assert_eq!(
+            lit_pixels(&chip_8),
+            vec![(9, 16), (9, 17), (10, 16), (10, 17)]
+        );
+
+        let chip_8 = run_with_pixel(quirks, &[0x00FB]);
+        assert_eq!(lit_pixels(&chip_8), block(8, 20));
+
+        let chip_8 = run_with_pixel(quirks, &[0x00FC]);
+        assert_eq!(lit_pixels(&chip_8), block(8, 12));
+    }
+
+    #[test]
+    fn test_count_collided_rows() {
+        let sprite = [0x80, 0x00, 0x80];
+        let opcodes = [
+            0x00FF,
+            0x6000,
+            0x6100,
+            0xA000 | SPRITE_START,
+            0xD013,
+            0xD013,
+        ];
+
+        let chip_8 = run(Quirks::schip_legacy(), false, &opcodes, &sprite);
+        assert_eq!(chip_8.registers[15], 2);
+
+        let chip_8 = run(Quirks::schip_modern(), false, &opcodes, &sprite);
+        assert_eq!(chip_8.registers[15], 1);
+
+        // Only the rows that are clipped by the bottom of the screen are counted.
+        let opcodes = [0x00FF, 0x6000, 0x613E, 0xA000 | SPRITE_START, 0xD013];
+        let chip_8 = run(Quirks::schip_legacy(), false, &opcodes, &sprite);
+        assert_eq!(chip_8.registers[15], 1);
+
+        let chip_8 = run(Quirks::schip_legacy(), true, &opcodes, &sprite);
+        assert_eq!(chip_8.registers[15], 0);
+
+        // Standard mode always sets `VF` to `1` on collision.
+        let opcodes = [0x6000, 0x6100, 0xA000 | SPRITE_START, 0xD013, 0xD013];
+        let chip_8 = run(Quirks::schip_legacy(), false, &opcodes, &sprite);
+        assert_eq!(chip_8.registers[15], 1);
+    }
+
+    #[test]
+    fn test_draw_inside_edges() {
+        for &is_super_mode in &[false, true] {
+            for &should_wrap in &[false, true] {
+                let (height, width) = if is_super_mode { (64, 128) } else { (32, 64) };
+                let bottom_right = ((width - 1) as u8, (height - 1) as u8);
+                let chip_8 = draw(
+                    Quirks::new(),
+                    should_wrap,
+                    is_super_mode,
+                    (0, 0),
+                    1,
+                    &[0x80],
+                );
+                assert_eq!(lit_screen_pixels(&chip_8), vec![(0, 0)]);
+                assert_eq!(chip_8.registers[15], 0);
+
+                let chip_8 = draw(
+                    Quirks::new(),
+                    should_wrap,
+                    is_super_mode,
+                    bottom_right,
+                    1,
+                    &[0x80],
+                );
+                assert_eq!(lit_screen_pixels(&chip_8), vec![(height - 1, width - 1)]);
+                assert_eq!(chip_8.registers[15], 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_draw_right_edge() {
+        let chip_8 = draw(Quirks::new(), false, false, (62, 0), 1, &[0xFF]);
+        assert_eq!(lit_screen_pixels(&chip_8), vec![(0, 62), (0, 63)]);
+
+        let chip_8 = draw(Quirks::new(), true, false, (62, 0), 1, &[0xFF]);
+        let mut expected: Vec<_> = (0..6).map(|col| (0, col)).collect();
+        expected.extend_from_slice(&[(0, 62), (0, 63)]);
+        assert_eq!(lit_screen_pixels(&chip_8), expected);
+
+        let chip_8 = draw(Quirks::new(), false, true, (126, 0), 1, &[0xC1]);
+        assert_eq!(lit_screen_pixels(&chip_8), vec![(0, 126), (0, 127)]);
+
+        let chip_8 = draw(Quirks::new(), true, true, (126, 0), 1, &[0xC1]);
+        assert_eq!(lit_screen_pixels(&chip_8), vec![(0, 5), (0, 126), (0, 127)]);
+    }
+
+    #[test]
+    fn test_draw_bottom_edge() {
+        let sprite = [0x80, 0x80, 0x80];
+        let chip_8 = draw(Quirks::new(), false, false, (0, 30), 3, &sprite);
+        assert_eq!(lit_screen_pixels(&chip_8), vec![(30, 0), (31, 0)]);
+        assert_eq!(chip_8.registers[15], 0);
+
+        let chip_8 = draw(Quirks::new(), true, false, (0, 30), 3, &sprite);
+        assert_eq!(lit_screen_pixels(&chip_8), vec![(0, 0), (30, 0), (31, 0)]);
+        assert_eq!(chip_8.registers[15], 0);
+
+        let chip_8 = draw(Quirks::new(), false, true, (0, 62), 3, &sprite);
+        assert_eq!(lit_screen_pixels(&chip_8), vec![(62, 0), (63, 0)]);
+        assert_eq!(chip_8.registers[15], 0);
+
+        let chip_8 = draw(Quirks::new(), true, true, (0, 62), 3, &sprite);
+        assert_eq!(lit_screen_pixels(&chip_8), vec![(0, 0), (62, 0), (63, 0)]);
+        assert_eq!(chip_8.registers[15], 0);
+
+        // Clipped rows are only counted by SCHIP 1.1 in super mode.
+        let chip_8 = draw(Quirks::schip_modern(), false, true, (0, 62), 3, &sprite);
+        assert_eq!(chip_8.registers[15], 0);
+
+        let chip_8 = draw(Quirks::schip_legacy(), false, false, (0, 30), 3, &sprite);
+        assert_eq!(chip_8.registers[15], 0);
+
+        let chip_8 = draw(Quirks::schip_legacy(), false, true, (0, 62), 3, &sprite);
+        assert_eq!(chip_8.registers[15], 1);
+    }
+
+    #[test]
+    fn test_draw_corner() {
+        let sprite = [0xC0, 0xC0];
+        let chip_8 = draw(Quirks::new(), false, false, (63, 31), 2, &sprite);
+        assert_eq!(lit_screen_pixels(&chip_8), vec![(31, 63)]);
+        assert_eq!(chip_8.registers[15], 0);
+
+        let chip_8 = draw(Quirks::new(), true, false, (63, 31), 2, &sprite);
+        assert_eq!(
+            lit_screen_pixels(&chip_8),
+            vec![(0, 0), (0, 63), (31, 0), (31, 63)],
+        );
+        assert_eq!(chip_8.registers[15], 0);
+    }
+
+    #[test]
+    fn test_draw_wraps_origin() {
+        for &should_wrap in &[false, true] {
+            // The left and top edges are reached by wrapping the starting coordinate.
+            let chip_8 = draw(Quirks::new(), should_wrap, false, (64, 32), 1, &[0x80]);
+            assert_eq!(lit_screen_pixels(&chip_8), vec![(0, 0)]);
+
+            let chip_8 = draw(Quirks::new(), should_wrap, false, (255, 255), 1, &[0x80]);
+            assert_eq!(lit_screen_pixels(&chip_8), vec![(31, 63)]);
+
+            let chip_8 = draw(Quirks::new(), should_wrap, true, (130, 65), 1, &[0x80]);
+            assert_eq!(lit_screen_pixels(&chip_8), vec![(1, 2)]);
+        }
+
+        // Only the pixels past the edge are clipped after wrapping the starting coordinate.
+        let chip_8 = draw(Quirks::new(), false, false, (126, 0), 1, &[0xF0]);
+        assert_eq!(lit_screen_pixels(&chip_8), vec![(0, 62), (0, 63)]);
+    }
+
+    #[test]
+    fn test_draw_large_sprite() {
+        let sprite = [0xFF; 32];
+        let mut quirks = Quirks::new();
+
+        quirks.large_sprite = LargeSprite::Empty;
+        let chip_8 = draw(quirks, false, false, (0, 0), 0, &sprite);
+        assert_eq!(lit_screen_pixels(&chip_8), vec![]);
+
+        quirks.large_sprite = LargeSprite::Tall;
+        let chip_8 = draw(quirks, false, false, (0, 0), 0, &sprite);
+        assert_eq!(lit_screen_pixels(&chip_8).len(), 8 * 16);
+        assert!(lit_screen_pixels(&chip_8).iter().all(|&(_, col)| col < 8));
+
+        quirks.large_sprite = LargeSprite::Square;
+        let chip_8 = draw(quirks, false, false, (0, 0), 0, &sprite);
+        assert_eq!(lit_screen_pixels(&chip_8).len(), 16 * 16);
+
+        // The large sprite is clipped at the bottom right corner in standard mode.
+        let chip_8 = draw(quirks, false, false, (56, 24), 0, &sprite);
+        assert_eq!(lit_screen_pixels(&chip_8).len(), 8 * 8);
+
+        let chip_8 = draw(quirks, true, false, (56, 24), 0, &sprite);
+        assert_eq!(lit_screen_pixels(&chip_8).len(), 16 * 16);
+        assert!(lit_screen_pixels(&chip_8).contains(&(0, 0)));
+
+        // Super mode always draws a 16x16 sprite.
+        quirks.large_sprite = LargeSprite::Empty;
+        let chip_8 = draw(quirks, false, true, (120, 56), 0, &sprite);
+        assert_eq!(lit_screen_pixels(&chip_8).len(), 8 * 8);
+    }
+
+    // Loads a rom that waits for a key with `F30A` after one cycle, with key 5 already held.
+    fn wait_for_key(quirks: Quirks) -> Chip8 {
+        let mut chip_8 = Chip8::new();
+        chip_8.set_quirks(quirks);
+        chip_8.load_rom(&[0x60, 0x00, 0xF3, 0x0A], true);
+        chip_8.press_hex_key(5);
+        chip_8.execute_cycle();
+        chip_8.execute_cycle();
+        chip_8
+    }
+
+    #[test]
+    fn test_wait_for_key_press() {
+        let mut chip_8 = wait_for_key(Quirks::new());
+        assert_eq!(chip_8.program_counter(), 0x202);
+
+        chip_8.release_hex_key(5);
+        chip_8.execute_cycle();
+        assert_eq!(chip_8.program_counter(), 0x202);
+
+        chip_8.press_hex_key(7);
+        chip_8.execute_cycle();
+        assert_eq!(chip_8.program_counter(), 0x204);
+        assert_eq!(chip_8.registers[3], 7);
+    }
+
+    #[test]
+    fn test_wait_for_key_release() {
+        let mut quirks = Quirks::new();
+        quirks.wait_for_key_release = true;
+
+        let mut chip_8 = wait_for_key(quirks);
+        chip_8.release_hex_key(5);
+        chip_8.execute_cycle();
+        assert_eq!(chip_8.program_counter(), 0x202);
+
+        chip_8.press_hex_key(7);
+        chip_8.execute_cycle();
+        assert_eq!(chip_8.program_counter(), 0x202);
+
+        chip_8.release_hex_key(7);
+        chip_8.execute_cycle();
+        assert_eq!(chip_8.program_counter(), 0x204);
+        assert_eq!(chip_8.registers[3], 7);
+
+        // A key that is pressed and released between cycles also completes `FX0A`.
+        let mut chip_8 = wait_for_key(quirks);
+        chip_8.press_hex_key(9);
+        chip_8.release_hex_key(9);
+        chip_8.execute_cycle();
+        assert_eq!(chip_8.program_counter(), 0x204);
+        assert_eq!(chip_8.registers[3], 9);
+    }
+
+    #[test]
+    fn test_host_keys() {
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&[0xF3, 0x0A], true);
+        assert!(!chip_8.press_host_key("Escape"));
+        assert!(chip_8.press_host_key("x"));
+        assert!(chip_8.keypad.is_pressed(0x0));
+        chip_8.execute_cycle();
+        assert_eq!(chip_8.registers[3], 0x0);
+
+        let mut key_map = KeyMap::azerty();
+        key_map.bind("ArrowUp", 0x5);
+        chip_8.set_key_map(key_map);
+        assert!(chip_8.press_host_key("ArrowUp"));
+        assert!(chip_8.keypad.is_pressed(0x5));
+        assert!(chip_8.release_host_key("ArrowUp"));
+        assert!(!chip_8.keypad.is_pressed(0x5));
+
+        chip_8.press_hex_key(0xC);
+        assert!(chip_8.keypad.is_pressed(0xC));
+    }
+
+    #[test]
+    fn test_controller_profile() {
+        let rom = [0x12, 0x00];
+        let mut profiles = ControllerProfiles::new();
+        let mut profile = ControllerProfile::new();
+        profile.bind(Button::A, 0xB);
+        profiles.insert("4a8df55e2fb8c8e2eb5ee1d4e5a4ab7bf27d1c9b", profile);
+
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&rom, true);
+        assert_eq!(chip_8.rom_hash(), sha1::hex_digest(&rom));
+        chip_8.set_controller_profile(profiles.get(&chip_8.rom_hash()));
+        assert!(chip_8.press_button(Button::Up));
+        assert!(chip_8.keypad.is_pressed(0x5));
+        assert!(chip_8.release_button(Button::Up));
+
+        profiles.insert(&chip_8.rom_hash(), profile);
+        chip_8.set_controller_profile(profiles.get(&chip_8.rom_hash()));
+        assert!(!chip_8.press_button(Button::Up));
+        assert!(chip_8.press_button(Button::A));
+        assert!(chip_8.keypad.is_pressed(0xB));
+    }
+
+    #[test]
+    fn test_accessors() {
+        let chip_8 = run(
+            Quirks::new(),
+            true,
+            &[0x6A05, 0x2204, 0x00FF, 0xFA15, 0xFA18],
+            &[],
+        );
+        assert_eq!(chip_8.data_registers()[0xA], 5);
+        assert_eq!(chip_8.stack()[0], 0x204);
+        assert_eq!(chip_8.stack_pointer(), 1);
+        assert_eq!(chip_8.delay_timer(), 5);
+        assert_eq!(chip_8.sound_timer(), 5);
+        assert_eq!(chip_8.screen_mode(), ScreenMode::Super);
+        assert_eq!(chip_8.memory_range(0x200, 4), vec![0x6A, 0x05, 0x22, 0x04]);
+        assert_eq!(chip_8.memory_range(4094, 4).len(), 2);
+        assert_eq!(chip_8.memory_range(5000, 4).len(), 0);
+        assert_eq!(
+            chip_8.screen_buffer().len(),
+            chip_8.screen_buffer_width() * chip_8.screen_buffer_height() / 8,
+        );
+    }
+
+    #[test]
+    fn test_seed() {
+        // `C0FF`, `C10F`, and `C2F0`.
+        let rom = [0xC0, 0xFF, 0xC1, 0x0F, 0xC2, 0xF0];
+        let registers = |seed| {
+            let mut chip_8 = Chip8::new();
+            chip_8.set_seed(seed);
+            chip_8.load_rom(&rom, true);
+            for _ in 0..3 {
+                chip_8.execute_cycle();
+            }
+            chip_8.data_registers()[..3].to_vec()
+        };
+        assert_eq!(registers(1), vec![0x21, 0x01, 0xC0]);
+        assert_eq!(registers(1), registers(1));
+        assert_ne!(registers(1), registers(2));
+    }
+
+    #[test]
+    fn test_mode_switch() {
+        let mut quirks = Quirks::new();
+        quirks.clear_on_mode_switch = false;
+        let chip_8 = run_with_pixel(quirks, &[0x00FF]);
+        assert_eq!(chip_8.screen_width(), SCREEN_BUFFER_WIDTH);
+        assert_eq!(lit_pixels(&chip_8), block(8, 16));
+
+        let chip_8 = run_with_pixel(quirks, &[0x00FF, 0x00FE]);
+        assert_eq!(lit_pixels(&chip_8), block(8, 16));
+
+        quirks.clear_on_mode_switch = true;
+        let chip_8 = run_with_pixel(quirks, &[0x00FF]);
+        assert_eq!(lit_pixels(&chip_8), vec![]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut chip_8 = Chip8::new();
+        let rom = vec![0; MEMORY_SIZE - PROGRAM_START as usize + 1];
+        assert_eq!(
+            chip_8.try_load_rom(&rom, true),
+            Err(Error::RomTooLarge(rom.len())),
+        );
+
+        let step = |rom: &[u8]| {
+            let mut chip_8 = Chip8::new();
+            chip_8.try_load_rom(rom, true).unwrap();
+            let ret = chip_8.step();
+            assert_eq!(chip_8.program_counter(), PROGRAM_START);
+            ret
+        };
+        assert_eq!(step(&[0x00, 0xEE]), Err(Error::StackUnderflow));
+        assert_eq!(step(&[0x50, 0x01]), Err(Error::UnknownOpcode(0x5001)));
+        assert_eq!(step(&[0xFF, 0x75]), Err(Error::UnknownOpcode(0xFF75)));
+
+        let mut chip_8 = Chip8::new();
+        chip_8.try_load_rom(&[0x22, 0x00], true).unwrap();
+        for _ in 0..STACK_SIZE {
+            chip_8.step().unwrap();
+        }
+        assert_eq!(chip_8.step(), Err(Error::StackOverflow));
+
+        let mut chip_8 = Chip8::new();
+        chip_8
+            .try_load_rom(&[0xAF, 0xFF, 0xF1, 0x55], true)
+            .unwrap();
+        chip_8.step().unwrap();
+        assert_eq!(chip_8.run_frame(1), Err(Error::MemoryOutOfBounds(0xFFF)));
+    }
 
     #[test]
     fn test_rom() {
@@ -549,18 +1831,13 @@ mod tests {
             chip_8.execute_cycle();
         }
 
-        let screen = unsafe {
-            slice::from_raw_parts(
-                chip_8.screen(),
-                chip_8.screen_height() * chip_8.screen_width(),
-            )
-        };
+        let screen = chip_8.screen_buffer();
 
         let mut hasher = DefaultHasher::new();
-        for val in screen {
+        for val in screen.iter() {
             hasher.write_u8(*val);
         }
 
-        assert_eq!(hasher.finish(), 0xA309_E966_20E3_20C5);
+        assert_eq!(hasher.finish(), 0x7778_D37F_6BD5_08B8);
     }
 }
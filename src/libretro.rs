@@ -0,0 +1,749 @@
+// A libretro core. Building the crate with the `libretro` feature exports the libretro API from
+// the shared library, so it can be loaded by RetroArch and other libretro frontends. The libretro
+// API is a singleton, so the emulator and the callbacks of the frontend are kept in globals.
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_uint, c_void};
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{Button, Chip8, DrawMode, Error, KeyMap, Palette, Quirks, SCREEN_BUFFER_HEIGHT};
+use crate::{SCREEN_BUFFER_WIDTH, STATE_SIZE};
+
+const API_VERSION: c_uint = 1;
+
+const ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const ENVIRONMENT_SET_INPUT_DESCRIPTORS: c_uint = 11;
+const ENVIRONMENT_GET_VARIABLE: c_uint = 15;
+const ENVIRONMENT_SET_VARIABLES: c_uint = 16;
+const ENVIRONMENT_GET_VARIABLE_UPDATE: c_uint = 17;
+const ENVIRONMENT_GET_LOG_INTERFACE: c_uint = 27;
+const ENVIRONMENT_SET_CONTROLLER_INFO: c_uint = 35;
+
+const PIXEL_FORMAT_XRGB8888: c_uint = 1;
+const LOG_ERROR: c_uint = 3;
+const REGION_NTSC: c_uint = 0;
+
+const DEVICE_JOYPAD: c_uint = 1;
+const DEVICE_KEYBOARD: c_uint = 3;
+
+// The ids of the buttons of `DEVICE_JOYPAD`, in the order of `BUTTONS`.
+const JOYPAD_IDS: [c_uint; 10] = [4, 5, 6, 7, 8, 0, 9, 1, 3, 2];
+const BUTTONS: [Button; 10] = [
+    Button::Up,
+    Button::Down,
+    Button::Left,
+    Button::Right,
+    Button::A,
+    Button::B,
+    Button::X,
+    Button::Y,
+    Button::Start,
+    Button::Select,
+];
+
+const KEY_DESCRIPTIONS: [&[u8]; 16] = [
+    b"Key 0\0", b"Key 1\0", b"Key 2\0", b"Key 3\0", b"Key 4\0", b"Key 5\0", b"Key 6\0", b"Key 7\0",
+    b"Key 8\0", b"Key 9\0", b"Key A\0", b"Key B\0", b"Key C\0", b"Key D\0", b"Key E\0", b"Key F\0",
+];
+
+const FRAMES_PER_SECOND: f64 = 60.0;
+const SAMPLE_RATE: usize = 44100;
+const SAMPLES_PER_FRAME: usize = SAMPLE_RATE / FRAMES_PER_SECOND as usize;
+const BEEP_FREQUENCY: usize = 440;
+const BEEP_AMPLITUDE: i16 = 0x1000;
+
+const QUIRKS_KEY: &[u8] = b"chipo_quirks\0";
+const INSTRUCTIONS_PER_FRAME_KEY: &[u8] = b"chipo_instructions_per_frame\0";
+const PALETTE_KEY: &[u8] = b"chipo_palette\0";
+
+type EnvironmentFn = unsafe extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+type VideoRefreshFn =
+    unsafe extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+type AudioSampleFn = unsafe extern "C" fn(left: i16, right: i16);
+type AudioSampleBatchFn = unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+type InputPollFn = unsafe extern "C" fn();
+type InputStateFn =
+    unsafe extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+type LogPrintfFn = unsafe extern "C" fn(level: c_uint, fmt: *const c_char, ...);
+
+#[repr(C)]
+pub struct SystemInfo {
+    library_name: *const c_char,
+    library_version: *const c_char,
+    valid_extensions: *const c_char,
+    need_fullpath: bool,
+    block_extract: bool,
+}
+
+#[repr(C)]
+pub struct GameGeometry {
+    base_width: c_uint,
+    base_height: c_uint,
+    max_width: c_uint,
+    max_height: c_uint,
+    aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct SystemTiming {
+    fps: f64,
+    sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct SystemAvInfo {
+    geometry: GameGeometry,
+    timing: SystemTiming,
+}
+
+#[repr(C)]
+pub struct GameInfo {
+    path: *const c_char,
+    data: *const c_void,
+    size: usize,
+    meta: *const c_char,
+}
+
+#[repr(C)]
+struct Variable {
+    key: *const c_char,
+    value: *const c_char,
+}
+
+#[repr(C)]
+struct ControllerDescription {
+    desc: *const c_char,
+    id: c_uint,
+}
+
+#[repr(C)]
+struct ControllerInfo {
+    types: *const ControllerDescription,
+    num_types: c_uint,
+}
+
+#[repr(C)]
+struct InputDescriptor {
+    port: c_uint,
+    device: c_uint,
+    index: c_uint,
+    id: c_uint,
+    description: *const c_char,
+}
+
+#[repr(C)]
+struct LogCallback {
+    log: Option<LogPrintfFn>,
+}
+
+#[derive(Copy, Clone)]
+struct Callbacks {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+    log: Option<LogPrintfFn>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+    log: None,
+});
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+static DEVICE: AtomicU32 = AtomicU32::new(DEVICE_JOYPAD);
+
+// The frontend aborts on panics across the API, so a poisoned lock is still usable.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+fn callbacks() -> Callbacks {
+    *lock(&CALLBACKS)
+}
+
+// The values of the core options.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Options {
+    quirks: Quirks,
+    instructions_per_frame: usize,
+    palette: Palette,
+}
+
+impl Options {
+    // Parses the values of the core options, falling back to the default of each option if the
+    // value is missing or invalid.
+    fn parse(
+        quirks: Option<&str>,
+        instructions_per_frame: Option<&str>,
+        palette: Option<&str>,
+    ) -> Self {
+        Options {
+            quirks: quirks.and_then(Quirks::from_preset).unwrap_or_default(),
+            instructions_per_frame: instructions_per_frame
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(10),
+            palette: palette.and_then(Palette::from_name).unwrap_or_default(),
+        }
+    }
+
+    unsafe fn from_environment(environment: Option<EnvironmentFn>) -> Self {
+        let get_variable = |key: &[u8]| -> Option<String> {
+            let environment = environment?;
+            let mut variable = Variable {
+                key: key.as_ptr() as *const c_char,
+                value: ptr::null(),
+            };
+            let variable_ptr = &mut variable as *mut Variable as *mut c_void;
+            if !environment(ENVIRONMENT_GET_VARIABLE, variable_ptr) || variable.value.is_null() {
+                return None;
+            }
+            CStr::from_ptr(variable.value)
+                .to_str()
+                .ok()
+                .map(String::from)
+        };
+        Options::parse(
+            get_variable(QUIRKS_KEY).as_deref(),
+            get_variable(INSTRUCTIONS_PER_FRAME_KEY).as_deref(),
+            get_variable(PALETTE_KEY).as_deref(),
+        )
+    }
+}
+
+struct Core {
+    chip_8: Chip8,
+    rom: Vec<u8>,
+    options: Options,
+    key_map: KeyMap,
+    keys: u16,
+    frame: Vec<u32>,
+    audio: Vec<i16>,
+    beep_phase: usize,
+    error: Option<Error>,
+}
+
+impl Core {
+    fn new(rom: &[u8], options: Options) -> Result<Self, Error> {
+        let chip_8 = Chip8::new();
+        let key_map = chip_8.key_map();
+        let mut ret = Core {
+            chip_8,
+            rom: rom.to_vec(),
+            options,
+            key_map,
+            keys: 0,
+            frame: vec![0; SCREEN_BUFFER_WIDTH * SCREEN_BUFFER_HEIGHT],
+            audio: vec![0; SAMPLES_PER_FRAME * 2],
+            beep_phase: 0,
+            error: None,
+        };
+        ret.reset()?;
+        Ok(ret)
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        let quirks = self.options.quirks;
+        self.chip_8.set_quirks(quirks);
+        self.keys = 0;
+        self.error = None;
+        self.chip_8
+            .try_load_rom(&self.rom, quirks.draw_mode == DrawMode::Wrap)
+    }
+
+    fn set_options(&mut self, options: Options) {
+        if options.quirks != self.options.quirks {
+            self.chip_8.set_quirks(options.quirks);
+        }
+        self.options = options;
+    }
+
+    // Returns the hex keys held on `device` as a bit mask.
+    fn poll_keys(&self, input_state: InputStateFn, device: c_uint) -> u16 {
+        let is_pressed = |device: c_uint, id: c_uint| unsafe { input_state(0, device, 0, id) != 0 };
+        let mut keys = 0;
+        if device == DEVICE_KEYBOARD {
+            for (host_key, key) in self.key_map.bindings() {
+                let bytes = host_key.as_bytes();
+                if bytes.len() == 1 && is_pressed(DEVICE_KEYBOARD, c_uint::from(bytes[0])) {
+                    keys |= 1 << key;
+                }
+            }
+        } else {
+            let profile = self.chip_8.controller_profile();
+            for (button, id) in BUTTONS.iter().zip(JOYPAD_IDS.iter()) {
+                if let Some(key) = profile.get(*button) {
+                    if is_pressed(DEVICE_JOYPAD, *id) {
+                        keys |= 1 << key;
+                    }
+                }
+            }
+        }
+        keys
+    }
+
+    fn set_keys(&mut self, keys: u16) {
+        for key in 0..16 {
+            let mask = 1 << key;
+            if keys & mask != 0 && self.keys & mask == 0 {
+                self.chip_8.press_hex_key(key);
+            } else if keys & mask == 0 && self.keys & mask != 0 {
+                self.chip_8.release_hex_key(key);
+            }
+        }
+        self.keys = keys;
+    }
+
+    // Runs a frame and returns the error that halted the emulator during this frame, if any. A
+    // halted emulator keeps presenting its last frame until it is reset.
+    fn run_frame(&mut self) -> Option<Error> {
+        let is_beeping = self.error.is_none() && self.chip_8.sound_timer() > 0;
+        let mut ret = None;
+        if self.error.is_none() {
+            if let Err(error) = self.chip_8.run_frame(self.options.instructions_per_frame) {
+                self.error = Some(error);
+                ret = Some(error);
+            }
+        }
+        self.render_video();
+        self.render_audio(is_beeping);
+        ret
+    }
+
+    fn render_video(&mut self) {
+        let Palette {
+            background,
+            foreground,
+        } = self.options.palette;
+        let screen = self.chip_8.screen_buffer();
+        for (i, pixel) in self.frame.iter_mut().enumerate() {
+            *pixel = if screen[i / 8] & (1 << (i % 8)) != 0 {
+                foreground
+            } else {
+                background
+            };
+        }
+    }
+
+    // Renders a square wave while the sound timer is active.
+    fn render_audio(&mut self, is_beeping: bool) {
+        let half_period = SAMPLE_RATE / BEEP_FREQUENCY / 2;
+        for frame in self.audio.chunks_mut(2) {
+            let sample = if !is_beeping {
+                0
+            } else if self.beep_phase / half_period % 2 == 0 {
+                BEEP_AMPLITUDE
+            } else {
+                -BEEP_AMPLITUDE
+            };
+            frame[0] = sample;
+            frame[1] = sample;
+            self.beep_phase = if is_beeping { self.beep_phase + 1 } else { 0 };
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    API_VERSION
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_environment(callback: Option<EnvironmentFn>) {
+    lock(&CALLBACKS).environment = callback;
+    let environment = match callback {
+        Some(environment) => environment,
+        None => return,
+    };
+
+    let mut variables = [
+        Variable {
+            key: QUIRKS_KEY.as_ptr() as *const c_char,
+            value: b"Quirks preset; chip-8|schip-legacy|schip-modern\0".as_ptr() as *const c_char,
+        },
+        Variable {
+            key: INSTRUCTIONS_PER_FRAME_KEY.as_ptr() as *const c_char,
+            value: b"Instructions per frame; 10|15|20|30|50|100|200|500|1000\0".as_ptr()
+                as *const c_char,
+        },
+        Variable {
+            key: PALETTE_KEY.as_ptr() as *const c_char,
+            value: b"Palette; black-on-white|white-on-black|green|amber\0".as_ptr()
+                as *const c_char,
+        },
+        Variable {
+            key: ptr::null(),
+            value: ptr::null(),
+        },
+    ];
+    environment(
+        ENVIRONMENT_SET_VARIABLES,
+        variables.as_mut_ptr() as *mut c_void,
+    );
+
+    let devices = [
+        ControllerDescription {
+            desc: b"Gamepad\0".as_ptr() as *const c_char,
+            id: DEVICE_JOYPAD,
+        },
+        ControllerDescription {
+            desc: b"Keyboard\0".as_ptr() as *const c_char,
+            id: DEVICE_KEYBOARD,
+        },
+    ];
+    let mut controller_info = [
+        ControllerInfo {
+            types: devices.as_ptr(),
+            num_types: devices.len() as c_uint,
+        },
+        ControllerInfo {
+            types: ptr::null(),
+            num_types: 0,
+        },
+    ];
+    environment(
+        ENVIRONMENT_SET_CONTROLLER_INFO,
+        controller_info.as_mut_ptr() as *mut c_void,
+    );
+
+    let mut log_callback = LogCallback { log: None };
+    if environment(
+        ENVIRONMENT_GET_LOG_INTERFACE,
+        &mut log_callback as *mut LogCallback as *mut c_void,
+    ) {
+        lock(&CALLBACKS).log = log_callback.log;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: Option<VideoRefreshFn>) {
+    lock(&CALLBACKS).video_refresh = callback;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: Option<AudioSampleFn>) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: Option<AudioSampleBatchFn>) {
+    lock(&CALLBACKS).audio_sample_batch = callback;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: Option<InputPollFn>) {
+    lock(&CALLBACKS).input_poll = callback;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: Option<InputStateFn>) {
+    lock(&CALLBACKS).input_state = callback;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *lock(&CORE) = None;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    if let Some(info) = info.as_mut() {
+        *info = SystemInfo {
+            library_name: b"chipo\0".as_ptr() as *const c_char,
+            library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+            valid_extensions: b"ch8|c8|sc8\0".as_ptr() as *const c_char,
+            need_fullpath: false,
+            block_extract: false,
+        };
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    if let Some(info) = info.as_mut() {
+        *info = SystemAvInfo {
+            geometry: GameGeometry {
+                base_width: SCREEN_BUFFER_WIDTH as c_uint,
+                base_height: SCREEN_BUFFER_HEIGHT as c_uint,
+                max_width: SCREEN_BUFFER_WIDTH as c_uint,
+                max_height: SCREEN_BUFFER_HEIGHT as c_uint,
+                aspect_ratio: 2.0,
+            },
+            timing: SystemTiming {
+                fps: FRAMES_PER_SECOND,
+                sample_rate: SAMPLE_RATE as f64,
+            },
+        };
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(port: c_uint, device: c_uint) {
+    if port == 0 {
+        DEVICE.store(device, Ordering::Relaxed);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = lock(&CORE).as_mut() {
+        // The rom was successfully loaded before, so it fits in memory.
+        let _ = core.reset();
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_run() {
+    let callbacks = callbacks();
+    if let Some(input_poll) = callbacks.input_poll {
+        input_poll();
+    }
+
+    let mut core = lock(&CORE);
+    let core = match core.as_mut() {
+        Some(core) => core,
+        None => return,
+    };
+
+    if let Some(environment) = callbacks.environment {
+        let mut is_updated = false;
+        let is_updated_ptr = &mut is_updated as *mut bool as *mut c_void;
+        if environment(ENVIRONMENT_GET_VARIABLE_UPDATE, is_updated_ptr) && is_updated {
+            core.set_options(Options::from_environment(Some(environment)));
+        }
+    }
+
+    if let Some(input_state) = callbacks.input_state {
+        let keys = core.poll_keys(input_state, DEVICE.load(Ordering::Relaxed));
+        core.set_keys(keys);
+    }
+
+    if let Some(error) = core.run_frame() {
+        if let Some(log) = callbacks.log {
+            let message = format!("chipo: {}\n\0", error);
+            log(
+                LOG_ERROR,
+                b"%s\0".as_ptr() as *const c_char,
+                message.as_ptr(),
+            );
+        }
+    }
+
+    if let Some(video_refresh) = callbacks.video_refresh {
+        video_refresh(
+            core.frame.as_ptr() as *const c_void,
+            SCREEN_BUFFER_WIDTH as c_uint,
+            SCREEN_BUFFER_HEIGHT as c_uint,
+            SCREEN_BUFFER_WIDTH * 4,
+        );
+    }
+
+    if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+        let mut written = 0;
+        while written < SAMPLES_PER_FRAME {
+            let frames = audio_sample_batch(
+                core.audio[written * 2..].as_ptr(),
+                SAMPLES_PER_FRAME - written,
+            );
+            if frames == 0 {
+                break;
+            }
+            written += frames;
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    STATE_SIZE
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    match lock(&CORE).as_ref() {
+        Some(core) if !data.is_null() && size >= STATE_SIZE => {
+            let state = core.chip_8.save_state();
+            slice::from_raw_parts_mut(data as *mut u8, state.len()).copy_from_slice(&state);
+            true
+        }
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    match lock(&CORE).as_mut() {
+        Some(core) if !data.is_null() => {
+            let state = slice::from_raw_parts(data as *const u8, size);
+            if core.chip_8.load_state(state).is_err() {
+                return false;
+            }
+            // The held keys are part of the state, so they are resynchronized on the next frame.
+            core.keys = 0;
+            core.error = None;
+            true
+        }
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    let game = match game.as_ref() {
+        Some(game) if !game.data.is_null() => game,
+        _ => return false,
+    };
+    let environment = match callbacks().environment {
+        Some(environment) => environment,
+        None => return false,
+    };
+
+    let mut pixel_format = PIXEL_FORMAT_XRGB8888;
+    let pixel_format_ptr = &mut pixel_format as *mut c_uint as *mut c_void;
+    if !environment(ENVIRONMENT_SET_PIXEL_FORMAT, pixel_format_ptr) {
+        return false;
+    }
+
+    let rom = slice::from_raw_parts(game.data as *const u8, game.size);
+    let core = match Core::new(rom, Options::from_environment(Some(environment))) {
+        Ok(core) => core,
+        Err(_) => return false,
+    };
+
+    let profile = core.chip_8.controller_profile();
+    let mut descriptors: Vec<InputDescriptor> = BUTTONS
+        .iter()
+        .zip(JOYPAD_IDS.iter())
+        .filter_map(|(button, id)| {
+            profile.get(*button).map(|key| InputDescriptor {
+                port: 0,
+                device: DEVICE_JOYPAD,
+                index: 0,
+                id: *id,
+                description: KEY_DESCRIPTIONS[key as usize].as_ptr() as *const c_char,
+            })
+        })
+        .collect();
+    descriptors.push(InputDescriptor {
+        port: 0,
+        device: 0,
+        index: 0,
+        id: 0,
+        description: ptr::null(),
+    });
+    environment(
+        ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+        descriptors.as_mut_ptr() as *mut c_void,
+    );
+
+    *lock(&CORE) = Some(core);
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const GameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *lock(&CORE) = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A beep followed by a loop that draws the `0` character at (0, 0) while key `5` is held.
+    const ROM: [u8; 14] = [
+        0x60, 0x02, 0xF0, 0x18, 0x61, 0x05, 0xE1, 0x9E, 0x12, 0x06, 0xD2, 0x25, 0x12, 0x06,
+    ];
+
+    unsafe extern "C" fn input_state(
+        _port: c_uint,
+        device: c_uint,
+        _index: c_uint,
+        id: c_uint,
+    ) -> i16 {
+        match (device, id) {
+            (DEVICE_JOYPAD, 4) => 1,
+            (DEVICE_KEYBOARD, 113) => 1,
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn test_options() {
+        let options = Options::parse(Some("schip-modern"), Some("200"), Some("amber"));
+        assert_eq!(options.quirks, Quirks::schip_modern());
+        assert_eq!(options.instructions_per_frame, 200);
+        assert_eq!(options.palette, Palette::from_name("amber").unwrap());
+
+        let options = Options::parse(Some("schip"), None, Some("red"));
+        assert_eq!(options.quirks, Quirks::new());
+        assert_eq!(options.instructions_per_frame, 10);
+        assert_eq!(options.palette, Palette::new());
+    }
+
+    #[test]
+    fn test_poll_keys() {
+        let core = Core::new(&ROM, Options::parse(None, None, None)).unwrap();
+        assert_eq!(core.poll_keys(input_state, DEVICE_JOYPAD), 1 << 0x5);
+        assert_eq!(core.poll_keys(input_state, DEVICE_KEYBOARD), 1 << 0x4);
+    }
+
+    #[test]
+    fn test_run_frame() {
+        let options = Options::parse(None, Some("4"), Some("white-on-black"));
+        let mut core = Core::new(&ROM, options).unwrap();
+        assert_eq!(core.run_frame(), None);
+        assert!(core.audio.iter().all(|sample| *sample == 0));
+        assert!(core.frame.iter().all(|pixel| *pixel == 0));
+
+        core.set_keys(1 << 0x5);
+        assert_eq!(core.run_frame(), None);
+        assert!(core.audio.contains(&BEEP_AMPLITUDE));
+        assert!(core.audio.contains(&-BEEP_AMPLITUDE));
+        assert_eq!(core.frame[0], 0x00FF_FFFF);
+        assert_eq!(core.frame[SCREEN_BUFFER_WIDTH * 2], 0x00FF_FFFF);
+
+        core.rom = vec![0x00, 0xEE];
+        core.reset().unwrap();
+        assert_eq!(core.run_frame(), Some(Error::StackUnderflow));
+        assert_eq!(core.run_frame(), None);
+        assert_eq!(core.error, Some(Error::StackUnderflow));
+    }
+}
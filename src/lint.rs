@@ -0,0 +1,331 @@
+use std::fmt;
+
+use crate::{ControlFlowGraph, Instruction, MEMORY_SIZE, PROGRAM_START, REGISTER_COUNT};
+
+/// The kinds of behaviour that differ between interpreters and that a rom may rely on.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LintKind {
+    /// `8XY6` or `8XYE` with `X != Y`. The COSMAC VIP shifts `VY` into `VX`, while SCHIP shifts
+    /// `VX` in place.
+    AmbiguousShift,
+    /// `FX55` or `FX65` followed by an instruction that uses `I` before setting it. The COSMAC VIP
+    /// increments `I` past the stored registers, while SCHIP leaves it unchanged.
+    IndexAfterLoadStore,
+    /// `BNNN` where the high nibble of `NNN` is not `0`. The COSMAC VIP jumps to `NNN + V0`, while
+    /// SCHIP jumps to `XNN + VX`.
+    AmbiguousJumpOffset,
+    /// `DXYN` with constant coordinates that draws past the edge of the screen. Some interpreters
+    /// wrap the sprite around the screen, while others clip it.
+    SpriteAtEdge,
+    /// An instruction that only SCHIP supports.
+    SuperChipInstruction,
+}
+
+/// A reachable instruction that relies on ambiguous behaviour.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Lint {
+    /// The address of the instruction.
+    pub address: u16,
+    /// The kind of behaviour.
+    pub kind: LintKind,
+    /// The disassembly of the instruction.
+    pub mnemonic: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self.kind {
+            LintKind::AmbiguousShift => "shifts VY into VX on the COSMAC VIP and VX on SCHIP",
+            LintKind::IndexAfterLoadStore => {
+                "is followed by a use of I, which the COSMAC VIP increments and SCHIP does not"
+            }
+            LintKind::AmbiguousJumpOffset => "adds V0 on the COSMAC VIP and VX on SCHIP",
+            LintKind::SpriteAtEdge => "draws past the edge of the screen, which wraps or clips",
+            LintKind::SuperChipInstruction => "is a SCHIP instruction",
+        };
+        write!(
+            f,
+            "0x{:03X} {}: {}",
+            self.address, self.mnemonic, description
+        )
+    }
+}
+
+// Returns `true` if `instruction` reads `I`.
+fn uses_index(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Draw(..)
+            | Instruction::AddIndex(_)
+            | Instruction::StoreBcd(_)
+            | Instruction::StoreRegisters(_)
+            | Instruction::LoadRegisters(_)
+    )
+}
+
+// Returns `true` if `instruction` sets `I` without reading it.
+fn sets_index(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::LoadIndex(_) | Instruction::LoadFont(_) | Instruction::LoadLargeFont(_)
+    )
+}
+
+// Returns `true` if the instruction after `instruction` is not always the next one in memory.
+// Skips are not included, since both of their successors follow them in memory.
+fn changes_flow(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Jump(_)
+            | Instruction::Call(_)
+            | Instruction::Return
+            | Instruction::Exit
+            | Instruction::JumpOffset(_)
+    )
+}
+
+fn is_super_chip(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::ScrollDown(_)
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::Exit
+            | Instruction::LowResolution
+            | Instruction::HighResolution
+            | Instruction::Draw(_, _, 0)
+            | Instruction::LoadLargeFont(_)
+            | Instruction::StoreFlags(_)
+            | Instruction::LoadFlags(_)
+    )
+}
+
+/// The ambiguous behaviours that a rom relies on, found in the instructions that can be reached
+/// from `PROGRAM_START`, and the quirks preset that the rom most likely expects.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RomLints {
+    lints: Vec<Lint>,
+}
+
+impl RomLints {
+    /// Lints `rom` as if it was loaded at `PROGRAM_START`.
+    pub fn analyze(rom: &[u8]) -> Self {
+        let graph = ControlFlowGraph::analyze(rom);
+        let start = PROGRAM_START as usize;
+        let mut memory = vec![0; MEMORY_SIZE];
+        let end = (start + rom.len()).min(MEMORY_SIZE);
+        memory[start..end].copy_from_slice(&rom[..end - start]);
+        let decode = |address: u16| {
+            memory
+                .get(address as usize..address as usize + 2)
+                .and_then(|bytes| Instruction::decode(u16::from_be_bytes([bytes[0], bytes[1]])))
+        };
+        let uses_high_resolution = graph.blocks().any(|block| {
+            (block.start..block.end)
+                .step_by(2)
+                .any(|address| decode(address) == Some(Instruction::HighResolution))
+        });
+
+        let mut lints = Vec::new();
+        for block in graph.blocks() {
+            // The values of the registers that are known within the block, and the screen size.
+            let mut registers: [Option<u8>; REGISTER_COUNT] = [None; REGISTER_COUNT];
+            let mut screen = if uses_high_resolution {
+                None
+            } else {
+                Some((64, 32))
+            };
+
+            for address in (block.start..block.end).step_by(2) {
+                let instruction = match decode(address) {
+                    Some(instruction) => instruction,
+                    None => continue,
+                };
+                let mut lint = |kind| {
+                    lints.push(Lint {
+                        address,
+                        kind,
+                        mnemonic: instruction.to_string(),
+                    })
+                };
+                if is_super_chip(instruction) {
+                    lint(LintKind::SuperChipInstruction);
+                }
+                match instruction {
+                    Instruction::ShiftRight(x, y) | Instruction::ShiftLeft(x, y) if x != y => {
+                        lint(LintKind::AmbiguousShift)
+                    }
+                    Instruction::JumpOffset(nnn) if nnn & 0xF00 != 0 => {
+                        lint(LintKind::AmbiguousJumpOffset)
+                    }
+                    Instruction::StoreRegisters(_) | Instruction::LoadRegisters(_) => {
+                        let mut next = address.wrapping_add(2);
+                        while let Some(following) = decode(next) {
+                            if uses_index(following) {
+                                lint(LintKind::IndexAfterLoadStore);
+                                break;
+                            }
+                            if sets_index(following) || changes_flow(following) {
+                                break;
+                            }
+                            next = next.wrapping_add(2);
+                        }
+                    }
+                    Instruction::Draw(x, y, n) => {
+                        let (width, height) = match n {
+                            0 => (16, 16),
+                            n => (8, n),
+                        };
+                        if let (Some((screen_width, screen_height)), Some(vx), Some(vy)) =
+                            (screen, registers[x], registers[y])
+                        {
+                            let col = vx as usize % screen_width;
+                            let row = vy as usize % screen_height;
+                            if col + width > screen_width || row + height > screen_height {
+                                lint(LintKind::SpriteAtEdge);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+
+                match instruction {
+                    Instruction::LoadByte(x, kk) => registers[x] = Some(kk),
+                    Instruction::LowResolution => screen = Some((64, 32)),
+                    Instruction::HighResolution => screen = Some((128, 64)),
+                    _ => {
+                        for register in written_registers(instruction) {
+                            registers[register] = None;
+                        }
+                    }
+                }
+            }
+        }
+        RomLints { lints }
+    }
+
+    /// Returns the lints in ascending order of address.
+    pub fn lints(&self) -> &[Lint] {
+        &self.lints
+    }
+
+    /// Returns `true` if the rom uses SCHIP instructions.
+    pub fn uses_super_chip(&self) -> bool {
+        self.lints
+            .iter()
+            .any(|lint| lint.kind == LintKind::SuperChipInstruction)
+    }
+
+    /// Returns the name of the quirks preset that the rom most likely expects, which is one of
+    /// `Quirks::PRESETS`. Roms that use SCHIP instructions are expected to target the SCHIP 1.1
+    /// interpreter, and others the COSMAC VIP.
+    ///
+    /// Returns `None` if the rom has an `AmbiguousShift`, `IndexAfterLoadStore`, or
+    /// `AmbiguousJumpOffset` lint. The presets do not differ in these behaviours, so no preset
+    /// can be suggested for them.
+    pub fn suggested_preset(&self) -> Option<&'static str> {
+        let is_ambiguous = self.lints.iter().any(|lint| {
+            matches!(
+                lint.kind,
+                LintKind::AmbiguousShift
+                    | LintKind::IndexAfterLoadStore
+                    | LintKind::AmbiguousJumpOffset
+            )
+        });
+        if is_ambiguous {
+            None
+        } else if self.uses_super_chip() {
+            Some("schip-legacy")
+        } else {
+            Some("chip-8")
+        }
+    }
+}
+
+// Returns the registers that `instruction` may change, other than `6XKK`.
+fn written_registers(instruction: Instruction) -> Vec<usize> {
+    match instruction {
+        Instruction::AddByte(x, _)
+        | Instruction::Load(x, _)
+        | Instruction::Random(x, _)
+        | Instruction::LoadDelayTimer(x)
+        | Instruction::WaitForKey(x) => vec![x],
+        Instruction::Or(x, _)
+        | Instruction::And(x, _)
+        | Instruction::Xor(x, _)
+        | Instruction::Add(x, _)
+        | Instruction::Sub(x, _)
+        | Instruction::ShiftRight(x, _)
+        | Instruction::SubNegated(x, _)
+        | Instruction::ShiftLeft(x, _) => vec![x, 0xF],
+        Instruction::Draw(..) => vec![0xF],
+        Instruction::LoadRegisters(x) | Instruction::LoadFlags(x) => (0..=x).collect(),
+        // Calls may change any register before the next instruction of the block runs.
+        Instruction::Call(_) => (0..REGISTER_COUNT).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze() {
+        // 200: SHR V1, V2; 202: SHL V3, V3; 204: LD [I], V1; 206: SE V0, 1; 208: ADD I, V1;
+        // 20A: LD V0, 60; 20C: LD V1, 10; 20E: DRW V0, V1, 5; 210: LD V0, 10; 212: DRW V0, V1, 5;
+        // 214: JP V1, 0x200
+        let lints = RomLints::analyze(&[
+            0x81, 0x26, 0x83, 0x3E, 0xF1, 0x55, 0x30, 0x01, 0xF1, 0x1E, 0x60, 0x3C, 0x61, 0x0A,
+            0xD0, 0x15, 0x60, 0x0A, 0xD0, 0x15, 0xB1, 0x00,
+        ]);
+        let found: Vec<(u16, LintKind)> = lints
+            .lints()
+            .iter()
+            .map(|lint| (lint.address, lint.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (0x200, LintKind::AmbiguousShift),
+                (0x204, LintKind::IndexAfterLoadStore),
+                (0x20E, LintKind::SpriteAtEdge),
+                (0x214, LintKind::AmbiguousJumpOffset),
+            ]
+        );
+        assert_eq!(
+            lints.lints()[0].to_string(),
+            "0x200 SHR V1, V2: shifts VY into VX on the COSMAC VIP and VX on SCHIP"
+        );
+        assert_eq!(lints.suggested_preset(), None);
+
+        // 200: JP 0x200
+        let lints = RomLints::analyze(&[0x12, 0x00]);
+        assert!(lints.lints().is_empty());
+        assert_eq!(lints.suggested_preset(), Some("chip-8"));
+    }
+
+    #[test]
+    fn test_super_chip() {
+        // 200: HIGH; 202: LD V0, 120; 204: DRW V0, V0, 0; 206: LD I, 0x200; 208: LD [I], V1;
+        // 20A: LD I, 0x200; 20C: JP 0x20C
+        let lints = RomLints::analyze(&[
+            0x00, 0xFF, 0x60, 0x78, 0xD0, 0x00, 0xA2, 0x00, 0xF1, 0x55, 0xA2, 0x00, 0x12, 0x0C,
+        ]);
+        let found: Vec<(u16, LintKind)> = lints
+            .lints()
+            .iter()
+            .map(|lint| (lint.address, lint.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (0x200, LintKind::SuperChipInstruction),
+                (0x204, LintKind::SuperChipInstruction),
+                (0x204, LintKind::SpriteAtEdge),
+            ]
+        );
+        assert!(lints.uses_super_chip());
+        assert_eq!(lints.suggested_preset(), Some("schip-legacy"));
+    }
+}
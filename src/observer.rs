@@ -0,0 +1,54 @@
+use crate::{Chip8, Error};
+
+/// Observes the instructions executed by [`Chip8::step_observed`] and
+/// [`Chip8::run_frame_observed`]. [`Tracer`](crate::Tracer), [`Profiler`](crate::Profiler), and
+/// [`Coverage`](crate::Coverage) are observers, and `()` observes nothing.
+///
+/// The hooks are only called for instructions that are executed, so they are skipped once the
+/// interpreter has exited or if the program counter is out of bounds.
+pub trait StepObserver {
+    /// Called before the instruction `opcode` at `address` is executed.
+    fn before_step(&mut self, _chip_8: &Chip8, _address: u16, _opcode: u16) {}
+
+    /// Called after the instruction `opcode` at `address` is executed, unless it returned an
+    /// error.
+    fn after_step(&mut self, _chip_8: &Chip8, _address: u16, _opcode: u16) {}
+
+    /// Called after the timers are decremented at the end of a frame.
+    fn after_frame(&mut self, _chip_8: &Chip8) {}
+}
+
+impl StepObserver for () {}
+
+impl Chip8 {
+    /// Runs one cycle like `step`, and reports the executed instruction to `observer`.
+    pub fn step_observed(&mut self, observer: &mut impl StepObserver) -> Result<(), Error> {
+        let address = self.pc;
+        let opcode = match self.fetch_opcode() {
+            Ok(opcode) if self.is_running => opcode,
+            _ => return self.step(),
+        };
+        observer.before_step(self, address, opcode);
+        self.step()?;
+        observer.after_step(self, address, opcode);
+        Ok(())
+    }
+
+    /// Runs a frame like `run_frame`, and reports the executed instructions and the end of the
+    /// frame to `observer`.
+    pub fn run_frame_observed(
+        &mut self,
+        cycles: usize,
+        observer: &mut impl StepObserver,
+    ) -> Result<(), Error> {
+        let mut should_draw = false;
+        for _ in 0..cycles {
+            self.step_observed(observer)?;
+            should_draw |= self.should_draw;
+        }
+        self.should_draw = should_draw;
+        self.decrement_timers();
+        observer.after_frame(self);
+        Ok(())
+    }
+}
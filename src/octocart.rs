@@ -0,0 +1,328 @@
+use std::error;
+use std::fmt;
+
+use crate::gif::Gif;
+use crate::json::Json;
+use crate::palette::parse_color;
+use crate::{DrawMode, Palette, Quirks};
+
+/// An error that occurred while loading an Octo cartridge.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OctocartError {
+    /// A description of the error.
+    pub message: String,
+}
+
+impl fmt::Display for OctocartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl error::Error for OctocartError {}
+
+/// The options of an Octo program. Options that are missing from the cartridge are `None`,
+/// `false`, or `0`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct OctoOptions {
+    /// The number of instructions per frame.
+    pub tickrate: Option<usize>,
+    /// If `true`, `8XY6` and `8XYE` shift `VX` in place instead of shifting `VY` into `VX`.
+    pub shift_quirks: bool,
+    /// If `true`, `FX55` and `FX65` leave `I` unchanged.
+    pub load_store_quirks: bool,
+    /// If `true`, `VF` is set before the result of arithmetic instructions is stored.
+    pub vf_order_quirks: bool,
+    /// If `true`, sprites are clipped at the edges of the screen instead of wrapping.
+    pub clip_quirks: bool,
+    /// If `true`, `BNNN` jumps to `XNN + VX` instead of `NNN + V0`.
+    pub jump_quirks: bool,
+    /// If `true`, `DXYN` waits for the next frame.
+    pub vblank_quirks: bool,
+    /// If `true`, `8XY1`, `8XY2`, and `8XY3` reset `VF` to `0`.
+    pub logic_quirks: bool,
+    /// The color of pixels that are set in the first plane, as a `0xRRGGBB` value.
+    pub fill_color: Option<u32>,
+    /// The color of pixels that are set in the second plane.
+    pub fill_color_2: Option<u32>,
+    /// The color of pixels that are set in both planes.
+    pub blend_color: Option<u32>,
+    /// The color of pixels that are not set.
+    pub background_color: Option<u32>,
+    /// The color of the border while the buzzer sounds.
+    pub buzz_color: Option<u32>,
+    /// The color of the border while the buzzer is quiet.
+    pub quiet_color: Option<u32>,
+    /// The clockwise rotation of the screen in degrees, which is `0`, `90`, `180`, or `270`.
+    pub screen_rotation: u16,
+}
+
+impl OctoOptions {
+    /// Returns the quirks that match these options as closely as chipo supports. Octo behaves like
+    /// `Quirks::schip_modern`, and `clip_quirks` selects the drawing mode. The other quirks have
+    /// no equivalent in `Quirks`, so the program may not run correctly if `unsupported_quirks`
+    /// is not empty.
+    pub fn quirks(&self) -> Quirks {
+        let mut quirks = Quirks::schip_modern();
+        quirks.draw_mode = if self.clip_quirks {
+            DrawMode::Clip
+        } else {
+            DrawMode::Wrap
+        };
+        quirks
+    }
+
+    /// Returns the Octo names and values of the quirks that chipo cannot emulate. chipo always
+    /// shifts `VX` in place and leaves `I` unchanged in `FX55` and `FX65`, which are
+    /// `shiftQuirks` and `loadStoreQuirks`, and none of the other quirks besides `clipQuirks`.
+    pub fn unsupported_quirks(&self) -> Vec<(&'static str, bool)> {
+        [
+            ("shiftQuirks", self.shift_quirks, true),
+            ("loadStoreQuirks", self.load_store_quirks, true),
+            ("vfOrderQuirks", self.vf_order_quirks, false),
+            ("jumpQuirks", self.jump_quirks, false),
+            ("vBlankQuirks", self.vblank_quirks, false),
+            ("logicQuirks", self.logic_quirks, false),
+        ]
+        .iter()
+        .filter(|(_, value, supported)| value != supported)
+        .map(|(name, value, _)| (*name, *value))
+        .collect()
+    }
+
+    /// Returns the palette of the background and fill colors, if both are set.
+    pub fn palette(&self) -> Option<Palette> {
+        Some(Palette {
+            background: self.background_color?,
+            foreground: self.fill_color?,
+        })
+    }
+}
+
+/// An Octo cartridge, which is a GIF image that carries the source and the options of an Octo
+/// program.
+///
+/// The payload of a cartridge is stored in the palette indices of its frames, which are read in
+/// order. Each byte is split across two consecutive pixels, where the low nibble of the first
+/// index is the high nibble of the byte and the low nibble of the second index is the low nibble.
+/// The first four bytes are the big-endian length of a JSON object with the source in `program`
+/// and the options in `options`.
+///
+/// The source is Octo assembly, which has to be assembled by Octo before it can be loaded.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Octocart {
+    /// The Octo source of the program.
+    pub source: String,
+    /// The options of the program.
+    pub options: OctoOptions,
+}
+
+impl Octocart {
+    /// Decodes an Octo cartridge from the contents of a GIF.
+    pub fn parse(gif: &[u8]) -> Result<Self, OctocartError> {
+        let error = |message: String| OctocartError { message };
+        let gif = Gif::decode(gif).map_err(error)?;
+
+        let mut nibbles = gif.frames.iter().flatten().map(|index| index & 0x0F);
+        let mut next_byte = || Some(nibbles.next()? << 4 | nibbles.next()?);
+        let mut read_bytes = |count: usize| {
+            (0..count)
+                .map(|_| next_byte())
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| error(String::from("Truncated cartridge payload")))
+        };
+        let size = read_bytes(4)?;
+        let size = u32::from_be_bytes([size[0], size[1], size[2], size[3]]);
+        let payload = read_bytes(size as usize)?;
+
+        // Octo writes each character as a byte, so payloads that are not UTF-8 are Latin-1.
+        let payload = String::from_utf8(payload)
+            .unwrap_or_else(|error| error.into_bytes().iter().map(|&c| c as char).collect());
+        let payload = Json::parse(&payload).map_err(error)?;
+        let source = payload
+            .get("program")
+            .and_then(Json::as_str)
+            .ok_or_else(|| error(String::from("Expected the program to be a string")))?
+            .to_string();
+        let options = match payload.get("options") {
+            Some(options) => parse_options(options).map_err(error)?,
+            None => OctoOptions::default(),
+        };
+        Ok(Octocart { source, options })
+    }
+}
+
+fn parse_options(options: &Json) -> Result<OctoOptions, String> {
+    if !matches!(options, Json::Object(_)) {
+        return Err(String::from("Expected the options to be an object"));
+    }
+    let flag = |name: &str| match options.get(name) {
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| format!("Expected {} to be a boolean", name)),
+        None => Ok(false),
+    };
+    let color = |name: &str| match options.get(name) {
+        Some(value) => value
+            .as_str()
+            .and_then(parse_color)
+            .map(Some)
+            .ok_or_else(|| format!("Invalid color for {}", name)),
+        None => Ok(None),
+    };
+    let tickrate = match options.get("tickrate") {
+        Some(tickrate) => Some(
+            tickrate
+                .as_u64()
+                .ok_or("Expected the tickrate to be a non-negative integer")? as usize,
+        ),
+        None => None,
+    };
+    let screen_rotation = match options.get("screenRotation") {
+        Some(rotation) => match rotation.as_u64() {
+            Some(rotation @ 0) | Some(rotation @ 90) | Some(rotation @ 180)
+            | Some(rotation @ 270) => rotation as u16,
+            _ => {
+                return Err(String::from(
+                    "Expected the screen rotation to be 0, 90, 180, or 270",
+                ))
+            }
+        },
+        None => 0,
+    };
+
+    Ok(OctoOptions {
+        tickrate,
+        shift_quirks: flag("shiftQuirks")?,
+        load_store_quirks: flag("loadStoreQuirks")?,
+        vf_order_quirks: flag("vfOrderQuirks")?,
+        clip_quirks: flag("clipQuirks")?,
+        jump_quirks: flag("jumpQuirks")?,
+        vblank_quirks: flag("vBlankQuirks")?,
+        logic_quirks: flag("logicQuirks")?,
+        fill_color: color("fillColor")?,
+        fill_color_2: color("fillColor2")?,
+        blend_color: color("blendColor")?,
+        background_color: color("backgroundColor")?,
+        buzz_color: color("buzzColor")?,
+        quiet_color: color("quietColor")?,
+        screen_rotation,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gif;
+
+    const WIDTH: u16 = 16;
+    const HEIGHT: u16 = 8;
+
+    // Encodes `payload` into a cartridge, with a label in the high nibbles of the indices.
+    fn cartridge(payload: &[u8]) -> Vec<u8> {
+        let mut bytes = (payload.len() as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(payload);
+        let indices: Vec<u8> = bytes
+            .iter()
+            .flat_map(|byte| vec![byte >> 4, byte & 0x0F])
+            .enumerate()
+            .map(|(i, nibble)| (i as u8 % 3) << 4 | nibble)
+            .collect();
+        let frames: Vec<Vec<u8>> = indices
+            .chunks(WIDTH as usize * HEIGHT as usize)
+            .map(|frame| {
+                let mut frame = frame.to_vec();
+                frame.resize(WIDTH as usize * HEIGHT as usize, 0x20);
+                frame
+            })
+            .collect();
+        gif::encode(WIDTH, HEIGHT, &frames)
+    }
+
+    #[test]
+    fn test_parse() {
+        let source = ": main\n\tv0 := 1 # é\n\tloop again\n";
+        let payload = format!(
+            r##"{{"program": {}, "options": {{"tickrate": 500, "clipQuirks": true,
+                "shiftQuirks": true, "fillColor": "#FFCC00", "backgroundColor": "#996600",
+                "screenRotation": 90, "fontStyle": "octo"}}}}"##,
+            Json::from(source)
+        );
+        let cart = Octocart::parse(&cartridge(payload.as_bytes())).unwrap();
+        assert_eq!(cart.source, source);
+        assert_eq!(cart.options.tickrate, Some(500));
+        assert!(cart.options.shift_quirks);
+        assert!(!cart.options.load_store_quirks);
+        assert_eq!(cart.options.screen_rotation, 90);
+        assert_eq!(cart.options.blend_color, None);
+        assert_eq!(
+            cart.options.palette(),
+            Some(Palette {
+                background: 0x0099_6600,
+                foreground: 0x00FF_CC00,
+            })
+        );
+        let mut quirks = Quirks::schip_modern();
+        quirks.draw_mode = DrawMode::Clip;
+        assert_eq!(cart.options.quirks(), quirks);
+        assert_eq!(
+            cart.options.unsupported_quirks(),
+            vec![("loadStoreQuirks", false)]
+        );
+
+        // Octo writes characters as bytes.
+        let cart = Octocart::parse(&cartridge(b"{\"program\": \"\xE9\"}")).unwrap();
+        assert_eq!(cart.source, "\u{e9}");
+        assert_eq!(cart.options, OctoOptions::default());
+        assert_eq!(cart.options.quirks().draw_mode, DrawMode::Wrap);
+        assert_eq!(
+            cart.options.unsupported_quirks(),
+            vec![("shiftQuirks", false), ("loadStoreQuirks", false)]
+        );
+    }
+
+    #[test]
+    fn test_parse_gif_errors() {
+        let mut truncated = cartridge(b"{}");
+        truncated.truncate(truncated.len() - 20);
+        for (gif, expected) in &[
+            (b"PNG".to_vec(), "Unexpected end of GIF"),
+            (truncated, "Unexpected end of GIF"),
+            (
+                gif::encode(WIDTH, HEIGHT, &[vec![0; 4]]),
+                "Truncated image data",
+            ),
+            (
+                gif::encode(2, 2, &[vec![0, 0, 0, 0]]),
+                "Truncated cartridge payload",
+            ),
+        ] {
+            assert_eq!(Octocart::parse(gif).unwrap_err().message, *expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_payload_errors() {
+        for &(payload, expected) in &[
+            (
+                &b"{\"options\": {}}"[..],
+                "Expected the program to be a string",
+            ),
+            (
+                b"{\"program\": \"\", \"options\": {\"screenRotation\": 45}}",
+                "Expected the screen rotation to be 0, 90, 180, or 270",
+            ),
+            (
+                b"{\"program\": \"\", \"options\": {\"fillColor\": \"red\"}}",
+                "Invalid color for fillColor",
+            ),
+        ] {
+            assert_eq!(
+                Octocart::parse(&cartridge(payload)).unwrap_err(),
+                OctocartError {
+                    message: String::from(expected)
+                }
+            );
+        }
+    }
+}
@@ -0,0 +1,51 @@
+/// The colors used to draw the screen, as `0xRRGGBB` values.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Palette {
+    /// The color of unset pixels.
+    pub background: u32,
+    /// The color of set pixels.
+    pub foreground: u32,
+}
+
+impl Palette {
+    /// The names of the palettes accepted by `Palette::from_name`.
+    pub const NAMES: [&'static str; 4] = ["black-on-white", "white-on-black", "green", "amber"];
+
+    /// Constructs a new `Palette` that draws black pixels on a white background.
+    pub fn new() -> Self {
+        Palette {
+            background: 0x00FF_FFFF,
+            foreground: 0x0000_0000,
+        }
+    }
+
+    /// Returns the palette named `name`, which is one of `Palette::NAMES`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let (background, foreground) = match name {
+            "black-on-white" => (0x00FF_FFFF, 0x0000_0000),
+            "white-on-black" => (0x0000_0000, 0x00FF_FFFF),
+            "green" => (0x000F_380F, 0x009B_BC0F),
+            "amber" => (0x0000_0000, 0x00FF_B000),
+            _ => return None,
+        };
+        Some(Palette {
+            background,
+            foreground,
+        })
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Parses a color in the `#RRGGBB` format.
+pub(crate) fn parse_color(color: &str) -> Option<u32> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
@@ -0,0 +1,372 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::{disassemble, Chip8, Instruction, StepObserver, SymbolMap};
+
+/// The number of instructions spent in a subroutine called with `2NNN`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SubroutineProfile {
+    /// The address of the subroutine.
+    pub address: u16,
+    /// The number of calls to the subroutine.
+    pub calls: u64,
+    /// The number of instructions executed between the calls and their returns, including other
+    /// subroutines that were called. Recursive calls are only counted once.
+    pub inclusive_cycles: u64,
+    /// The number of instructions of the subroutine itself, excluding other subroutines.
+    pub exclusive_cycles: u64,
+}
+
+/// A loop formed by a jump or skip back to an earlier address.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LoopProfile {
+    /// The first address of the loop, which is the destination of the jump.
+    pub start: u16,
+    /// The address of the instruction that jumps back to `start`.
+    pub end: u16,
+    /// The number of times the jump back was taken.
+    pub iterations: u64,
+    /// The number of instructions executed between `start` and `end`, inclusive.
+    pub cycles: u64,
+}
+
+// A subroutine that has not returned yet.
+#[derive(Clone, Copy, Debug)]
+struct Frame {
+    address: u16,
+    start: u64,
+}
+
+/// Counts the instructions executed by a `Chip8` per address and per opcode class, the time spent
+/// in each subroutine, and the loops that were taken.
+///
+/// Time is measured in executed instructions, which is what limits a rom at a fixed number of
+/// instructions per frame.
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+    address_counts: BTreeMap<u16, u64>,
+    class_counts: BTreeMap<&'static str, u64>,
+    subroutines: BTreeMap<u16, SubroutineProfile>,
+    loops: BTreeMap<(u16, u16), u64>,
+    frames: Vec<Frame>,
+    cycles: u64,
+    // The stack pointer before the instruction that is being executed.
+    stack_pointer: u16,
+}
+
+impl Profiler {
+    /// Constructs a new empty `Profiler`.
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    /// Returns the number of instructions executed.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Removes all the recorded counts.
+    pub fn clear(&mut self) {
+        *self = Profiler::new();
+    }
+
+    /// Returns an iterator over the addresses and the number of times they were executed in
+    /// ascending order of address.
+    pub fn address_counts(&self) -> impl Iterator<Item = (u16, u64)> + '_ {
+        self.address_counts
+            .iter()
+            .map(|(address, count)| (*address, *count))
+    }
+
+    /// Returns an iterator over the opcode patterns, such as `DXYN`, and the number of times they
+    /// were executed in alphabetical order.
+    pub fn class_counts(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        self.class_counts
+            .iter()
+            .map(|(pattern, count)| (*pattern, *count))
+    }
+
+    /// Returns the subroutines that were called in ascending order of address. Subroutines that
+    /// have not returned yet include the instructions executed so far.
+    pub fn subroutines(&self) -> Vec<SubroutineProfile> {
+        let mut subroutines = self.subroutines.clone();
+        for (i, frame) in self.frames.iter().enumerate() {
+            if self.frames[..i].iter().all(|f| f.address != frame.address) {
+                subroutines
+                    .entry(frame.address)
+                    .or_default()
+                    .inclusive_cycles += self.cycles - frame.start;
+            }
+        }
+        subroutines.into_values().collect()
+    }
+
+    /// Returns the `count` loops that executed the most instructions, from most to least.
+    pub fn hottest_loops(&self, count: usize) -> Vec<LoopProfile> {
+        let mut loops: Vec<LoopProfile> = self
+            .loops
+            .iter()
+            .map(|((start, end), iterations)| LoopProfile {
+                start: *start,
+                end: *end,
+                iterations: *iterations,
+                cycles: self.address_counts.range(start..=end).map(|(_, c)| c).sum(),
+            })
+            .collect();
+        loops.sort_by(|a, b| b.cycles.cmp(&a.cycles).then(a.start.cmp(&b.start)));
+        loops.truncate(count);
+        loops
+    }
+
+    /// Writes a text report of the `count` hottest addresses, the opcode classes, the subroutines,
+    /// and the `count` hottest loops to `out`. Instructions are disassembled from `memory`, and
+    /// named with the labels of `symbols` if given.
+    pub fn write_report(
+        &self,
+        out: &mut impl Write,
+        memory: &[u8],
+        symbols: Option<&SymbolMap>,
+        count: usize,
+    ) -> io::Result<()> {
+        let name = |address: u16| match symbols.and_then(|s| s.enclosing_label(address)) {
+            Some((label, name)) if label == address => name.to_string(),
+            Some((label, name)) => format!("{}+0x{:X}", name, address - label),
+            None => String::new(),
+        };
+        let percent = |cycles: u64| cycles as f64 * 100.0 / self.cycles.max(1) as f64;
+
+        writeln!(out, "Instructions: {}", self.cycles)?;
+
+        writeln!(out, "\nHottest addresses:")?;
+        let mut addresses: Vec<(u16, u64)> = self.address_counts().collect();
+        addresses.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        for (address, cycles) in addresses.into_iter().take(count) {
+            let row = format!(
+                "  {:03X} {:>10} {:>6.2}%  {:<20} {}",
+                address,
+                cycles,
+                percent(cycles),
+                disassemble(memory, address as usize),
+                name(address)
+            );
+            writeln!(out, "{}", row.trim_end())?;
+        }
+
+        writeln!(out, "\nOpcode classes:")?;
+        let mut classes: Vec<(&str, u64)> = self.class_counts().collect();
+        classes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        for (pattern, cycles) in classes {
+            writeln!(
+                out,
+                "  {} {:>10} {:>6.2}%",
+                pattern,
+                cycles,
+                percent(cycles)
+            )?;
+        }
+
+        writeln!(out, "\nSubroutines:")?;
+        writeln!(
+            out,
+            "  {:>14} {:>10} {:>18}",
+            "calls", "inclusive", "exclusive"
+        )?;
+        let mut subroutines = self.subroutines();
+        subroutines.sort_by(|a, b| {
+            b.inclusive_cycles
+                .cmp(&a.inclusive_cycles)
+                .then(a.address.cmp(&b.address))
+        });
+        for subroutine in subroutines {
+            let row = format!(
+                "  {:03X} {:>10} {:>10} {:>6.2}% {:>10} {:>6.2}%  {}",
+                subroutine.address,
+                subroutine.calls,
+                subroutine.inclusive_cycles,
+                percent(subroutine.inclusive_cycles),
+                subroutine.exclusive_cycles,
+                percent(subroutine.exclusive_cycles),
+                name(subroutine.address)
+            );
+            writeln!(out, "{}", row.trim_end())?;
+        }
+
+        writeln!(out, "\nHottest loops:")?;
+        writeln!(out, "  {:>18} {:>10}", "iterations", "cycles")?;
+        for profile in self.hottest_loops(count) {
+            let row = format!(
+                "  {:03X}-{:03X} {:>10} {:>10} {:>6.2}%  {}",
+                profile.start,
+                profile.end,
+                profile.iterations,
+                profile.cycles,
+                percent(profile.cycles),
+                name(profile.start)
+            );
+            writeln!(out, "{}", row.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl StepObserver for Profiler {
+    fn before_step(&mut self, chip_8: &Chip8, _address: u16, _opcode: u16) {
+        self.stack_pointer = chip_8.sp;
+    }
+
+    fn after_step(&mut self, chip_8: &Chip8, address: u16, opcode: u16) {
+        let instruction = match Instruction::decode(opcode) {
+            Some(instruction) => instruction,
+            None => return,
+        };
+
+        self.cycles += 1;
+        *self.address_counts.entry(address).or_default() += 1;
+        *self.class_counts.entry(instruction.pattern()).or_default() += 1;
+        if let Some(frame) = self.frames.last() {
+            self.subroutines
+                .entry(frame.address)
+                .or_default()
+                .exclusive_cycles += 1;
+        }
+
+        match instruction {
+            Instruction::Call(target) if chip_8.sp > self.stack_pointer => {
+                let subroutine = self.subroutines.entry(target).or_default();
+                subroutine.address = target;
+                subroutine.calls += 1;
+                self.frames.push(Frame {
+                    address: target,
+                    start: self.cycles,
+                });
+            }
+            Instruction::Return => {
+                while self.frames.len() > chip_8.sp as usize {
+                    let frame = self.frames.pop().unwrap();
+                    if self.frames.iter().all(|f| f.address != frame.address) {
+                        self.subroutines
+                            .entry(frame.address)
+                            .or_default()
+                            .inclusive_cycles += self.cycles - frame.start;
+                    }
+                }
+            }
+            // Jumps to the same address halt the rom or wait for a key, and are not loops.
+            _ if chip_8.pc < address => {
+                *self.loops.entry((chip_8.pc, address)).or_default() += 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 200: LD V0, 3; 202: CALL 0x20C; 204: ADD V0, 0xFF; 206: SE V0, 0; 208: JP 0x202;
+    // 20A: JP 0x20A; 20C: CALL 0x210; 20E: RET; 210: RET
+    const ROM: [u8; 18] = [
+        0x60, 0x03, 0x22, 0x0C, 0x70, 0xFF, 0x30, 0x00, 0x12, 0x02, 0x12, 0x0A, 0x22, 0x10, 0x00,
+        0xEE, 0x00, 0xEE,
+    ];
+
+    fn profile(cycles: usize) -> Profiler {
+        let mut profiler = Profiler::new();
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&ROM, false);
+        for _ in 0..cycles {
+            chip_8.step_observed(&mut profiler).unwrap();
+        }
+        profiler
+    }
+
+    #[test]
+    fn test_counts() {
+        let profiler = profile(30);
+        assert_eq!(profiler.cycles(), 30);
+        let counts: Vec<_> = profiler.address_counts().collect();
+        assert_eq!(
+            counts,
+            vec![
+                (0x200, 1),
+                (0x202, 3),
+                (0x204, 3),
+                (0x206, 3),
+                (0x208, 2),
+                (0x20A, 9),
+                (0x20C, 3),
+                (0x20E, 3),
+                (0x210, 3),
+            ]
+        );
+        let classes: Vec<_> = profiler.class_counts().collect();
+        assert_eq!(
+            classes,
+            vec![
+                ("00EE", 6),
+                ("1NNN", 11),
+                ("2NNN", 6),
+                ("3XKK", 3),
+                ("6XKK", 1),
+                ("7XKK", 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_subroutines() {
+        let profiler = profile(30);
+        assert_eq!(
+            profiler.subroutines(),
+            vec![
+                SubroutineProfile {
+                    address: 0x20C,
+                    calls: 3,
+                    inclusive_cycles: 9,
+                    exclusive_cycles: 6,
+                },
+                SubroutineProfile {
+                    address: 0x210,
+                    calls: 3,
+                    inclusive_cycles: 3,
+                    exclusive_cycles: 3,
+                },
+            ]
+        );
+
+        // Neither subroutine has returned after the third instruction.
+        let subroutines = profile(3).subroutines();
+        assert_eq!(subroutines[0].inclusive_cycles, 1);
+        assert_eq!(subroutines[1].inclusive_cycles, 0);
+    }
+
+    #[test]
+    fn test_loops() {
+        let profiler = profile(30);
+        assert_eq!(
+            profiler.hottest_loops(10),
+            vec![LoopProfile {
+                start: 0x202,
+                end: 0x208,
+                iterations: 2,
+                cycles: 11,
+            }]
+        );
+
+        let mut memory = vec![0; 0x200];
+        memory.extend_from_slice(&ROM);
+        let mut symbols = SymbolMap::new();
+        symbols.insert_label(0x20C, "update");
+        let mut out = Vec::new();
+        profiler
+            .write_report(&mut out, &memory, Some(&symbols), 1)
+            .unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.starts_with(
+            "Instructions: 30\n\nHottest addresses:\n  20A          9  30.00%  JP 0x20A\n"
+        ));
+        assert!(report.contains("  20C          3          9  30.00%          6  20.00%  update\n"));
+        assert!(report.ends_with("  202-208          2         11  36.67%\n"));
+    }
+}
@@ -0,0 +1,116 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// The drawing mode used by `DXYN` for pixels outside of the screen.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DrawMode {
+    /// Pixels outside of the screen are not drawn.
+    Clip,
+    /// Pixels outside of the screen wrap around to the other side.
+    Wrap,
+}
+
+/// The sprite drawn by `DXY0` in standard mode. In super mode, `DXY0` always draws a 16x16 sprite.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LargeSprite {
+    /// Nothing is drawn, like the original chip-8 interpreter.
+    Empty,
+    /// An 8x16 sprite is drawn, like the SCHIP 1.1 interpreter.
+    Tall,
+    /// A 16x16 sprite is drawn, like modern SCHIP interpreters.
+    Square,
+}
+
+/// Behaviours that differ between chip-8 interpreters. Roms written for one interpreter may rely
+/// on a specific behaviour and render incorrectly when run with another.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Quirks {
+    /// If `true`, the screen is cleared when switching between standard and super mode with
+    /// `00FE` and `00FF`. Otherwise, the contents of the screen are kept as is.
+    pub clear_on_mode_switch: bool,
+    /// If `true`, scrolling in standard mode with `00CN`, `00FB`, and `00FC` moves the screen by
+    /// half of the expected distance, like the SCHIP 1.1 interpreter.
+    pub half_scroll_in_standard_mode: bool,
+    /// If `true`, `DXYN` in super mode sets `VF` to the number of sprite rows that collided with
+    /// existing pixels or were clipped by the bottom of the screen, like the SCHIP 1.1
+    /// interpreter. Otherwise, `VF` is set to `1` if any pixel collided.
+    pub count_collided_rows: bool,
+    /// The drawing mode of `DXYN`. The starting coordinate of a sprite always wraps around the
+    /// screen, and the drawing mode only applies to the pixels of the sprite that go past the edge
+    /// of the screen.
+    pub draw_mode: DrawMode,
+    /// The sprite drawn by `DXY0` in standard mode.
+    pub large_sprite: LargeSprite,
+    /// If `true`, `FX0A` waits for a key to be pressed and then released, like the COSMAC VIP
+    /// interpreter. The first key released after being pressed during the wait is stored.
+    /// Otherwise, `FX0A` completes as soon as a key is pressed. In both cases, keys that were
+    /// already held when `FX0A` started are ignored.
+    pub wait_for_key_release: bool,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl Quirks {
+    /// Constructs a new `Quirks` with the default behaviours.
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        Quirks {
+            clear_on_mode_switch: false,
+            half_scroll_in_standard_mode: false,
+            count_collided_rows: false,
+            draw_mode: DrawMode::Wrap,
+            large_sprite: LargeSprite::Empty,
+            wait_for_key_release: false,
+        }
+    }
+
+    /// Constructs a new `Quirks` that matches the SCHIP 1.1 interpreter on the HP48. Legacy SCHIP
+    /// games expect these behaviours.
+    pub fn schip_legacy() -> Self {
+        Quirks {
+            clear_on_mode_switch: false,
+            half_scroll_in_standard_mode: true,
+            count_collided_rows: true,
+            draw_mode: DrawMode::Clip,
+            large_sprite: LargeSprite::Tall,
+            wait_for_key_release: false,
+        }
+    }
+
+    /// Constructs a new `Quirks` that matches modern SCHIP interpreters such as Octo.
+    pub fn schip_modern() -> Self {
+        Quirks {
+            clear_on_mode_switch: true,
+            half_scroll_in_standard_mode: false,
+            count_collided_rows: false,
+            draw_mode: DrawMode::Clip,
+            large_sprite: LargeSprite::Square,
+            wait_for_key_release: false,
+        }
+    }
+}
+
+impl Quirks {
+    /// The names of the presets accepted by `Quirks::from_preset`.
+    pub const PRESETS: [&'static str; 3] = ["chip-8", "schip-legacy", "schip-modern"];
+
+    /// Returns the preset named `name`, which is one of `Quirks::PRESETS`. `chip-8` is
+    /// `Quirks::new`, `schip-legacy` is `Quirks::schip_legacy`, and `schip-modern` is
+    /// `Quirks::schip_modern`.
+    pub fn from_preset(name: &str) -> Option<Self> {
+        match name {
+            "chip-8" => Some(Quirks::new()),
+            "schip-legacy" => Some(Quirks::schip_legacy()),
+            "schip-modern" => Some(Quirks::schip_modern()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
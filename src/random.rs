@@ -0,0 +1,49 @@
+// A xorshift generator for `CXNN`. Its state is small enough to be part of save states, and it can
+// be seeded so that runs are reproducible.
+
+// The state that replaces a seed of `0`, which xorshift never leaves.
+const ZERO_SEED_STATE: u32 = 0x9E37_79B9;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct XorShift(u32);
+
+impl XorShift {
+    pub fn new(seed: u32) -> Self {
+        XorShift(if seed == 0 { ZERO_SEED_STATE } else { seed })
+    }
+
+    // Restores a generator from `state`, which is `None` if the state is not reachable.
+    pub fn from_state(state: u32) -> Option<Self> {
+        if state == 0 {
+            None
+        } else {
+            Some(XorShift(state))
+        }
+    }
+
+    pub fn state(self) -> u32 {
+        self.0
+    }
+
+    pub fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xorshift() {
+        let mut random = XorShift::new(1);
+        assert_eq!(random.next(), 0x0004_2021);
+        assert_eq!(random.next(), 0x0408_0601);
+        assert_eq!(XorShift::new(0), XorShift::new(ZERO_SEED_STATE));
+        assert_eq!(XorShift::from_state(0), None);
+        assert_eq!(XorShift::from_state(7).map(XorShift::state), Some(7));
+    }
+}
@@ -0,0 +1,642 @@
+use std::error;
+use std::fmt;
+
+use crate::json::Json;
+use crate::{Chip8, Error, Instruction, REGISTER_COUNT};
+
+const REQUIRED_COLUMNS: [&str; REGISTER_COUNT + 4] = [
+    "pc", "opcode", "v0", "v1", "v2", "v3", "v4", "v5", "v6", "v7", "v8", "v9", "va", "vb", "vc",
+    "vd", "ve", "vf", "i", "sp",
+];
+
+/// An error that occurred while parsing a reference trace.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceParseError {
+    /// The line of the trace where the error occurred, starting from `1`.
+    pub line: usize,
+    /// A description of the error.
+    pub message: String,
+}
+
+impl fmt::Display for TraceParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for TraceParseError {}
+
+/// The state of another emulator before it executed an instruction.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReferenceStep {
+    /// The address of the instruction.
+    pub pc: u16,
+    /// The opcode of the instruction.
+    pub opcode: u16,
+    /// The registers `V0` to `VF`.
+    pub registers: [u8; REGISTER_COUNT],
+    /// The index register.
+    pub index: u16,
+    /// The stack pointer.
+    pub stack_pointer: u16,
+    /// The delay timer, if the trace records it.
+    pub delay_timer: Option<u8>,
+    /// The sound timer, if the trace records it.
+    pub sound_timer: Option<u8>,
+    /// Addresses and the bytes expected at them.
+    pub memory: Vec<(u16, u8)>,
+}
+
+/// A value of `Chip8` that differs from a reference step.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Difference {
+    /// The program counter is `actual` instead of `expected`.
+    ProgramCounter {
+        /// The value in the trace.
+        expected: u16,
+        /// The value in `Chip8`.
+        actual: u16,
+    },
+    /// The opcode at the program counter is `actual` instead of `expected`.
+    Opcode {
+        /// The value in the trace.
+        expected: u16,
+        /// The value in `Chip8`.
+        actual: u16,
+    },
+    /// The register `VX` is `actual` instead of `expected`.
+    Register {
+        /// The index of the register.
+        register: usize,
+        /// The value in the trace.
+        expected: u8,
+        /// The value in `Chip8`.
+        actual: u8,
+    },
+    /// The index register is `actual` instead of `expected`.
+    Index {
+        /// The value in the trace.
+        expected: u16,
+        /// The value in `Chip8`.
+        actual: u16,
+    },
+    /// The stack pointer is `actual` instead of `expected`.
+    StackPointer {
+        /// The value in the trace.
+        expected: u16,
+        /// The value in `Chip8`.
+        actual: u16,
+    },
+    /// The delay timer is `actual` instead of `expected`.
+    DelayTimer {
+        /// The value in the trace.
+        expected: u8,
+        /// The value in `Chip8`.
+        actual: u8,
+    },
+    /// The sound timer is `actual` instead of `expected`.
+    SoundTimer {
+        /// The value in the trace.
+        expected: u8,
+        /// The value in `Chip8`.
+        actual: u8,
+    },
+    /// The byte at `address` is `actual` instead of `expected`.
+    Memory {
+        /// The address of the byte.
+        address: u16,
+        /// The value in the trace.
+        expected: u8,
+        /// The value in `Chip8`.
+        actual: u8,
+    },
+    /// Executing the previous instruction returned an error.
+    Error(Error),
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::ProgramCounter { expected, actual } => {
+                write!(f, "PC is 0x{:03X}, expected 0x{:03X}", actual, expected)
+            }
+            Difference::Opcode { expected, actual } => {
+                write!(f, "opcode is 0x{:04X}, expected 0x{:04X}", actual, expected)
+            }
+            Difference::Register {
+                register,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "V{:X} is 0x{:02X}, expected 0x{:02X}",
+                register, actual, expected
+            ),
+            Difference::Index { expected, actual } => {
+                write!(f, "I is 0x{:03X}, expected 0x{:03X}", actual, expected)
+            }
+            Difference::StackPointer { expected, actual } => {
+                write!(f, "SP is {}, expected {}", actual, expected)
+            }
+            Difference::DelayTimer { expected, actual } => {
+                write!(f, "DT is {}, expected {}", actual, expected)
+            }
+            Difference::SoundTimer { expected, actual } => {
+                write!(f, "ST is {}, expected {}", actual, expected)
+            }
+            Difference::Memory {
+                address,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "memory at 0x{:03X} is 0x{:02X}, expected 0x{:02X}",
+                address, actual, expected
+            ),
+            Difference::Error(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// The first step where `Chip8` diverged from a reference trace.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Divergence {
+    /// The index of the step in the trace, starting from `0`.
+    pub step: usize,
+    /// The address of the previous instruction, which is usually the one that behaves
+    /// differently.
+    pub previous_pc: Option<u16>,
+    /// Every value that differs at this step.
+    pub differences: Vec<Difference>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "step {}", self.step)?;
+        if let Some(pc) = self.previous_pc {
+            write!(f, " after the instruction at 0x{:03X}", pc)?;
+        }
+        let differences: Vec<String> = self.differences.iter().map(|d| d.to_string()).collect();
+        write!(f, ": {}", differences.join(", "))
+    }
+}
+
+/// A trace of another emulator that `Chip8` can be replayed against in lockstep.
+///
+/// Each step is the state of the emulator before it executed an instruction. Traces are CSV files
+/// with a header row or JSON, either an array of objects or one object per line, with the
+/// following columns or keys:
+///
+/// - `pc`, `opcode`, `v0` to `vf`, `i`, and `sp`, which are required.
+/// - `dt` and `st`, which are compared if they are present.
+/// - `memory`, which lists bytes that are compared, such as `300=0C 301=FF` in CSV or
+///   `{"300": 12, "301": 255}` in JSON.
+///
+/// Values are hex in CSV and in JSON strings, with an optional `0x` prefix, and decimal in JSON
+/// numbers.
+///
+/// ```text
+/// pc,opcode,v0,v1,v2,v3,v4,v5,v6,v7,v8,v9,va,vb,vc,vd,ve,vf,i,sp
+/// 200,6A12,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0
+/// 202,A345,0,0,0,0,0,0,0,0,0,0,12,0,0,0,0,0,0,0
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReferenceTrace {
+    steps: Vec<ReferenceStep>,
+}
+
+impl ReferenceTrace {
+    /// Constructs a trace from `steps`.
+    pub fn new(steps: Vec<ReferenceStep>) -> Self {
+        ReferenceTrace { steps }
+    }
+
+    /// Returns the steps of the trace.
+    pub fn steps(&self) -> &[ReferenceStep] {
+        &self.steps
+    }
+
+    /// Parses a CSV trace.
+    pub fn parse_csv(text: &str) -> Result<Self, TraceParseError> {
+        let mut columns: Option<Vec<String>> = None;
+        let mut steps = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| TraceParseError {
+                line: index + 1,
+                message,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+            let header = match &columns {
+                Some(header) => header,
+                None => {
+                    let header: Vec<String> =
+                        cells.iter().map(|cell| cell.to_lowercase()).collect();
+                    if let Some(column) = REQUIRED_COLUMNS
+                        .iter()
+                        .find(|column| !header.iter().any(|name| name == *column))
+                    {
+                        return Err(error(format!("Missing column: {}", column)));
+                    }
+                    columns = Some(header);
+                    continue;
+                }
+            };
+            if cells.len() != header.len() {
+                return Err(error(format!(
+                    "Expected {} columns, found {}",
+                    header.len(),
+                    cells.len()
+                )));
+            }
+
+            let mut step = ReferenceStep::default();
+            for (name, cell) in header.iter().zip(cells) {
+                if name == "memory" {
+                    for entry in cell.split_whitespace() {
+                        let (address, value) = entry
+                            .split_once('=')
+                            .ok_or_else(|| error(format!("Expected `address=byte`: {}", entry)))?;
+                        step.memory.push((
+                            parse_hex(address).map_err(&error)?,
+                            parse_hex(value).map_err(&error)?,
+                        ));
+                    }
+                } else {
+                    set_field(&mut step, name, parse_hex(cell).map_err(&error)?).map_err(&error)?;
+                }
+            }
+            steps.push(step);
+        }
+        Ok(ReferenceTrace { steps })
+    }
+
+    /// Parses a JSON trace, which is either an array of steps or one step per line.
+    pub fn parse_json(text: &str) -> Result<Self, TraceParseError> {
+        let mut steps = Vec::new();
+        if text.trim_start().starts_with('[') {
+            let error = |message: String| TraceParseError { line: 1, message };
+            let values = Json::parse(text).map_err(error)?;
+            for value in values.as_array().unwrap_or(&[]) {
+                steps.push(parse_json_step(value).map_err(error)?);
+            }
+        } else {
+            for (index, line) in text.lines().enumerate() {
+                let error = |message: String| TraceParseError {
+                    line: index + 1,
+                    message,
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let value = Json::parse(line).map_err(error)?;
+                steps.push(parse_json_step(&value).map_err(error)?);
+            }
+        }
+        Ok(ReferenceTrace { steps })
+    }
+
+    /// Runs `chip_8` in lockstep with the trace and returns the number of steps that matched, or
+    /// the first step that diverged. The timers are decremented every `cycles_per_frame`
+    /// instructions. The random numbers of `CXNN` are taken from the trace, so that only the
+    /// masking and the destination register are compared.
+    pub fn replay(&self, chip_8: &mut Chip8, cycles_per_frame: usize) -> Result<usize, Divergence> {
+        let cycles_per_frame = cycles_per_frame.max(1);
+        let mut previous_pc = None;
+        for (i, step) in self.steps.iter().enumerate() {
+            let differences = compare(chip_8, step);
+            if !differences.is_empty() {
+                return Err(Divergence {
+                    step: i,
+                    previous_pc,
+                    differences,
+                });
+            }
+            let next = match self.steps.get(i + 1) {
+                Some(next) => next,
+                None => break,
+            };
+
+            previous_pc = Some(chip_8.pc);
+            if let Err(error) = chip_8.step() {
+                return Err(Divergence {
+                    step: i + 1,
+                    previous_pc,
+                    differences: vec![Difference::Error(error)],
+                });
+            }
+            if let Some(Instruction::Random(x, byte)) = Instruction::decode(step.opcode) {
+                chip_8.registers[x] = next.registers[x] & byte;
+            }
+            if (i + 1) % cycles_per_frame == 0 {
+                chip_8.decrement_timers();
+            }
+        }
+        Ok(self.steps.len())
+    }
+}
+
+fn parse_hex<T: TryFromHex>(text: &str) -> Result<T, String> {
+    let hex = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    T::from_hex(hex).ok_or_else(|| format!("Invalid hex value: {}", text))
+}
+
+trait TryFromHex: Sized {
+    fn from_hex(hex: &str) -> Option<Self>;
+}
+
+impl TryFromHex for u8 {
+    fn from_hex(hex: &str) -> Option<Self> {
+        u8::from_str_radix(hex, 16).ok()
+    }
+}
+
+impl TryFromHex for u16 {
+    fn from_hex(hex: &str) -> Option<Self> {
+        u16::from_str_radix(hex, 16).ok()
+    }
+}
+
+// Sets the field of `step` named `name`, which is a column of the trace.
+fn set_field(step: &mut ReferenceStep, name: &str, value: u16) -> Result<(), String> {
+    let byte = || {
+        if value <= u8::MAX as u16 {
+            Ok(value as u8)
+        } else {
+            Err(format!("{} does not fit in a byte: 0x{:X}", name, value))
+        }
+    };
+    match name {
+        "pc" => step.pc = value,
+        "opcode" => step.opcode = value,
+        "i" => step.index = value,
+        "sp" => step.stack_pointer = value,
+        "dt" => step.delay_timer = Some(byte()?),
+        "st" => step.sound_timer = Some(byte()?),
+        _ => {
+            let register = name
+                .strip_prefix('v')
+                .filter(|register| register.len() == 1)
+                .and_then(|register| usize::from_str_radix(register, 16).ok());
+            // Other columns are ignored, so that traces can carry extra information.
+            if let Some(register) = register {
+                step.registers[register] = byte()?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn json_value(value: &Json) -> Result<u16, String> {
+    match value {
+        Json::Number(_) => value
+            .as_u64()
+            .filter(|value| *value <= u16::MAX as u64)
+            .map(|value| value as u16)
+            .ok_or_else(|| format!("Invalid value: {}", value)),
+        Json::String(text) => parse_hex(text),
+        _ => Err(format!("Invalid value: {}", value)),
+    }
+}
+
+fn parse_json_step(value: &Json) -> Result<ReferenceStep, String> {
+    let members = match value {
+        Json::Object(members) => members,
+        _ => return Err(String::from("Expected an object")),
+    };
+    let mut step = ReferenceStep::default();
+    for column in REQUIRED_COLUMNS.iter() {
+        if !members
+            .iter()
+            .any(|(name, _)| name.to_lowercase() == *column)
+        {
+            return Err(format!("Missing key: {}", column));
+        }
+    }
+    for (name, value) in members {
+        let name = name.to_lowercase();
+        if name == "memory" {
+            let entries = match value {
+                Json::Object(entries) => entries,
+                _ => return Err(String::from("Expected memory to be an object")),
+            };
+            for (address, byte) in entries {
+                let byte = json_value(byte)?;
+                if byte > u8::MAX as u16 {
+                    return Err(format!("Memory value does not fit in a byte: {}", byte));
+                }
+                step.memory.push((parse_hex(address)?, byte as u8));
+            }
+        } else {
+            set_field(&mut step, &name, json_value(value)?)?;
+        }
+    }
+    Ok(step)
+}
+
+fn compare(chip_8: &Chip8, step: &ReferenceStep) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    if chip_8.pc != step.pc {
+        differences.push(Difference::ProgramCounter {
+            expected: step.pc,
+            actual: chip_8.pc,
+        });
+    } else {
+        let memory = chip_8.memory();
+        let pc = chip_8.pc as usize;
+        if let (Some(high), Some(low)) = (memory.get(pc), memory.get(pc + 1)) {
+            let opcode = u16::from_be_bytes([*high, *low]);
+            if opcode != step.opcode {
+                differences.push(Difference::Opcode {
+                    expected: step.opcode,
+                    actual: opcode,
+                });
+            }
+        }
+    }
+    for (register, (expected, actual)) in step.registers.iter().zip(&chip_8.registers).enumerate() {
+        if expected != actual {
+            differences.push(Difference::Register {
+                register,
+                expected: *expected,
+                actual: *actual,
+            });
+        }
+    }
+    if chip_8.index != step.index {
+        differences.push(Difference::Index {
+            expected: step.index,
+            actual: chip_8.index,
+        });
+    }
+    if chip_8.sp != step.stack_pointer {
+        differences.push(Difference::StackPointer {
+            expected: step.stack_pointer,
+            actual: chip_8.sp,
+        });
+    }
+    match step.delay_timer {
+        Some(expected) if expected != chip_8.delay_timer => {
+            differences.push(Difference::DelayTimer {
+                expected,
+                actual: chip_8.delay_timer,
+            })
+        }
+        _ => {}
+    }
+    match step.sound_timer {
+        Some(expected) if expected != chip_8.sound_timer => {
+            differences.push(Difference::SoundTimer {
+                expected,
+                actual: chip_8.sound_timer,
+            })
+        }
+        _ => {}
+    }
+    for (address, expected) in &step.memory {
+        let actual = chip_8.memory().get(*address as usize).copied().unwrap_or(0);
+        if actual != *expected {
+            differences.push(Difference::Memory {
+                address: *address,
+                expected: *expected,
+                actual,
+            });
+        }
+    }
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 200: LD V1, 0x81; 202: LD V2, 0x05; 204: SHR V1, V2; 206: LD I, 0x300; 208: LD [I], V1;
+    // 20A: JP 0x20A
+    const ROM: [u8; 12] = [
+        0x61, 0x81, 0x62, 0x05, 0x81, 0x26, 0xA3, 0x00, 0xF1, 0x55, 0x12, 0x0A,
+    ];
+
+    // A trace of an interpreter that shifts VX in place and leaves I unchanged after FX55.
+    const CSV: &str = "\
+        # shift in place\n\
+        pc,opcode,v0,v1,v2,v3,v4,v5,v6,v7,v8,v9,va,vb,vc,vd,ve,vf,i,sp,memory\n\
+        200,6181,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,\n\
+        202,6205,0,81,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,\n\
+        204,8126,0,81,5,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,\n\
+        206,A300,0,40,5,0,0,0,0,0,0,0,0,0,0,0,0,1,0,0,\n\
+        208,F155,0,40,5,0,0,0,0,0,0,0,0,0,0,0,0,1,300,0,\n\
+        20A,120A,0,40,5,0,0,0,0,0,0,0,0,0,0,0,0,1,300,0,300=00 301=40\n";
+
+    // A trace of the COSMAC VIP interpreter, where shifts read VY and FX55 increments I.
+    const VIP_CSV: &str = "\
+        pc,opcode,v0,v1,v2,v3,v4,v5,v6,v7,v8,v9,va,vb,vc,vd,ve,vf,i,sp\n\
+        200,6181,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0\n\
+        202,6205,0,81,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0\n\
+        204,8126,0,81,5,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0\n\
+        206,A300,0,2,5,0,0,0,0,0,0,0,0,0,0,0,0,1,0,0\n";
+
+    #[test]
+    fn test_parse() {
+        let csv = ReferenceTrace::parse_csv(CSV).unwrap();
+        assert_eq!(csv.steps().len(), 6);
+        assert_eq!(csv.steps()[2].registers[2], 0x05);
+        assert_eq!(csv.steps()[5].memory, vec![(0x300, 0x00), (0x301, 0x40)]);
+
+        let json = ReferenceTrace::parse_json(
+            "{\"pc\":512,\"opcode\":\"6181\",\"v0\":0,\"v1\":0,\"v2\":0,\"v3\":0,\"v4\":0,\"v5\":0,\
+             \"v6\":0,\"v7\":0,\"v8\":0,\"v9\":0,\"va\":0,\"vb\":0,\"vc\":0,\"vd\":0,\"ve\":0,\
+             \"vf\":0,\"i\":0,\"sp\":0,\"dt\":3,\"memory\":{\"0x300\":\"FF\"}}\n",
+        )
+        .unwrap();
+        let step = &json.steps()[0];
+        assert_eq!((step.pc, step.opcode), (0x200, 0x6181));
+        assert_eq!(step.delay_timer, Some(3));
+        assert_eq!(step.memory, vec![(0x300, 0xFF)]);
+        assert_eq!(
+            ReferenceTrace::parse_json("[]"),
+            Ok(ReferenceTrace::default())
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            ReferenceTrace::parse_csv("pc,opcode\n"),
+            Err(TraceParseError {
+                line: 1,
+                message: String::from("Missing column: v0")
+            })
+        );
+        assert_eq!(
+            ReferenceTrace::parse_csv(&CSV.replace("20A,120A", "20A,120A,1")),
+            Err(TraceParseError {
+                line: 8,
+                message: String::from("Expected 21 columns, found 22")
+            })
+        );
+        assert_eq!(
+            ReferenceTrace::parse_csv(&CSV.replace("202,6205,0,81", "202,6205,0,181")),
+            Err(TraceParseError {
+                line: 4,
+                message: String::from("v1 does not fit in a byte: 0x181")
+            })
+        );
+        assert_eq!(
+            ReferenceTrace::parse_json("\n{\"pc\":1}"),
+            Err(TraceParseError {
+                line: 2,
+                message: String::from("Missing key: opcode")
+            })
+        );
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&ROM, false);
+        let trace = ReferenceTrace::parse_csv(CSV).unwrap();
+        assert_eq!(trace.replay(&mut chip_8, 10), Ok(6));
+
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&ROM, false);
+        let trace = ReferenceTrace::parse_csv(VIP_CSV).unwrap();
+        let divergence = trace.replay(&mut chip_8, 10).unwrap_err();
+        assert_eq!(
+            divergence,
+            Divergence {
+                step: 3,
+                previous_pc: Some(0x204),
+                differences: vec![Difference::Register {
+                    register: 1,
+                    expected: 0x02,
+                    actual: 0x40
+                }],
+            }
+        );
+        assert_eq!(
+            divergence.to_string(),
+            "step 3 after the instruction at 0x204: V1 is 0x40, expected 0x02"
+        );
+
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&[0x50, 0x01, 0x00, 0x00], false);
+        let trace = ReferenceTrace::new(vec![
+            ReferenceStep {
+                pc: 0x200,
+                opcode: 0x5001,
+                ..ReferenceStep::default()
+            };
+            2
+        ]);
+        assert_eq!(
+            trace.replay(&mut chip_8, 10).unwrap_err().differences,
+            vec![Difference::Error(Error::UnknownOpcode(0x5001))]
+        );
+    }
+}
@@ -1,85 +1,277 @@
 const STANDARD_SCREEN_HEIGHT: usize = 32;
 const STANDARD_SCREEN_WIDTH: usize = 64;
-const SUPER_SCREEN_HEIGHT: usize = STANDARD_SCREEN_HEIGHT * 2;
-const SUPER_SCREEN_WIDTH: usize = STANDARD_SCREEN_WIDTH * 2;
+pub const SUPER_SCREEN_HEIGHT: usize = STANDARD_SCREEN_HEIGHT * 2;
+pub const SUPER_SCREEN_WIDTH: usize = STANDARD_SCREEN_WIDTH * 2;
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// The resolution of the screen.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum ScreenMode {
+    /// The 64x32 resolution of the original chip-8.
     Standard,
+    /// The 128x64 resolution of the SCHIP.
     Super,
 }
 
+/// A rectangular area of the screen that has changed since the dirty regions were last taken.
+/// All values are in pixels of the screen mode that was active when the regions were taken.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DirtyRegion {
+    /// The column of the left edge of the region.
+    pub x: usize,
+    /// The row of the top edge of the region.
+    pub y: usize,
+    /// The width of the region.
+    pub width: usize,
+    /// The height of the region.
+    pub height: usize,
+}
+
 pub struct Screen {
     mode: ScreenMode,
-    pixels: [u8; SUPER_SCREEN_HEIGHT * SUPER_SCREEN_WIDTH / 8],
+    rows: [u128; SUPER_SCREEN_HEIGHT],
+    dirty_spans: [Option<(usize, usize)>; SUPER_SCREEN_HEIGHT],
+}
+
+// Spreads the lowest 16 bits of `bits` so that every bit is repeated twice.
+fn double_bits(bits: u16) -> u32 {
+    let mut bits = u32::from(bits);
+    bits = (bits | (bits << 8)) & 0x00FF_00FF;
+    bits = (bits | (bits << 4)) & 0x0F0F_0F0F;
+    bits = (bits | (bits << 2)) & 0x3333_3333;
+    bits = (bits | (bits << 1)) & 0x5555_5555;
+    bits | (bits << 1)
 }
 
+// The screen is always stored as a `SUPER_SCREEN_WIDTH` by `SUPER_SCREEN_HEIGHT` buffer. In standard
+// mode, every pixel is drawn as a 2x2 block so that the buffer looks the same as it would on a
+// SCHIP. This keeps the contents of the screen well-defined when the mode changes. All coordinates
+// given to the public methods are in pixels of the current mode.
+//
+// Each row of the buffer is a single `u128` where bit `i` is the pixel in column `i`, so sprites
+// and scrolls operate on entire rows at once.
 impl Screen {
     pub fn new() -> Self {
         Screen {
             mode: ScreenMode::Standard,
-            pixels: [0; SUPER_SCREEN_HEIGHT * SUPER_SCREEN_WIDTH / 8],
+            rows: [0; SUPER_SCREEN_HEIGHT],
+            dirty_spans: [None; SUPER_SCREEN_HEIGHT],
         }
     }
 
-    pub fn get_pixel(&self, row: usize, col: usize) -> bool {
-        let index = row * self.width() + col;
-        let byte_index = index / 8;
-        let bit_index = index % 8;
-        self.pixels[byte_index] & (1 << bit_index) != 0
+    pub fn scale(&self) -> usize {
+        match self.mode {
+            ScreenMode::Standard => 2,
+            ScreenMode::Super => 1,
+        }
     }
 
-    pub fn flip_pixel(&mut self, row: usize, col: usize) {
-        let index = row * self.width() + col;
-        let byte_index = index / 8;
-        let bit_index = index % 8;
-        self.pixels[byte_index] ^= 1 << bit_index;
+    fn mark_dirty(&mut self, row: usize, start: usize, end: usize) {
+        self.dirty_spans[row] = match self.dirty_spans[row] {
+            Some((prev_start, prev_end)) => Some((prev_start.min(start), prev_end.max(end))),
+            None => Some((start, end)),
+        };
     }
 
-    pub fn scroll_down(&mut self, rows: usize) {
-        let row_bytes = self.width() / 8;
-        for row in (0..self.height()).rev() {
-            for col in 0..row_bytes {
-                let index = row * row_bytes + col;
-                if index >= row_bytes * rows {
-                    self.pixels[index] = self.pixels[index - row_bytes * rows];
-                } else {
-                    self.pixels[index] = 0;
-                }
-            }
+    fn mark_all_dirty(&mut self) {
+        for row in 0..SUPER_SCREEN_HEIGHT {
+            self.mark_dirty(row, 0, SUPER_SCREEN_WIDTH);
         }
     }
 
-    pub fn scroll_right(&mut self) {
-        let row_bytes = self.width() / 8;
+    fn mark_row_changed(&mut self, row: usize, diff: u128) {
+        if diff != 0 {
+            let start = diff.trailing_zeros() as usize;
+            let end = SUPER_SCREEN_WIDTH - diff.leading_zeros() as usize;
+            self.mark_dirty(row, start, end);
+        }
+    }
+
+    // Marks every pixel that differs from `previous` as dirty. Used by operations that move large
+    // parts of the screen at once.
+    fn mark_changed(&mut self, previous: &[u128; SUPER_SCREEN_HEIGHT]) {
+        for (row, previous_row) in previous.iter().enumerate() {
+            let diff = self.rows[row] ^ previous_row;
+            self.mark_row_changed(row, diff);
+        }
+    }
+
+    /// Returns the regions of the screen that have changed since the last call and resets the
+    /// dirty state. Consecutive changed rows are merged into a single region.
+    pub fn take_dirty_regions(&mut self) -> Vec<DirtyRegion> {
+        let scale = self.scale();
+        let mut regions = Vec::new();
+        let mut current: Option<DirtyRegion> = None;
+
         for row in 0..self.height() {
-            for col in (1..row_bytes).rev() {
-                self.pixels[row * row_bytes + col] <<= 4;
-                self.pixels[row * row_bytes + col] |= self.pixels[row * row_bytes + col - 1] >> 4;
+            let mut span: Option<(usize, usize)> = None;
+            for buffer_row in row * scale..(row + 1) * scale {
+                if let Some((start, end)) = self.dirty_spans[buffer_row].take() {
+                    let (start, end) = (start / scale, (end + scale - 1) / scale);
+                    span = match span {
+                        Some((prev_start, prev_end)) => {
+                            Some((prev_start.min(start), prev_end.max(end)))
+                        }
+                        None => Some((start, end)),
+                    };
+                }
+            }
+
+            match (span, current.as_mut()) {
+                (Some((start, end)), Some(region)) => {
+                    let region_end = (region.x + region.width).max(end);
+                    region.x = region.x.min(start);
+                    region.width = region_end - region.x;
+                    region.height += 1;
+                }
+                (Some((start, end)), None) => {
+                    current = Some(DirtyRegion {
+                        x: start,
+                        y: row,
+                        width: end - start,
+                        height: 1,
+                    });
+                }
+                (None, _) => {
+                    if let Some(region) = current.take() {
+                        regions.push(region);
+                    }
+                }
             }
-            self.pixels[row * row_bytes] <<= 4;
         }
+
+        if let Some(region) = current {
+            regions.push(region);
+        }
+
+        regions
     }
 
-    pub fn scroll_left(&mut self) {
-        let row_bytes = self.width() / 8;
-        for row in 0..self.height() {
-            for col in 0..row_bytes - 1 {
-                self.pixels[row * row_bytes + col] >>= 4;
-                self.pixels[row * row_bytes + col] |= self.pixels[row * row_bytes + col + 1] << 4;
+    // Draws one row of a sprite by XORing it onto the screen at `row` and `col`. The lowest
+    // `width` bits of `bits` are the pixels of the row, with the most significant bit on the left.
+    // Pixels past the right edge of the screen wrap around if `should_wrap` is `true`, and are
+    // clipped otherwise. Returns `true` if any pixel that was set is unset.
+    pub fn draw_sprite_row(
+        &mut self,
+        row: usize,
+        col: usize,
+        bits: u16,
+        width: usize,
+        should_wrap: bool,
+    ) -> bool {
+        let scale = self.scale();
+        let line = u32::from(bits.reverse_bits() >> (16 - width));
+        let line = if scale == 2 {
+            double_bits(line as u16)
+        } else {
+            line
+        };
+        let mask = if should_wrap {
+            u128::from(line).rotate_left((col * scale) as u32)
+        } else {
+            u128::from(line) << (col * scale)
+        };
+
+        let mut has_collided = false;
+        for buffer_row in row * scale..(row + 1) * scale {
+            has_collided |= self.rows[buffer_row] & mask != 0;
+            self.rows[buffer_row] ^= mask;
+            self.mark_row_changed(buffer_row, mask);
+        }
+        has_collided
+    }
+
+    // Scrolls the buffer down by `rows` buffer rows.
+    fn shift_down(&mut self, rows: usize) {
+        let previous = self.rows;
+        let rows = rows.min(SUPER_SCREEN_HEIGHT);
+        self.rows.copy_within(0..SUPER_SCREEN_HEIGHT - rows, rows);
+        for row in self.rows[..rows].iter_mut() {
+            *row = 0;
+        }
+        self.mark_changed(&previous);
+    }
+
+    // Scrolls the buffer right by `cols` buffer columns if `cols` is positive, and left otherwise.
+    fn shift_horizontal(&mut self, cols: isize) {
+        let previous = self.rows;
+        for row in self.rows.iter_mut() {
+            if cols >= 0 {
+                *row <<= cols;
+            } else {
+                *row >>= -cols;
             }
-            self.pixels[(row + 1) * row_bytes - 1] >>= 4;
         }
+        self.mark_changed(&previous);
     }
 
-    pub fn clear_screen(&mut self) {
-        for pixel in self.pixels.iter_mut() {
-            *pixel = 0;
+    // Returns the number of buffer pixels that a scroll of one pixel moves. In standard mode, the
+    // SCHIP 1.1 interpreter scrolls by super mode pixels, which is half of the expected distance.
+    fn scroll_scale(&self, is_halved: bool) -> usize {
+        if is_halved {
+            1
+        } else {
+            self.scale()
         }
     }
 
+    // Scrolls the screen down by `rows` pixels of the current mode.
+    pub fn scroll_down(&mut self, rows: usize, is_halved: bool) {
+        self.shift_down(rows * self.scroll_scale(is_halved));
+    }
+
+    // Scrolls the screen right by 4 pixels of the current mode.
+    pub fn scroll_right(&mut self, is_halved: bool) {
+        self.shift_horizontal(4 * self.scroll_scale(is_halved) as isize);
+    }
+
+    // Scrolls the screen left by 4 pixels of the current mode.
+    pub fn scroll_left(&mut self, is_halved: bool) {
+        self.shift_horizontal(-4 * self.scroll_scale(is_halved) as isize);
+    }
+
+    pub fn clear_screen(&mut self) {
+        let previous = self.rows;
+        self.rows = [0; SUPER_SCREEN_HEIGHT];
+        self.mark_changed(&previous);
+    }
+
+    pub fn reset(&mut self) {
+        self.rows = [0; SUPER_SCREEN_HEIGHT];
+        self.mode = ScreenMode::Standard;
+        self.dirty_spans = [None; SUPER_SCREEN_HEIGHT];
+        self.mark_all_dirty();
+    }
+
+    pub fn rows(&self) -> &[u128; SUPER_SCREEN_HEIGHT] {
+        &self.rows
+    }
+
+    // Replaces the mode and contents of the screen, and marks the entire screen as dirty.
+    pub fn restore(&mut self, mode: ScreenMode, rows: [u128; SUPER_SCREEN_HEIGHT]) {
+        self.mode = mode;
+        self.rows = rows;
+        self.dirty_spans = [None; SUPER_SCREEN_HEIGHT];
+        self.mark_all_dirty();
+    }
+
+    // Returns a copy of the buffer with every row in little-endian byte order.
+    pub fn buffer(&self) -> Vec<u8> {
+        self.rows
+            .iter()
+            .flat_map(|row| row.to_le_bytes().to_vec())
+            .collect()
+    }
+
+    // Returns a pointer to the buffer. Since every row is stored as a `u128`, the bytes of the
+    // buffer are only in the documented order on little-endian targets such as WebAssembly.
+    #[cfg(feature = "pointer-api")]
     pub fn pixels(&self) -> *const u8 {
-        self.pixels.as_ptr()
+        self.rows.as_ptr() as *const u8
     }
 
     pub fn width(&self) -> usize {
@@ -96,11 +288,108 @@ impl Screen {
         }
     }
 
-    pub fn get_mode(&mut self) -> ScreenMode {
+    pub fn get_mode(&self) -> ScreenMode {
         self.mode
     }
 
-    pub fn set_mode(&mut self, mode: ScreenMode) {
-        self.mode = mode;
+    // Changes the mode of the screen. The contents of the buffer are kept as is unless
+    // `should_clear` is `true`. The entire screen is always marked as dirty since the resolution
+    // changes.
+    pub fn set_mode(&mut self, mode: ScreenMode, should_clear: bool) {
+        if should_clear {
+            self.rows = [0; SUPER_SCREEN_HEIGHT];
+        }
+        if self.mode != mode || should_clear {
+            self.mode = mode;
+            self.dirty_spans = [None; SUPER_SCREEN_HEIGHT];
+            self.mark_all_dirty();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_double_bits() {
+        assert_eq!(double_bits(0), 0);
+        assert_eq!(double_bits(0b1011), 0b1100_1111);
+        assert_eq!(double_bits(0xFFFF), 0xFFFF_FFFF);
+    }
+
+    #[test]
+    fn test_draw_sprite_row() {
+        let mut screen = Screen::new();
+        screen.set_mode(ScreenMode::Super, false);
+
+        assert!(!screen.draw_sprite_row(0, 124, 0xA5, 8, false));
+        assert_eq!(screen.rows[0], 0b0101 << 124);
+        assert!(screen.draw_sprite_row(0, 124, 0x80, 8, true));
+        assert_eq!(screen.rows[0], 0b0100 << 124);
+
+        assert!(!screen.draw_sprite_row(1, 124, 0xA5, 8, true));
+        assert_eq!(screen.rows[1], (0b0101 << 124) | 0b1010);
+
+        assert!(!screen.draw_sprite_row(2, 0, 0x8001, 16, false));
+        assert_eq!(screen.rows[2], 1 | (1 << 15));
+
+        screen.set_mode(ScreenMode::Standard, true);
+        assert!(!screen.draw_sprite_row(1, 62, 0xC1, 8, true));
+        assert_eq!(screen.rows[2], (0b1111 << 124) | (0b11 << 10));
+        assert_eq!(screen.rows[3], screen.rows[2]);
+    }
+
+    #[test]
+    fn test_dirty_regions() {
+        let mut screen = Screen::new();
+        assert!(screen.take_dirty_regions().is_empty());
+
+        screen.draw_sprite_row(3, 10, 1, 1, false);
+        screen.draw_sprite_row(4, 2, 1, 1, false);
+        screen.draw_sprite_row(6, 5, 1, 1, false);
+        assert_eq!(
+            screen.take_dirty_regions(),
+            vec![
+                DirtyRegion {
+                    x: 2,
+                    y: 3,
+                    width: 9,
+                    height: 2,
+                },
+                DirtyRegion {
+                    x: 5,
+                    y: 6,
+                    width: 1,
+                    height: 1,
+                },
+            ],
+        );
+
+        screen.scroll_down(1, false);
+        assert_eq!(
+            screen.take_dirty_regions(),
+            vec![DirtyRegion {
+                x: 2,
+                y: 3,
+                width: 9,
+                height: 5,
+            }],
+        );
+
+        screen.clear_screen();
+        screen.clear_screen();
+        assert_eq!(screen.take_dirty_regions().len(), 2);
+
+        screen.set_mode(ScreenMode::Super, false);
+        assert_eq!(
+            screen.take_dirty_regions(),
+            vec![DirtyRegion {
+                x: 0,
+                y: 0,
+                width: SUPER_SCREEN_WIDTH,
+                height: SUPER_SCREEN_HEIGHT,
+            }],
+        );
     }
 }
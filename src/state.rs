@@ -0,0 +1,251 @@
+// Save states are a fixed size binary snapshot of everything that a running rom can observe, and
+// of the hash of the loaded rom. All multi-byte values are stored in little-endian. The
+// configuration of the emulator, such as the quirks and key maps, is not part of the state.
+
+use crate::error::Error;
+use crate::random::XorShift;
+use crate::screen::{ScreenMode, SUPER_SCREEN_HEIGHT};
+use crate::{Chip8, MEMORY_SIZE, REGISTER_COUNT, STACK_SIZE, SUPER_MODE_RPL_FLAG_COUNT};
+
+const STATE_MAGIC: &[u8; 4] = b"CHP8";
+const STATE_VERSION: u8 = 3;
+
+/// The size in bytes of a save state returned by [`Chip8::save_state`].
+pub const STATE_SIZE: usize = STATE_MAGIC.len()
+    + 1
+    + MEMORY_SIZE
+    + REGISTER_COUNT
+    + 2
+    + 2
+    + 1
+    + 1
+    + STACK_SIZE * 2
+    + 2
+    + SUPER_MODE_RPL_FLAG_COUNT
+    + 2
+    + 2
+    + 4
+    + 1
+    + 1
+    + SUPER_SCREEN_HEIGHT * 16
+    + 20;
+
+struct StateReader<'a> {
+    state: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        if self.state.len() < length {
+            return Err(Error::InvalidState);
+        }
+        let (ret, rest) = self.state.split_at(length);
+        self.state = rest;
+        Ok(ret)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, Error> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_bool(&mut self) -> Result<bool, Error> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::InvalidState),
+        }
+    }
+}
+
+impl Chip8 {
+    /// Returns a snapshot of the state of the emulator that can be restored with `load_state`.
+    /// The snapshot is always `STATE_SIZE` bytes and does not include the quirks, key map, or
+    /// controller profile.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(STATE_SIZE);
+        state.extend_from_slice(STATE_MAGIC);
+        state.push(STATE_VERSION);
+        state.extend_from_slice(&self.memory);
+        state.extend_from_slice(&self.registers);
+        state.extend_from_slice(&self.index.to_le_bytes());
+        state.extend_from_slice(&self.pc.to_le_bytes());
+        state.push(self.delay_timer);
+        state.push(self.sound_timer);
+        for entry in self.stack.iter() {
+            state.extend_from_slice(&entry.to_le_bytes());
+        }
+        state.extend_from_slice(&self.sp.to_le_bytes());
+        state.extend_from_slice(&self.super_mode_rpl_flags);
+        state.extend_from_slice(&self.keypad.keys().to_le_bytes());
+        state.extend_from_slice(&self.waiting_keys.to_le_bytes());
+        state.extend_from_slice(&self.random.state().to_le_bytes());
+        state.push(self.is_running as u8);
+        state.push(match self.screen.get_mode() {
+            ScreenMode::Standard => 0,
+            ScreenMode::Super => 1,
+        });
+        for row in self.screen.rows().iter() {
+            state.extend_from_slice(&row.to_le_bytes());
+        }
+        state.extend_from_slice(&self.rom_hash);
+        state
+    }
+
+    /// Restores a snapshot returned by `save_state`, including the hash of the rom that was
+    /// loaded. If the snapshot is malformed, an error is returned and the state of the emulator
+    /// is not modified.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), Error> {
+        if state.len() != STATE_SIZE {
+            return Err(Error::InvalidState);
+        }
+
+        let mut reader = StateReader { state };
+        if reader.take(STATE_MAGIC.len())? != STATE_MAGIC || reader.read_u8()? != STATE_VERSION {
+            return Err(Error::InvalidState);
+        }
+
+        let mut memory = [0; MEMORY_SIZE];
+        memory.copy_from_slice(reader.take(MEMORY_SIZE)?);
+        let mut registers = [0; REGISTER_COUNT];
+        registers.copy_from_slice(reader.take(REGISTER_COUNT)?);
+        let index = reader.read_u16()?;
+        let pc = reader.read_u16()?;
+        let delay_timer = reader.read_u8()?;
+        let sound_timer = reader.read_u8()?;
+        let mut stack = [0; STACK_SIZE];
+        for entry in stack.iter_mut() {
+            *entry = reader.read_u16()?;
+        }
+        let sp = reader.read_u16()?;
+        if sp as usize > STACK_SIZE {
+            return Err(Error::InvalidState);
+        }
+        let mut super_mode_rpl_flags = [0; SUPER_MODE_RPL_FLAG_COUNT];
+        super_mode_rpl_flags.copy_from_slice(reader.take(SUPER_MODE_RPL_FLAG_COUNT)?);
+        let keys = reader.read_u16()?;
+        let waiting_keys = reader.read_u16()?;
+        let random = XorShift::from_state(reader.read_u32()?).ok_or(Error::InvalidState)?;
+        let is_running = reader.read_bool()?;
+        let mode = if reader.read_bool()? {
+            ScreenMode::Super
+        } else {
+            ScreenMode::Standard
+        };
+        let mut rows = [0; SUPER_SCREEN_HEIGHT];
+        for row in rows.iter_mut() {
+            let mut bytes = [0; 16];
+            bytes.copy_from_slice(reader.take(16)?);
+            *row = u128::from_le_bytes(bytes);
+        }
+        let mut rom_hash = [0; 20];
+        rom_hash.copy_from_slice(reader.take(20)?);
+
+        self.memory = memory;
+        self.registers = registers;
+        self.index = index;
+        self.pc = pc;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.stack = stack;
+        self.sp = sp;
+        self.super_mode_rpl_flags = super_mode_rpl_flags;
+        self.keypad.set_keys(keys);
+        self.waiting_keys = waiting_keys;
+        self.random = random;
+        self.is_running = is_running;
+        self.screen.restore(mode, rows);
+        self.rom_hash = rom_hash;
+        self.should_draw = true;
+        self.should_beep = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load_state() {
+        let rom = [
+            0x00, 0xFF, 0x6A, 0x05, 0xA2, 0x00, 0xDA, 0xA5, 0x22, 0x0C, 0x12, 0x0A, 0x7A, 0x01,
+            0x00, 0xEE,
+        ];
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&rom, true);
+        for _ in 0..5 {
+            chip_8.execute_cycle();
+        }
+        chip_8.press_hex_key(0x3);
+
+        let state = chip_8.save_state();
+        assert_eq!(state.len(), STATE_SIZE);
+
+        let mut other = Chip8::new();
+        other.load_state(&state).unwrap();
+        assert_eq!(other.save_state(), state);
+        assert_eq!(other.rom_hash(), chip_8.rom_hash());
+        assert_eq!(other.screen_buffer(), chip_8.screen_buffer());
+
+        for _ in 0..3 {
+            chip_8.execute_cycle();
+            other.execute_cycle();
+        }
+        assert_eq!(other.save_state(), chip_8.save_state());
+    }
+
+    #[test]
+    fn test_load_state_restores_random_numbers() {
+        // `CXFF` in a loop.
+        let rom = [0xC0, 0xFF, 0x12, 0x00];
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&rom, true);
+        let state = chip_8.save_state();
+        let run = |chip_8: &mut Chip8| {
+            (0..8)
+                .map(|_| {
+                    chip_8.execute_cycle();
+                    chip_8.execute_cycle();
+                    chip_8.registers[0]
+                })
+                .collect::<Vec<u8>>()
+        };
+        let values = run(&mut chip_8);
+        chip_8.load_state(&state).unwrap();
+        assert_eq!(run(&mut chip_8), values);
+    }
+
+    #[test]
+    fn test_load_invalid_state() {
+        let mut chip_8 = Chip8::new();
+        let state = chip_8.save_state();
+        assert_eq!(chip_8.load_state(&state[1..]), Err(Error::InvalidState));
+
+        let mut bad_magic = state.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(chip_8.load_state(&bad_magic), Err(Error::InvalidState));
+
+        let mut bad_stack_pointer = state.clone();
+        let sp_offset = 5 + MEMORY_SIZE + REGISTER_COUNT + 6 + STACK_SIZE * 2;
+        bad_stack_pointer[sp_offset] = 17;
+        assert_eq!(
+            chip_8.load_state(&bad_stack_pointer),
+            Err(Error::InvalidState)
+        );
+
+        let mut bad_random = state.clone();
+        let random_offset = sp_offset + 2 + SUPER_MODE_RPL_FLAG_COUNT + 4;
+        bad_random[random_offset..random_offset + 4].copy_from_slice(&[0; 4]);
+        assert_eq!(chip_8.load_state(&bad_random), Err(Error::InvalidState));
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+
+/// A line of a source file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourceLocation {
+    /// The path of the source file, as written in the symbol map.
+    pub path: String,
+    /// The line in the source file, starting from `1`.
+    pub line: usize,
+}
+
+/// An error that occurred while parsing a symbol map.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SymbolParseError {
+    /// The line of the symbol map where the error occurred, starting from `1`.
+    pub line: usize,
+    /// A description of the error.
+    pub message: String,
+}
+
+impl fmt::Display for SymbolParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for SymbolParseError {}
+
+/// Maps addresses to the Octo or assembly source lines that produced them, and to labels.
+///
+/// Each line of a symbol map starts with a hex address followed by either `path:line` for the
+/// source line that assembled to that address, or the name of a label at that address. Lines
+/// starting with `#` are comments.
+///
+/// ```text
+/// 0x200 main
+/// 0x200 game.8o:12
+/// 0x202 game.8o:13
+/// 0x204 draw-player
+/// 0x204 game.8o:20
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SymbolMap {
+    locations: BTreeMap<u16, SourceLocation>,
+    labels: BTreeMap<u16, String>,
+}
+
+impl SymbolMap {
+    /// Constructs a new empty `SymbolMap`.
+    pub fn new() -> Self {
+        SymbolMap {
+            locations: BTreeMap::new(),
+            labels: BTreeMap::new(),
+        }
+    }
+
+    /// Parses a symbol map.
+    pub fn parse(text: &str) -> Result<Self, SymbolParseError> {
+        let mut ret = SymbolMap::new();
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| SymbolParseError {
+                line: index + 1,
+                message,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (address, symbol) = match line.split_once(char::is_whitespace) {
+                Some((address, symbol)) => (address, symbol.trim()),
+                None => return Err(error(format!("Expected `address symbol`: {}", line))),
+            };
+            let hex = address
+                .strip_prefix("0x")
+                .or_else(|| address.strip_prefix("0X"))
+                .unwrap_or(address);
+            let address = u16::from_str_radix(hex, 16)
+                .map_err(|_| error(format!("Invalid hex address: {}", address)))?;
+
+            match symbol.rsplit_once(':') {
+                Some((path, line)) if !path.is_empty() && line.parse::<usize>().is_ok() => {
+                    ret.insert_location(address, path, line.parse().unwrap());
+                }
+                _ if symbol.contains(char::is_whitespace) || symbol.contains(':') => {
+                    return Err(error(format!("Invalid symbol: {}", symbol)));
+                }
+                _ => ret.insert_label(address, symbol),
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Sets the source line of `address`.
+    pub fn insert_location(&mut self, address: u16, path: &str, line: usize) {
+        self.locations.insert(
+            address,
+            SourceLocation {
+                path: path.to_string(),
+                line,
+            },
+        );
+    }
+
+    /// Sets the label at `address`.
+    pub fn insert_label(&mut self, address: u16, name: &str) {
+        self.labels.insert(address, name.to_string());
+    }
+
+    /// Returns the source line of `address`.
+    pub fn location(&self, address: u16) -> Option<&SourceLocation> {
+        self.locations.get(&address)
+    }
+
+    /// Returns the label at `address`.
+    pub fn label(&self, address: u16) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    /// Returns the address and name of the closest label at or before `address`, which is usually
+    /// the subroutine that contains it.
+    pub fn enclosing_label(&self, address: u16) -> Option<(u16, &str)> {
+        self.labels
+            .range(..=address)
+            .next_back()
+            .map(|(address, name)| (*address, name.as_str()))
+    }
+
+    /// Returns an iterator over the addresses and their source lines in ascending order of
+    /// address.
+    pub fn locations(&self) -> impl Iterator<Item = (u16, &SourceLocation)> {
+        self.locations
+            .iter()
+            .map(|(address, location)| (*address, location))
+    }
+
+    /// Returns an iterator over the addresses and names of the labels in ascending order of
+    /// address.
+    pub fn labels(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.labels
+            .iter()
+            .map(|(address, name)| (*address, name.as_str()))
+    }
+
+    /// Returns the first line at or after `line` in the source file at `path` that produced any
+    /// addresses, along with those addresses. `path` matches the paths in the symbol map if either
+    /// one is a suffix of the other, so absolute paths match relative paths in the symbol map.
+    pub fn resolve_line(&self, path: &str, line: usize) -> Option<(usize, Vec<u16>)> {
+        let resolved_line = self
+            .locations
+            .values()
+            .filter(|location| location.line >= line && is_same_path(&location.path, path))
+            .map(|location| location.line)
+            .min()?;
+        let addresses = self
+            .locations
+            .iter()
+            .filter(|(_, location)| {
+                location.line == resolved_line && is_same_path(&location.path, path)
+            })
+            .map(|(address, _)| *address)
+            .collect();
+        Some((resolved_line, addresses))
+    }
+}
+
+fn is_same_path(a: &str, b: &str) -> bool {
+    let a = a.replace('\\', "/");
+    let b = b.replace('\\', "/");
+    let a = a.trim_start_matches("./");
+    let b = b.trim_start_matches("./");
+    a == b || a.ends_with(&format!("/{}", b)) || b.ends_with(&format!("/{}", a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let symbols = SymbolMap::parse(
+            "# comment\n0x200 main\n200 game.8o:3\n0x202 game.8o:3\n0x204 sub\n0x204 game.8o:7\n",
+        )
+        .unwrap();
+        assert_eq!(
+            symbols.location(0x202),
+            Some(&SourceLocation {
+                path: String::from("game.8o"),
+                line: 3
+            })
+        );
+        assert_eq!(symbols.label(0x200), Some("main"));
+        assert_eq!(symbols.enclosing_label(0x206), Some((0x204, "sub")));
+        assert_eq!(symbols.enclosing_label(0x100), None);
+        assert_eq!(
+            symbols.resolve_line("/home/user/rom/game.8o", 1),
+            Some((3, vec![0x200, 0x202]))
+        );
+        assert_eq!(symbols.resolve_line("./game.8o", 4), Some((7, vec![0x204])));
+        assert_eq!(symbols.resolve_line("game.8o", 8), None);
+        assert_eq!(symbols.resolve_line("other.8o", 1), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = SymbolMap::parse("0x200\n").unwrap_err();
+        assert_eq!(error.line, 1);
+        assert_eq!(error.message, "Expected `address symbol`: 0x200");
+
+        let error = SymbolMap::parse("\n0xZZZ game.8o:1\n").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "Invalid hex address: 0xZZZ");
+
+        // Labels are single words, and locations need a line number after the colon.
+        assert_eq!(
+            SymbolMap::parse("0x200 two words\n").unwrap_err().message,
+            "Invalid symbol: two words"
+        );
+        assert_eq!(
+            SymbolMap::parse("0x200 game.8o:\n").unwrap_err().message,
+            "Invalid symbol: game.8o:"
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Helpers shared by the terminal frontends.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{
+    KeyCode, KeyEventKind, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+    PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Colors, Print, ResetColor, SetColors};
+use crossterm::{cursor, execute, queue, terminal};
+
+use crate::{Button, Chip8, Palette, TextStyle};
+
+// Most terminals do not report key releases, so keys are released once they have not been
+// repeated for this long. This is longer than the typical delay before keys start repeating.
+const KEY_HOLD_DURATION: Duration = Duration::from_millis(600);
+
+/// Puts the terminal in raw mode on an alternate screen, and restores it when dropped, including
+/// when unwinding from a panic.
+pub struct Terminal {
+    reports_key_releases: bool,
+}
+
+impl Terminal {
+    /// Sets up the terminal. Key release events are requested if the terminal supports them.
+    pub fn new() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        let reports_key_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if reports_key_releases {
+            execute!(
+                io::stdout(),
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+        Ok(Terminal {
+            reports_key_releases,
+        })
+    }
+
+    /// Returns `true` if the terminal reports key releases.
+    pub fn reports_key_releases(&self) -> bool {
+        self.reports_key_releases
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        if self.reports_key_releases {
+            let _ = execute!(stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(
+            stdout,
+            ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Forwards key events to the key map of an emulator, and the arrow keys, space, and enter to the
+/// d-pad, `A`, and start buttons of its controller profile. If the terminal does not report key
+/// releases, keys are released after they have not been repeated for a while.
+pub struct HostKeys {
+    reports_key_releases: bool,
+    release_times: HashMap<HostInput, Instant>,
+}
+
+// A key that is forwarded to the key map, or a key that presses a gamepad button.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum HostInput {
+    Key(String),
+    Button(Button),
+}
+
+impl HostInput {
+    fn from_code(code: KeyCode) -> Option<Self> {
+        let button = match code {
+            KeyCode::Up => Button::Up,
+            KeyCode::Down => Button::Down,
+            KeyCode::Left => Button::Left,
+            KeyCode::Right => Button::Right,
+            KeyCode::Char(' ') => Button::A,
+            KeyCode::Enter => Button::Start,
+            KeyCode::Char(c) => return Some(HostInput::Key(c.to_lowercase().to_string())),
+            _ => return None,
+        };
+        Some(HostInput::Button(button))
+    }
+
+    fn press(&self, chip_8: &mut Chip8) -> bool {
+        match self {
+            HostInput::Key(host_key) => chip_8.press_host_key(host_key),
+            HostInput::Button(button) => chip_8.press_button(*button),
+        }
+    }
+
+    fn release(&self, chip_8: &mut Chip8) -> bool {
+        match self {
+            HostInput::Key(host_key) => chip_8.release_host_key(host_key),
+            HostInput::Button(button) => chip_8.release_button(*button),
+        }
+    }
+}
+
+impl HostKeys {
+    /// Constructs a new `HostKeys` for `terminal`.
+    pub fn new(terminal: &Terminal) -> Self {
+        HostKeys {
+            reports_key_releases: terminal.reports_key_releases(),
+            release_times: HashMap::new(),
+        }
+    }
+
+    /// Presses or releases the hex key bound to `code`, directly or through a button. Returns
+    /// `false` if `code` is not bound.
+    pub fn handle_key(
+        &mut self,
+        chip_8: &mut Chip8,
+        code: KeyCode,
+        kind: KeyEventKind,
+        now: Instant,
+    ) -> bool {
+        let input = match HostInput::from_code(code) {
+            Some(input) => input,
+            None => return false,
+        };
+        if kind == KeyEventKind::Release {
+            self.release_times.remove(&input);
+            return input.release(chip_8);
+        }
+        if !input.press(chip_8) {
+            return false;
+        }
+        if !self.reports_key_releases {
+            self.release_times.insert(input, now + KEY_HOLD_DURATION);
+        }
+        true
+    }
+
+    /// Releases the keys that have not been repeated for a while.
+    pub fn release_expired(&mut self, chip_8: &mut Chip8, now: Instant) {
+        self.release_times.retain(|input, release_time| {
+            if *release_time > now {
+                return true;
+            }
+            input.release(chip_8);
+            false
+        });
+    }
+}
+
+/// Converts a `0xRRGGBB` color to a terminal color.
+pub fn color(rgb: u32) -> Color {
+    Color::Rgb {
+        r: (rgb >> 16) as u8,
+        g: (rgb >> 8) as u8,
+        b: rgb as u8,
+    }
+}
+
+/// Queues the screen of `chip_8` to be drawn with its top left corner at `(col, row)`.
+pub fn queue_screen(
+    out: &mut impl Write,
+    chip_8: &Chip8,
+    style: TextStyle,
+    palette: Palette,
+    (col, row): (u16, u16),
+) -> io::Result<()> {
+    queue!(
+        out,
+        SetColors(Colors::new(
+            color(palette.foreground),
+            color(palette.background)
+        ))
+    )?;
+    for (i, line) in chip_8.screen_text(style).into_iter().enumerate() {
+        queue!(out, cursor::MoveTo(col, row + i as u16), Print(line))?;
+    }
+    queue!(out, ResetColor)
+}
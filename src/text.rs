@@ -0,0 +1,92 @@
+// Renders the screen as lines of Unicode text for terminal frontends.
+
+use crate::Chip8;
+
+/// The characters used to render the screen as text.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TextStyle {
+    /// Each character is a half-block covering one column and two rows of pixels.
+    HalfBlock,
+    /// Each character is a braille pattern covering two columns and four rows of pixels.
+    Braille,
+}
+
+// The bit of a braille pattern for the dot at each row and column of a 2x4 cell.
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+impl Chip8 {
+    fn is_pixel_set(&self, row: usize, col: usize) -> bool {
+        let scale = self.screen.scale();
+        row < self.screen_height()
+            && col < self.screen_width()
+            && self.screen.rows()[row * scale] >> (col * scale) & 1 != 0
+    }
+
+    /// Returns the screen in pixels of the current mode as lines of text. Set pixels are drawn
+    /// with the foreground color of the terminal, and unset pixels are blank.
+    pub fn screen_text(&self, style: TextStyle) -> Vec<String> {
+        let (cell_width, cell_height) = match style {
+            TextStyle::HalfBlock => (1, 2),
+            TextStyle::Braille => (2, 4),
+        };
+
+        let mut ret = Vec::new();
+        for row in (0..self.screen_height()).step_by(cell_height) {
+            let line = (0..self.screen_width())
+                .step_by(cell_width)
+                .map(|col| match style {
+                    TextStyle::HalfBlock => {
+                        match (self.is_pixel_set(row, col), self.is_pixel_set(row + 1, col)) {
+                            (false, false) => ' ',
+                            (true, false) => '\u{2580}',
+                            (false, true) => '\u{2584}',
+                            (true, true) => '\u{2588}',
+                        }
+                    }
+                    TextStyle::Braille => {
+                        let mut pattern = 0x2800;
+                        for (i, dots) in BRAILLE_DOTS.iter().enumerate() {
+                            for (j, dot) in dots.iter().enumerate() {
+                                if self.is_pixel_set(row + i, col + j) {
+                                    pattern |= dot;
+                                }
+                            }
+                        }
+                        std::char::from_u32(pattern).unwrap()
+                    }
+                })
+                .collect();
+            ret.push(line);
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_screen_text() {
+        // Draws the `1` character at (0, 0).
+        let rom = [0x60, 0x01, 0xF0, 0x29, 0x61, 0x00, 0xD1, 0x15, 0x12, 0x08];
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&rom, false);
+        for _ in 0..4 {
+            chip_8.execute_cycle();
+        }
+
+        let lines = chip_8.screen_text(TextStyle::HalfBlock);
+        assert_eq!(lines.len(), 16);
+        assert_eq!(lines[0].chars().count(), 64);
+        assert!(lines[0].starts_with(" \u{2584}\u{2588} "));
+        assert!(lines[1].starts_with("  \u{2588} "));
+        assert!(lines[2].starts_with(" \u{2580}\u{2580}\u{2580} "));
+
+        let lines = chip_8.screen_text(TextStyle::Braille);
+        assert_eq!(lines.len(), 8);
+        assert_eq!(lines[0].chars().count(), 32);
+        assert!(lines[0].starts_with("\u{2810}\u{2847}\u{2800}"));
+        assert!(lines[1].starts_with("\u{2808}\u{2809}\u{2800}"));
+    }
+}
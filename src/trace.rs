@@ -0,0 +1,332 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::json::{json_object, Json};
+use crate::{disassemble, Chip8, StepObserver, REGISTER_COUNT};
+
+const REGISTER_NAMES: [&str; REGISTER_COUNT] = [
+    "V0", "V1", "V2", "V3", "V4", "V5", "V6", "V7", "V8", "V9", "VA", "VB", "VC", "VD", "VE", "VF",
+];
+
+/// The formats that traces can be written in.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TraceFormat {
+    /// One line per instruction with the address, opcode, and mnemonic, followed by the changed
+    /// registers after a `|`, such as `202 A345 LD I, 0x345 | I=345`.
+    Text,
+    /// One JSON object per line with the `address`, `opcode`, `mnemonic`, and `changes` of an
+    /// instruction, such as
+    /// `{"address":514,"opcode":41797,"mnemonic":"LD I, 0x345","changes":{"I":837}}`.
+    JsonLines,
+}
+
+/// An executed instruction and the registers that it changed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceEntry {
+    /// The address of the instruction.
+    pub address: u16,
+    /// The opcode of the instruction.
+    pub opcode: u16,
+    /// The disassembly of the instruction.
+    pub mnemonic: String,
+    /// The names and new values of the registers that changed, in the order `V0` to `VF`, `I`,
+    /// `SP`, `DT`, and `ST`.
+    pub changes: Vec<(&'static str, u16)>,
+}
+
+impl TraceEntry {
+    /// Writes the entry and a newline to `out` in `format`.
+    pub fn write(&self, out: &mut impl Write, format: TraceFormat) -> io::Result<()> {
+        match format {
+            TraceFormat::Text => writeln!(out, "{}", self),
+            TraceFormat::JsonLines => {
+                let changes = self
+                    .changes
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), Json::from(*value)))
+                    .collect();
+                let entry = json_object! {
+                    "address" => self.address,
+                    "opcode" => self.opcode,
+                    "mnemonic" => self.mnemonic.as_str(),
+                    "changes" => Json::Object(changes),
+                };
+                writeln!(out, "{}", entry)
+            }
+        }
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:03X} {:04X} {}",
+            self.address, self.opcode, self.mnemonic
+        )?;
+        for (i, (name, value)) in self.changes.iter().enumerate() {
+            let separator = if i == 0 { " | " } else { " " };
+            match *name {
+                "I" => write!(f, "{}{}={:03X}", separator, name, value)?,
+                "SP" => write!(f, "{}{}={}", separator, name, value)?,
+                _ => write!(f, "{}{}={:02X}", separator, name, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// The registers that are compared between instructions.
+#[derive(Clone, Copy, Eq, PartialEq)]
+struct Snapshot {
+    registers: [u8; REGISTER_COUNT],
+    index: u16,
+    sp: u16,
+    delay_timer: u8,
+    sound_timer: u8,
+}
+
+impl Snapshot {
+    fn new(chip_8: &Chip8) -> Self {
+        Snapshot {
+            registers: chip_8.registers,
+            index: chip_8.index,
+            sp: chip_8.sp,
+            delay_timer: chip_8.delay_timer,
+            sound_timer: chip_8.sound_timer,
+        }
+    }
+
+    fn changes(&self, after: &Snapshot) -> Vec<(&'static str, u16)> {
+        let mut changes: Vec<(&'static str, u16)> = REGISTER_NAMES
+            .iter()
+            .zip(self.registers.iter().zip(after.registers.iter()))
+            .filter(|(_, (before, after))| before != after)
+            .map(|(name, (_, after))| (*name, *after as u16))
+            .collect();
+        let others = [
+            ("I", self.index, after.index),
+            ("SP", self.sp, after.sp),
+            ("DT", self.delay_timer as u16, after.delay_timer as u16),
+            ("ST", self.sound_timer as u16, after.sound_timer as u16),
+        ];
+        for (name, before, after) in others.iter() {
+            if before != after {
+                changes.push((name, *after));
+            }
+        }
+        changes
+    }
+}
+
+/// Records the instructions executed by a `Chip8` into a ring buffer, and optionally streams them
+/// to a writer.
+///
+/// The timers decremented at the end of a frame by [`Chip8::run_frame_observed`] are recorded as
+/// changes of the last instruction of the frame, so each entry is written once the next
+/// instruction is executed or the tracer is flushed.
+pub struct Tracer {
+    entries: VecDeque<TraceEntry>,
+    // The newest entry, which is not written or added to `entries` yet.
+    last: Option<TraceEntry>,
+    // The disassembly and registers before the instruction that is being executed.
+    pending: Option<(String, Snapshot)>,
+    // The registers after the newest entry or frame, which the timers of a frame are compared to.
+    snapshot: Option<Snapshot>,
+    capacity: usize,
+    writer: Option<(Box<dyn Write + Send>, TraceFormat)>,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    /// Constructs a new `Tracer` that keeps the last `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        Tracer {
+            entries: VecDeque::with_capacity(capacity),
+            last: None,
+            pending: None,
+            snapshot: None,
+            capacity,
+            writer: None,
+            error: None,
+        }
+    }
+
+    /// Constructs a new `Tracer` that keeps the last `capacity` entries and also writes every
+    /// entry to `writer` in `format`.
+    pub fn with_writer(
+        capacity: usize,
+        writer: impl Write + Send + 'static,
+        format: TraceFormat,
+    ) -> Self {
+        let mut tracer = Tracer::new(capacity);
+        tracer.writer = Some((Box::new(writer), format));
+        tracer
+    }
+
+    /// Returns an iterator over the recorded entries from oldest to newest.
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        let count = self.entries.len() + self.last.iter().count();
+        self.entries
+            .iter()
+            .chain(&self.last)
+            .skip(count.saturating_sub(self.capacity))
+    }
+
+    /// Removes the recorded entries. Entries that were not written yet are discarded.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.last = None;
+    }
+
+    /// Writes the newest entry and flushes the writer. Writing stops at the first error, which is
+    /// returned here.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.commit_last();
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        match &mut self.writer {
+            Some((writer, _)) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    // Writes the newest entry and moves it to the ring buffer.
+    fn commit_last(&mut self) {
+        let entry = match self.last.take() {
+            Some(entry) => entry,
+            None => return,
+        };
+        if let (None, Some((writer, format))) = (&self.error, &mut self.writer) {
+            if let Err(error) = entry.write(writer, *format) {
+                self.error = Some(error);
+            }
+        }
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+impl StepObserver for Tracer {
+    fn before_step(&mut self, chip_8: &Chip8, address: u16, _opcode: u16) {
+        let mnemonic = disassemble(chip_8.memory(), address as usize);
+        self.pending = Some((mnemonic, Snapshot::new(chip_8)));
+    }
+
+    fn after_step(&mut self, chip_8: &Chip8, address: u16, opcode: u16) {
+        let (mnemonic, before) = match self.pending.take() {
+            Some(pending) => pending,
+            None => return,
+        };
+        let after = Snapshot::new(chip_8);
+        self.commit_last();
+        self.last = Some(TraceEntry {
+            address,
+            opcode,
+            mnemonic,
+            changes: before.changes(&after),
+        });
+        self.snapshot = Some(after);
+    }
+
+    fn after_frame(&mut self, chip_8: &Chip8) {
+        let after = Snapshot::new(chip_8);
+        if let (Some(last), Some(before)) = (&mut self.last, &self.snapshot) {
+            for (name, value) in before.changes(&after) {
+                match last.changes.iter_mut().find(|(change, _)| *change == name) {
+                    Some(change) => change.1 = value,
+                    None => last.changes.push((name, value)),
+                }
+            }
+        }
+        self.snapshot = Some(after);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use std::sync::{Arc, Mutex};
+
+    // A writer whose output can be inspected after it is moved into a tracer.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    const ROM: [u8; 10] = [0x6A, 0x12, 0xA3, 0x45, 0xFA, 0x15, 0x22, 0x08, 0x12, 0x08];
+
+    #[test]
+    fn test_text() {
+        let buffer = SharedBuffer::default();
+        let mut tracer = Tracer::with_writer(2, buffer.clone(), TraceFormat::Text);
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&ROM, false);
+        chip_8.run_frame_observed(4, &mut tracer).unwrap();
+        chip_8.step_observed(&mut tracer).unwrap();
+        let entries: Vec<_> = tracer.entries().map(|entry| entry.address).collect();
+        assert_eq!(entries, vec![0x206, 0x208]);
+        tracer.flush().unwrap();
+
+        assert_eq!(
+            buffer.text(),
+            "200 6A12 LD VA, 0x12 | VA=12\n\
+             202 A345 LD I, 0x345 | I=345\n\
+             204 FA15 LD DT, VA | DT=12\n\
+             206 2208 CALL 0x208 | SP=1 DT=11\n\
+             208 1208 JP 0x208\n"
+        );
+        let entries: Vec<_> = tracer.entries().map(|entry| entry.address).collect();
+        assert_eq!(entries, vec![0x206, 0x208]);
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let entry = TraceEntry {
+            address: 0x202,
+            opcode: 0xA345,
+            mnemonic: String::from("LD I, 0x345"),
+            changes: vec![("I", 0x345)],
+        };
+        let mut out = Vec::new();
+        entry.write(&mut out, TraceFormat::JsonLines).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"address\":514,\"opcode\":41797,\"mnemonic\":\"LD I, 0x345\",\"changes\":{\"I\":837}}\n"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut tracer = Tracer::new(4);
+        let mut chip_8 = Chip8::new();
+        chip_8.load_rom(&[0x50, 0x01], false);
+        assert_eq!(
+            chip_8.step_observed(&mut tracer),
+            Err(Error::UnknownOpcode(0x5001))
+        );
+        assert_eq!(tracer.entries().count(), 0);
+    }
+}